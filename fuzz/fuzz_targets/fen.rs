@@ -0,0 +1,29 @@
+#![no_main]
+
+use chess::{
+    board::{Board, FenMode},
+    debug::random_walk,
+};
+use libfuzzer_sys::fuzz_target;
+
+// Parsing any string must not panic, and every valid position must satisfy the invariants
+// for a few moves
+fuzz_target!(|data: &[u8]| {
+    let Ok(fen) = std::str::from_utf8(data) else {
+        return;
+    };
+
+    let _ = Board::from_fen_with_mode(fen, FenMode::Strict);
+
+    let Ok(board) = Board::from_fen(fen) else {
+        return;
+    };
+    if board.validate().is_err() {
+        return;
+    }
+
+    let choices = data.iter().rev().take(8).map(|&b| b as u64);
+    if let Err(e) = random_walk(&board, choices) {
+        panic!("{}", e);
+    }
+});
@@ -0,0 +1,27 @@
+#![no_main]
+
+use chess::{board::Board, debug::random_walk};
+use libfuzzer_sys::fuzz_target;
+
+const START_POSITIONS: [&str; 4] = [
+    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+    "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+    "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+    "n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1",
+];
+
+// The first byte picks the start position and every other byte picks the next move. A crash
+// reports the first failing position of the walk. The choices are not shrunk, so a shorter
+// reproducer needs libFuzzer's -minimize_crash.
+fuzz_target!(|data: &[u8]| {
+    let Some((&first, choices)) = data.split_first() else {
+        return;
+    };
+
+    let fen = START_POSITIONS[first as usize % START_POSITIONS.len()];
+    let board = Board::from_fen(fen).unwrap();
+
+    if let Err(e) = random_walk(&board, choices.iter().map(|&b| b as u64)) {
+        panic!("{}", e);
+    }
+});
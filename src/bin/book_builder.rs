@@ -0,0 +1,88 @@
+use std::{fs::File, io::BufReader, process::exit};
+
+use chess::{book::BookBuilder, pgn::PgnReader};
+
+const USAGE: &str = "Usage: book-builder [--max-plies N] [--min-games N] [--min-elo N] -o <book.bin> <games.pgn>...";
+
+fn parse_value<T: std::str::FromStr>(args: &mut impl Iterator<Item = String>, name: &str) -> T {
+    match args.next().and_then(|v| v.parse().ok()) {
+        Some(value) => value,
+        None => {
+            eprintln!("Invalid or missing value for {}\n{}", name, USAGE);
+            exit(1);
+        }
+    }
+}
+
+fn main() {
+    let mut builder = BookBuilder::new();
+    let mut output = None;
+    let mut inputs = Vec::new();
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" | "--output" => output = Some(parse_value::<String>(&mut args, &arg)),
+            "--max-plies" => builder.max_plies = parse_value(&mut args, &arg),
+            "--min-games" => builder.min_games = parse_value(&mut args, &arg),
+            "--min-elo" => builder.min_elo = parse_value(&mut args, &arg),
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            _ => inputs.push(arg),
+        }
+    }
+
+    let output = match output {
+        Some(output) if !inputs.is_empty() => output,
+        _ => {
+            eprintln!("{}", USAGE);
+            exit(1);
+        }
+    };
+
+    let (mut used, mut skipped) = (0, 0);
+
+    for input in &inputs {
+        let file = match File::open(input) {
+            Ok(file) => file,
+            Err(e) => {
+                eprintln!("Could not open '{}': {}", input, e);
+                exit(1);
+            }
+        };
+
+        for game in PgnReader::new(BufReader::new(file)) {
+            let game = match game {
+                Ok(game) => game,
+                Err(e) => {
+                    eprintln!("Error reading '{}': {}", input, e);
+                    exit(1);
+                }
+            };
+
+            match builder.add_game(&game) {
+                Ok(true) => used += 1,
+                Ok(false) => skipped += 1,
+                Err(e) => {
+                    eprintln!("Skipping game: {}", e);
+                    skipped += 1;
+                }
+            }
+        }
+    }
+
+    let book = builder.build();
+    if let Err(e) = book.save(&output) {
+        eprintln!("Could not write '{}': {}", output, e);
+        exit(1);
+    }
+
+    println!(
+        "Games used: {}, skipped: {}, book entries: {}",
+        used,
+        skipped,
+        book.len()
+    );
+}
@@ -0,0 +1,182 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, BufWriter, Write},
+    process::exit,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc,
+    },
+    thread,
+    time::Instant,
+};
+
+use chess::{
+    book::Book,
+    datagen::{self, DataReader, DatagenOptions},
+    rng::Rng,
+    tt::TranspositionTable,
+};
+
+const USAGE: &str = "Usage: datagen [options]
+       datagen --dump <data.bin>
+
+Options:
+    --games <n>          Number of self-play games [default: 100]
+    --threads <n>        Games played in parallel [default: 1]
+    --nodes <n>          Nodes searched per move [default: 5000]
+    --random-plies <n>   Random moves after the book moves [default: 8]
+    --book <file>        Polyglot book for the openings
+    --seed <n>           Seed for the openings [default: from the clock]
+    --text <file>        Append positions as text, `<fen> | <score> | <result>`
+    --binary <file>      Append positions in the packed binary format
+    --dump <file>        Print a binary file as text";
+
+/// Memory per thread for the transposition table, in megabytes.
+const HASH_MB: usize = 16;
+
+fn parse_value<T: std::str::FromStr>(args: &mut impl Iterator<Item = String>, name: &str) -> T {
+    match args.next().and_then(|v| v.parse().ok()) {
+        Some(value) => value,
+        None => {
+            eprintln!("Invalid or missing value for {}\n{}", name, USAGE);
+            exit(1);
+        }
+    }
+}
+
+fn open_output(path: &str) -> BufWriter<File> {
+    match OpenOptions::new().create(true).append(true).open(path) {
+        Ok(file) => BufWriter::new(file),
+        Err(e) => {
+            eprintln!("Could not open '{}': {}", path, e);
+            exit(1);
+        }
+    }
+}
+
+fn dump(path: &str) -> io::Result<()> {
+    let mut out = io::stdout().lock();
+    for point in DataReader::new(io::BufReader::new(File::open(path)?)) {
+        writeln!(out, "{}", point?)?;
+    }
+    Ok(())
+}
+
+fn main() {
+    let mut options = DatagenOptions::default();
+    let mut games = 100;
+    let mut threads = 1;
+    let mut book_path = None;
+    let mut seed = None;
+    let mut text_path = None;
+    let mut binary_path = None;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--games" => games = parse_value(&mut args, &arg),
+            "--threads" => threads = parse_value::<usize>(&mut args, &arg).max(1),
+            "--nodes" => options.nodes = parse_value(&mut args, &arg),
+            "--random-plies" => options.random_plies = parse_value(&mut args, &arg),
+            "--book" => book_path = Some(parse_value::<String>(&mut args, &arg)),
+            "--seed" => seed = Some(parse_value::<u64>(&mut args, &arg)),
+            "--text" => text_path = Some(parse_value::<String>(&mut args, &arg)),
+            "--binary" => binary_path = Some(parse_value::<String>(&mut args, &arg)),
+            "--dump" => {
+                let path = parse_value::<String>(&mut args, &arg);
+                if let Err(e) = dump(&path) {
+                    eprintln!("Could not read '{}': {}", path, e);
+                    exit(1);
+                }
+                return;
+            }
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            _ => {
+                eprintln!("Unknown argument '{}'\n{}", arg, USAGE);
+                exit(1);
+            }
+        }
+    }
+
+    if text_path.is_none() && binary_path.is_none() {
+        eprintln!("No output given\n{}", USAGE);
+        exit(1);
+    }
+
+    let book = book_path.map(|path| {
+        Book::open(&path).unwrap_or_else(|e| {
+            eprintln!("Could not open '{}': {}", path, e);
+            exit(1);
+        })
+    });
+
+    let mut text = text_path.as_deref().map(open_output);
+    let mut binary = binary_path.as_deref().map(open_output);
+
+    let seed = seed.unwrap_or_else(|| Rng::from_time().next_u64());
+    let started = AtomicUsize::new(0);
+    let start = Instant::now();
+
+    thread::scope(|s| {
+        let (sender, receiver) = mpsc::channel();
+
+        for id in 0..threads {
+            let sender = sender.clone();
+            let (options, book, started) = (&options, book.as_ref(), &started);
+
+            s.spawn(move || {
+                let tt = TranspositionTable::new(HASH_MB);
+                let mut rng = Rng::new(seed ^ id as u64);
+
+                while started.fetch_add(1, Ordering::Relaxed) < games {
+                    let points = datagen::play_game(options, &tt, book, &mut rng);
+                    if sender.send(points).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(sender);
+
+        let mut positions = 0;
+        for (game, points) in receiver.into_iter().enumerate() {
+            positions += points.len();
+
+            let result = points.iter().try_for_each(|point| {
+                if let Some(out) = &mut text {
+                    writeln!(out, "{}", point)?;
+                }
+                if let Some(out) = &mut binary {
+                    let bytes = point
+                        .to_bytes()
+                        .map_err(|e| io::Error::other(e.to_string()))?;
+                    out.write_all(&bytes)?;
+                }
+                Ok::<_, io::Error>(())
+            });
+            if let Err(e) = result {
+                eprintln!("Could not write positions: {}", e);
+                exit(1);
+            }
+
+            let seconds = start.elapsed().as_secs_f64();
+            eprintln!(
+                "Games: {}/{}, positions: {}, positions/s: {:.0}",
+                game + 1,
+                games,
+                positions,
+                positions as f64 / seconds.max(0.001)
+            );
+        }
+    });
+
+    for out in [text.as_mut(), binary.as_mut()].into_iter().flatten() {
+        if let Err(e) = out.flush() {
+            eprintln!("Could not write positions: {}", e);
+            exit(1);
+        }
+    }
+}
@@ -0,0 +1,520 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::Path,
+    process::exit,
+    time::{Duration, Instant},
+};
+
+use chess::{
+    board::Board,
+    color::Color,
+    eval::MATE,
+    game::{Adjudication, DrawRule, Game, ResignRule, TablebaseRule, Termination},
+    moves::Move,
+    pgn::{GameResult, PgnReader},
+    sprt::{MatchScore, Sprt, SprtStatus},
+    uci::{
+        client::{UciClient, UciClientError},
+        SearchParams, UciScore,
+    },
+};
+
+const USAGE: &str = "Usage: match --engine1 <command> --engine2 <command> [options]
+
+Options:
+    --option1 <name=value>      UCI option for engine 1, may be repeated
+    --option2 <name=value>      UCI option for engine 2, may be repeated
+    --openings <file>           Opening positions, EPD or PGN (.pgn)
+    --games <n>                 Number of games, played in pairs with colors swapped [default: 2]
+    --tc <seconds[+increment]>  Time control per game [default: 10+0.1]
+    --pgn <file>                Append the games to this PGN file
+    --sprt <elo0> <elo1> <alpha> <beta>
+                                Stop once the SPRT accepts one of the hypotheses
+    --draw <move> <count> <cp>  Draw when both engines' scores stay within <cp> of zero
+                                for <count> moves each, from move <move> on
+    --resign <count> <cp>       Win when both engines agree for <count> moves each that
+                                one side is ahead by at least <cp>
+    --max-moves <n>             Draw after <n> moves
+    --tb-adjudication <pieces> <cp>
+                                Trust the score of an engine that reports tablebase hits with
+                                at most <pieces> pieces on the board: draw at 0, win at <cp>
+    --insufficient-material     Draw when neither side has the material to mate";
+
+/// Grace time for engines that exceed their clock before they are considered hung.
+const TIMEOUT_MARGIN: Duration = Duration::from_secs(5);
+
+fn parse_value<T: std::str::FromStr>(args: &mut impl Iterator<Item = String>, name: &str) -> T {
+    match args.next().and_then(|v| v.parse().ok()) {
+        Some(value) => value,
+        None => {
+            eprintln!("Invalid or missing value for {}\n{}", name, USAGE);
+            exit(1);
+        }
+    }
+}
+
+/// Parses a time control like `40` or `10+0.1` into milliseconds.
+fn parse_time_control(tc: &str) -> Option<(u32, u32)> {
+    let (base, increment) = tc.split_once('+').unwrap_or((tc, "0"));
+    let seconds = |s: &str| s.parse::<f64>().ok().map(|s| (s * 1000.0) as u32);
+
+    Some((seconds(base)?, seconds(increment)?))
+}
+
+/// An external engine together with the command and options it was started with, so that it
+/// can be restarted after a crash.
+struct Player {
+    command: Vec<String>,
+    options: Vec<(String, String)>,
+    client: UciClient,
+}
+
+impl Player {
+    fn start(command: &str, options: &[(String, String)]) -> Result<Self, UciClientError> {
+        let command: Vec<String> = command.split_whitespace().map(|s| s.to_owned()).collect();
+        let program = command
+            .first()
+            .ok_or_else(|| UciClientError::new("Empty engine command"))?;
+
+        let mut client = UciClient::spawn(program, &command[1..], TIMEOUT_MARGIN)?;
+        for (name, value) in options {
+            client.set_option(name, Some(value))?;
+        }
+        client.is_ready(TIMEOUT_MARGIN)?;
+
+        Ok(Self {
+            command,
+            options: options.to_vec(),
+            client,
+        })
+    }
+
+    fn restart(&mut self) -> Result<(), UciClientError> {
+        *self = Self::start(&self.command.join(" "), &self.options)?;
+        Ok(())
+    }
+
+    fn name(&self) -> &str {
+        &self.client.name
+    }
+}
+
+/// Loads opening positions from an EPD file, or from a PGN file together with their moves.
+fn load_openings(path: &str) -> Result<Vec<(Board, Vec<Move>)>, String> {
+    let file = File::open(path).map_err(|e| format!("Could not open '{}': {}", path, e))?;
+    let is_pgn = Path::new(path).extension().is_some_and(|e| e == "pgn");
+
+    read_openings(BufReader::new(file), is_pgn)
+}
+
+fn read_openings(reader: impl BufRead, is_pgn: bool) -> Result<Vec<(Board, Vec<Move>)>, String> {
+    let mut openings = Vec::new();
+
+    if is_pgn {
+        for game in PgnReader::new(reader) {
+            let game = game.map_err(|e| e.to_string())?;
+            let board = game.start_position().map_err(|e| e.to_string())?;
+            let moves = game.replay(usize::MAX).map_err(|e| e.to_string())?;
+
+            openings.push((board, moves.into_iter().map(|(_, mv)| mv).collect()));
+        }
+    } else {
+        for line in reader.lines() {
+            let line = line.map_err(|e| e.to_string())?;
+            let fields: Vec<&str> = line.split_whitespace().take(4).collect();
+            if fields.len() < 4 {
+                continue;
+            }
+
+            let fen = format!("{} 0 1", fields.join(" "));
+            let board = Board::from_fen(&fen).map_err(|e| e.to_string())?;
+            openings.push((board, Vec::new()));
+        }
+    }
+
+    Ok(openings)
+}
+
+/// The opening of game `round` and whether the first engine plays white. Each opening is
+/// played twice, with the first engine as white in the first game.
+fn pairing(round: usize, openings: usize) -> (usize, bool) {
+    ((round / 2) % openings, round.is_multiple_of(2))
+}
+
+/// Score of the last iteration from white's point of view, with mates as large scores.
+fn white_score(score: UciScore, color: Color) -> i32 {
+    let score = match score {
+        UciScore::Cp(cp) => cp,
+        UciScore::Mate(moves) if moves > 0 => MATE - moves,
+        UciScore::Mate(moves) => -MATE - moves,
+    };
+
+    match color {
+        Color::White => score,
+        Color::Black => -score,
+    }
+}
+
+/// Plays one game between `white` and `black` from `opening`.
+fn play_game(
+    white: &mut Player,
+    black: &mut Player,
+    opening: &(Board, Vec<Move>),
+    time_control: (u32, u32),
+    adjudication: &Adjudication,
+) -> Game {
+    let (base, increment) = time_control;
+    let mut game = Game::new(opening.0.clone());
+    for &mv in &opening.1 {
+        game.push(mv, None);
+    }
+
+    for player in [&mut *white, &mut *black] {
+        if player.client.new_game().is_err() {
+            let _ = player.restart();
+        }
+    }
+
+    let mut clocks = [base as i64, base as i64];
+
+    while game.outcome().is_none() {
+        let color = game.board().color_to_move();
+        let (player, side) = match color {
+            Color::White => (&mut *white, 0),
+            Color::Black => (&mut *black, 1),
+        };
+
+        let mut params = SearchParams::new();
+        params.wtime = clocks[0].max(1) as u32;
+        params.btime = clocks[1].max(1) as u32;
+        params.winc = increment.min(u16::MAX as u32) as u16;
+        params.binc = params.winc;
+
+        let start = Instant::now();
+        let timeout = Duration::from_millis(clocks[side] as u64) + TIMEOUT_MARGIN;
+        let output = player
+            .client
+            .set_position(game.start(), game.moves())
+            .and_then(|_| player.client.go(&params, timeout));
+        let elapsed = start.elapsed().as_millis() as i64;
+
+        let output = match output {
+            Ok(output) => output,
+            Err(e) if e.illegal_move().is_some() => {
+                eprintln!("{}: {}", player.name(), e);
+                game.finish(GameResult::win_for(!color), Termination::IllegalMove);
+                break;
+            }
+            Err(e) => {
+                eprintln!("{}: {}", player.name(), e);
+                game.finish(GameResult::win_for(!color), Termination::Disconnect);
+                if let Err(e) = player.restart() {
+                    eprintln!("Could not restart engine: {}", e);
+                    exit(1);
+                }
+                break;
+            }
+        };
+
+        clocks[side] -= elapsed;
+        if clocks[side] < 0 {
+            game.finish(GameResult::win_for(!color), Termination::TimeForfeit);
+            break;
+        }
+        clocks[side] += increment as i64;
+
+        // The client only lets the null move through
+        let legal = game.board().legal_moves().contains(&output.best_move);
+        if !legal {
+            game.finish(GameResult::win_for(!color), Termination::IllegalMove);
+            break;
+        }
+
+        let last = output
+            .infos
+            .iter()
+            .rev()
+            .filter(|info| info.multipv.unwrap_or(1) == 1)
+            .find(|info| info.score.is_some());
+        let score = last
+            .and_then(|info| info.score)
+            .map(|score| white_score(score, color));
+        game.push(output.best_move, score);
+        if last.is_some_and(|info| info.tbhits.is_some_and(|hits| hits > 0)) {
+            game.mark_tablebase_score();
+        }
+
+        if game.outcome().is_none() {
+            if let Some(result) = adjudication.adjudicate(&game) {
+                game.finish(result, Termination::Adjudication);
+            }
+        }
+    }
+
+    game
+}
+
+fn main() {
+    let mut commands = [None, None];
+    let mut options: [Vec<(String, String)>; 2] = [Vec::new(), Vec::new()];
+    let mut openings_path = None;
+    let mut games = 2;
+    let mut time_control = (10_000, 100);
+    let mut pgn_path = None;
+    let mut sprt = None;
+    let mut adjudication = Adjudication::default();
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--engine1" => commands[0] = Some(parse_value::<String>(&mut args, &arg)),
+            "--engine2" => commands[1] = Some(parse_value::<String>(&mut args, &arg)),
+            "--option1" | "--option2" => {
+                let option = parse_value::<String>(&mut args, &arg);
+                let Some((name, value)) = option.split_once('=') else {
+                    eprintln!("Options are given as name=value\n{}", USAGE);
+                    exit(1);
+                };
+                let index = if arg == "--option1" { 0 } else { 1 };
+                options[index].push((name.to_owned(), value.to_owned()));
+            }
+            "--openings" => openings_path = Some(parse_value::<String>(&mut args, &arg)),
+            "--games" => games = parse_value(&mut args, &arg),
+            "--tc" => {
+                let tc = parse_value::<String>(&mut args, &arg);
+                time_control = parse_time_control(&tc).unwrap_or_else(|| {
+                    eprintln!("Invalid time control '{}'\n{}", tc, USAGE);
+                    exit(1);
+                });
+            }
+            "--pgn" => pgn_path = Some(parse_value::<String>(&mut args, &arg)),
+            "--sprt" => {
+                sprt = Some(Sprt {
+                    elo0: parse_value(&mut args, &arg),
+                    elo1: parse_value(&mut args, &arg),
+                    alpha: parse_value(&mut args, &arg),
+                    beta: parse_value(&mut args, &arg),
+                })
+            }
+            "--draw" => {
+                adjudication.draw = Some(DrawRule {
+                    move_number: parse_value(&mut args, &arg),
+                    move_count: parse_value(&mut args, &arg),
+                    score: parse_value(&mut args, &arg),
+                })
+            }
+            "--resign" => {
+                adjudication.resign = Some(ResignRule {
+                    move_count: parse_value(&mut args, &arg),
+                    score: parse_value(&mut args, &arg),
+                })
+            }
+            "--max-moves" => adjudication.max_moves = Some(parse_value(&mut args, &arg)),
+            "--tb-adjudication" => {
+                adjudication.tablebase = Some(TablebaseRule {
+                    max_pieces: parse_value(&mut args, &arg),
+                    score: parse_value(&mut args, &arg),
+                })
+            }
+            "--insufficient-material" => adjudication.insufficient_material = true,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            _ => {
+                eprintln!("Unknown argument '{}'\n{}", arg, USAGE);
+                exit(1);
+            }
+        }
+    }
+
+    let [Some(command1), Some(command2)] = &commands else {
+        eprintln!("{}", USAGE);
+        exit(1);
+    };
+
+    let openings = match &openings_path {
+        Some(path) => load_openings(path).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            exit(1);
+        }),
+        None => vec![(Board::default(), Vec::new())],
+    };
+    if openings.is_empty() {
+        eprintln!("No openings found");
+        exit(1);
+    }
+
+    let commands = [command1, command2];
+    let mut players = [0, 1].map(|i| {
+        Player::start(commands[i], &options[i]).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            exit(1);
+        })
+    });
+
+    let mut pgn = pgn_path.map(|path| {
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .unwrap_or_else(|e| {
+                eprintln!("Could not open '{}': {}", path, e);
+                exit(1);
+            })
+    });
+
+    let mut score = MatchScore::default();
+
+    for round in 0..games {
+        let (opening, first_is_white) = pairing(round, openings.len());
+        let opening = &openings[opening];
+        let [first, second] = &mut players;
+        let (white, black) = if first_is_white {
+            (first, second)
+        } else {
+            (second, first)
+        };
+
+        let game = play_game(white, black, opening, time_control, &adjudication);
+        let (result, termination) = game.outcome().expect("game is over");
+
+        match (result, first_is_white) {
+            (GameResult::Draw, _) => score.draws += 1,
+            (GameResult::WhiteWins, true) | (GameResult::BlackWins, false) => score.wins += 1,
+            _ => score.losses += 1,
+        }
+
+        if let Some(file) = &mut pgn {
+            let tags = [
+                ("Event", "Engine match".to_owned()),
+                ("Site", "?".to_owned()),
+                ("Date", "????.??.??".to_owned()),
+                ("Round", (round + 1).to_string()),
+                ("White", white.name().to_owned()),
+                ("Black", black.name().to_owned()),
+                (
+                    "TimeControl",
+                    format!(
+                        "{}+{}",
+                        time_control.0 as f64 / 1000.0,
+                        time_control.1 as f64 / 1000.0
+                    ),
+                ),
+            ]
+            .map(|(name, value)| (name.to_owned(), value));
+
+            if let Err(e) = writeln!(file, "{}", game.to_pgn(&tags)) {
+                eprintln!("Could not write PGN: {}", e);
+                exit(1);
+            }
+        }
+
+        println!(
+            "Game {} ({} vs {}): {} {{{}}}",
+            round + 1,
+            white.name(),
+            black.name(),
+            result,
+            termination
+        );
+        println!(
+            "Score of {} vs {}: {}",
+            players[0].name(),
+            players[1].name(),
+            score
+        );
+        if let Some((elo, margin)) = score.elo() {
+            println!("Elo difference: {:.1} +/- {:.1}", elo, margin);
+        }
+
+        if let Some(sprt) = &sprt {
+            let (lower, upper) = sprt.bounds();
+            let status = sprt.status(&score);
+            println!(
+                "SPRT: llr {:.2} ({:.2}, {:.2}) [{:.1}, {:.1}]",
+                sprt.llr(&score),
+                lower,
+                upper,
+                sprt.elo0,
+                sprt.elo1
+            );
+
+            match status {
+                SprtStatus::AcceptH0 => println!("H0 accepted"),
+                SprtStatus::AcceptH1 => println!("H1 accepted"),
+                SprtStatus::Continue => continue,
+            }
+            break;
+        }
+    }
+
+    for player in &mut players {
+        let _ = player.client.quit(TIMEOUT_MARGIN);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn time_control() {
+        assert_eq!(parse_time_control("40"), Some((40_000, 0)));
+        assert_eq!(parse_time_control("10+0.1"), Some((10_000, 100)));
+        assert_eq!(parse_time_control("0.5+0"), Some((500, 0)));
+        assert_eq!(parse_time_control("10+"), None);
+        assert_eq!(parse_time_control("ten"), None);
+    }
+
+    #[test]
+    fn pairs_share_an_opening() {
+        let games: Vec<_> = (0..6).map(|round| pairing(round, 2)).collect();
+        assert_eq!(
+            games,
+            [
+                (0, true),
+                (0, false),
+                (1, true),
+                (1, false),
+                (0, true),
+                (0, false)
+            ]
+        );
+    }
+
+    #[test]
+    fn epd_openings() {
+        let epd = "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 id \"e4\";\n\
+                   \n\
+                   too short\n\
+                   4k3/8/8/8/8/8/4P3/4K3 w - -\n";
+        let openings = read_openings(epd.as_bytes(), false).unwrap();
+
+        assert_eq!(openings.len(), 2);
+        assert_eq!(
+            openings[0].0.fen(),
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1"
+        );
+        assert!(openings.iter().all(|(_, moves)| moves.is_empty()));
+
+        assert!(read_openings("9/8/8/8/8/8/8/8 w - -".as_bytes(), false).is_err());
+    }
+
+    #[test]
+    fn pgn_openings() {
+        let pgn = "[Event \"?\"]\n\n1. e4 e5 2. Nf3 *\n\n\
+                   [FEN \"4k3/8/8/8/8/8/4P3/4K3 w - - 0 1\"]\n\n1. e3 *\n";
+        let openings = read_openings(pgn.as_bytes(), true).unwrap();
+
+        assert_eq!(openings.len(), 2);
+        assert_eq!(openings[0].0, Board::default());
+        let moves: Vec<String> = openings[0].1.iter().map(|mv| mv.to_string()).collect();
+        assert_eq!(moves, ["e2e4", "e7e5", "g1f3"]);
+        assert_eq!(openings[1].0.fen(), "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1");
+        assert_eq!(openings[1].1.len(), 1);
+
+        assert!(read_openings("1. e4 e4 *\n".as_bytes(), true).is_err());
+    }
+}
@@ -0,0 +1,601 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, BufRead, BufReader, Write},
+    process::exit,
+    time::{Duration, Instant},
+};
+
+use chess::{
+    board::Board,
+    debug::{perft_parallel, perft_parallel_divide, PerftTable},
+    uci::client::UciClient,
+};
+
+const USAGE: &str = "Usage: perft-suite [options] <suite>
+
+Runs perft on every position of a suite file with lines `<fen>, <nodes at depth 1>, ...`.
+
+Options:
+    --depth <n>        Maximum depth [default: all depths listed]
+    --threads <n>      Threads per perft [default: 1]
+    --hash <mb>        Node count table size, 0 for none [default: 0]
+    --json <file>      Write a JSON summary, `-` for stdout
+    --engine <path>    Reference UCI engine to divide against on mismatch
+    --divides <file>   Expected divides to compare against on mismatch, with lines
+                       `<fen>, <depth>, <move>: <nodes>, <move>: <nodes>, ...`";
+
+/// Time the reference engine gets for a single divide.
+const ENGINE_TIMEOUT: Duration = Duration::from_secs(600);
+
+fn parse_value<T: std::str::FromStr>(args: &mut impl Iterator<Item = String>, name: &str) -> T {
+    match args.next().and_then(|v| v.parse().ok()) {
+        Some(value) => value,
+        None => {
+            eprintln!("Invalid or missing value for {}\n{}", name, USAGE);
+            exit(1);
+        }
+    }
+}
+
+fn read_lines(path: &str) -> Vec<String> {
+    let lines = File::open(path).and_then(|f| BufReader::new(f).lines().collect());
+    lines.unwrap_or_else(|e: io::Error| {
+        eprintln!("Could not read '{}': {}", path, e);
+        exit(1);
+    })
+}
+
+/// FEN without the move counters, which do not change perft results.
+fn position_key(board: &Board) -> String {
+    let fen = board.fen();
+    fen.split_whitespace().take(4).collect::<Vec<_>>().join(" ")
+}
+
+/// Source of the correct node counts of every move.
+enum Reference {
+    Engine(UciClient),
+    Divides(HashMap<(String, usize), Vec<(String, u64)>>),
+}
+
+impl Reference {
+    fn read_divides(path: &str) -> Self {
+        Self::parse_divides(&read_lines(path)).unwrap_or_else(|line| {
+            eprintln!("Invalid divide on line {} of '{}'", line, path);
+            exit(1);
+        })
+    }
+
+    /// Parses divide lines, or returns the number of the first invalid line.
+    fn parse_divides(lines: &[String]) -> Result<Self, usize> {
+        let mut divides = HashMap::new();
+
+        for (i, line) in lines.iter().enumerate() {
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut fields = line.split(',');
+            let board = fields.next().and_then(|fen| Board::from_fen(fen).ok());
+            let depth = fields.next().and_then(|d| d.trim().parse().ok());
+            let moves: Option<Vec<(String, u64)>> = fields
+                .map(|field| {
+                    let (mv, nodes) = field.split_once(':')?;
+                    Some((mv.trim().to_owned(), nodes.trim().parse().ok()?))
+                })
+                .collect();
+
+            match (board, depth, moves) {
+                (Some(board), Some(depth), Some(moves)) => {
+                    divides.insert((position_key(&board), depth), moves);
+                }
+                _ => return Err(i + 1),
+            }
+        }
+
+        Ok(Reference::Divides(divides))
+    }
+
+    fn divide(&mut self, board: &Board, depth: usize) -> Option<Vec<(String, u64)>> {
+        match self {
+            Reference::Engine(engine) => {
+                let result = engine
+                    .set_position(board, &[])
+                    .and_then(|_| engine.perft(depth, ENGINE_TIMEOUT));
+                result
+                    .map_err(|e| eprintln!("Reference engine failed: {}", e))
+                    .ok()
+            }
+            Reference::Divides(divides) => divides.get(&(position_key(board), depth)).cloned(),
+        }
+    }
+}
+
+/// The deepest position where our divide differs from the reference.
+struct Mismatch {
+    fen: String,
+    depth: usize,
+    /// Moves leading from the suite position to `fen`
+    path: Vec<String>,
+    /// Moves only the reference generates
+    missing: Vec<String>,
+    /// Moves only we generate
+    extra: Vec<String>,
+    /// Moves with our and the reference node counts
+    differing: Vec<(String, u64, u64)>,
+}
+
+/// Follows the first move whose node count differs from the reference down to the position
+/// where the generated moves differ, or as deep as the reference goes.
+fn find_mismatch(
+    board: &Board,
+    depth: usize,
+    reference: &mut Reference,
+    threads: usize,
+    table: Option<&PerftTable>,
+) -> Option<Mismatch> {
+    let mut board = board.clone();
+    let mut depth = depth;
+    let mut path = Vec::new();
+    let mut mismatch = None;
+
+    while depth > 0 {
+        let Some(theirs) = reference.divide(&board, depth) else {
+            break;
+        };
+        let ours: Vec<(String, u64, _)> = perft_parallel_divide(&board, depth, threads, table)
+            .into_iter()
+            .map(|(mv, nodes)| (mv.to_string(), nodes, mv))
+            .collect();
+
+        let find = |mv: &str| theirs.iter().find(|(m, _)| m == mv).map(|&(_, n)| n);
+        let missing: Vec<String> = theirs
+            .iter()
+            .filter(|(mv, _)| !ours.iter().any(|(m, ..)| m == mv))
+            .map(|(mv, _)| mv.clone())
+            .collect();
+        let extra: Vec<String> = ours
+            .iter()
+            .filter(|(mv, ..)| find(mv).is_none())
+            .map(|(mv, ..)| mv.clone())
+            .collect();
+        let differing: Vec<(String, u64, u64)> = ours
+            .iter()
+            .filter_map(|(mv, nodes, _)| match find(mv) {
+                Some(n) if n != *nodes => Some((mv.clone(), *nodes, n)),
+                _ => None,
+            })
+            .collect();
+
+        // Once the move lists differ, the culprit is in this position
+        let next = match (&missing[..], &extra[..], differing.first()) {
+            ([], [], Some((next, ..))) => ours.iter().find(|(mv, ..)| mv == next).map(|m| m.2),
+            _ => None,
+        };
+
+        let current = Mismatch {
+            fen: board.fen(),
+            depth,
+            path: path.clone(),
+            missing,
+            extra,
+            differing,
+        };
+        mismatch = Some(current);
+
+        match next {
+            Some(mv) => {
+                path.push(mv.to_string());
+                board = board.do_move(mv);
+                depth -= 1;
+            }
+            None => break,
+        }
+    }
+
+    mismatch
+}
+
+fn print_mismatch(m: &Mismatch) {
+    println!("  First difference at depth {}: {}", m.depth, m.fen);
+    if !m.path.is_empty() {
+        println!("  After moves: {}", m.path.join(" "));
+    }
+    if !m.missing.is_empty() {
+        println!("  Missing moves: {}", m.missing.join(" "));
+    }
+    if !m.extra.is_empty() {
+        println!("  Extra moves: {}", m.extra.join(" "));
+    }
+    for (mv, ours, theirs) in &m.differing {
+        println!("  {}: {} (expected {})", mv, ours, theirs);
+    }
+}
+
+fn json_string(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn json_strings(strings: &[String]) -> String {
+    let strings: Vec<String> = strings.iter().map(|s| json_string(s)).collect();
+    format!("[{}]", strings.join(", "))
+}
+
+struct PositionResult {
+    fen: String,
+    depth: usize,
+    nodes: u64,
+    expected: u64,
+    seconds: f64,
+    mismatch: Option<Mismatch>,
+}
+
+impl PositionResult {
+    fn passed(&self) -> bool {
+        self.nodes == self.expected
+    }
+
+    fn to_json(&self) -> String {
+        let mismatch = match &self.mismatch {
+            Some(m) => {
+                let differing: Vec<String> = m
+                    .differing
+                    .iter()
+                    .map(|(mv, ours, theirs)| {
+                        format!(
+                            "{{\"move\": {}, \"nodes\": {}, \"expected\": {}}}",
+                            json_string(mv),
+                            ours,
+                            theirs
+                        )
+                    })
+                    .collect();
+                format!(
+                    "{{\"fen\": {}, \"depth\": {}, \"path\": {}, \"missing\": {}, \
+                     \"extra\": {}, \"differing\": [{}]}}",
+                    json_string(&m.fen),
+                    m.depth,
+                    json_strings(&m.path),
+                    json_strings(&m.missing),
+                    json_strings(&m.extra),
+                    differing.join(", ")
+                )
+            }
+            None => "null".to_owned(),
+        };
+
+        format!(
+            "{{\"fen\": {}, \"depth\": {}, \"nodes\": {}, \"expected\": {}, \"seconds\": {:.3}, \
+             \"passed\": {}, \"mismatch\": {}}}",
+            json_string(&self.fen),
+            self.depth,
+            self.nodes,
+            self.expected,
+            self.seconds,
+            self.passed(),
+            mismatch
+        )
+    }
+}
+
+fn main() {
+    let mut suite_path = None;
+    let mut max_depth = usize::MAX;
+    let mut threads = 1;
+    let mut hash = 0;
+    let mut json_path = None;
+    let mut engine_path = None;
+    let mut divides_path = None;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--depth" => max_depth = parse_value(&mut args, &arg),
+            "--threads" => threads = parse_value::<usize>(&mut args, &arg).max(1),
+            "--hash" => hash = parse_value(&mut args, &arg),
+            "--json" => json_path = Some(parse_value::<String>(&mut args, &arg)),
+            "--engine" => engine_path = Some(parse_value::<String>(&mut args, &arg)),
+            "--divides" => divides_path = Some(parse_value::<String>(&mut args, &arg)),
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            _ if !arg.starts_with('-') && suite_path.is_none() => suite_path = Some(arg),
+            _ => {
+                eprintln!("Unknown argument '{}'\n{}", arg, USAGE);
+                exit(1);
+            }
+        }
+    }
+
+    let Some(suite_path) = suite_path else {
+        eprintln!("No suite given\n{}", USAGE);
+        exit(1);
+    };
+
+    let mut reference = match (engine_path, divides_path) {
+        (Some(path), _) => {
+            match UciClient::spawn(&path, [] as [&str; 0], Duration::from_secs(10)) {
+                Ok(engine) => Some(Reference::Engine(engine)),
+                Err(e) => {
+                    eprintln!("Could not start '{}': {}", path, e);
+                    exit(1);
+                }
+            }
+        }
+        (None, Some(path)) => Some(Reference::read_divides(&path)),
+        (None, None) => None,
+    };
+
+    let table = (hash > 0).then(|| PerftTable::new(hash));
+    let mut results = Vec::new();
+
+    println!(
+        "{:>4} {:>5} {:>14} {:>9} {:>8}  {:<6} FEN",
+        "#", "Depth", "Nodes", "Seconds", "Mnps", "Result"
+    );
+
+    for line in read_lines(&suite_path) {
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let mut fields = line.split(',');
+        let fen = fields.next().unwrap_or_default().trim().to_owned();
+        let expected: Option<Vec<u64>> = fields.map(|f| f.trim().parse().ok()).collect();
+        let (Ok(board), Some(expected)) = (Board::from_fen(&fen), expected) else {
+            eprintln!("Invalid suite line '{}'", line);
+            exit(1);
+        };
+
+        let mut result = None;
+
+        // Only the reported depth is timed, so that the speed matches its node count
+        for (i, &expected) in expected.iter().enumerate().take(max_depth) {
+            let depth = i + 1;
+            let depth_start = Instant::now();
+            let nodes = perft_parallel(&board, depth, threads, table.as_ref());
+
+            result = Some(PositionResult {
+                fen: fen.clone(),
+                depth,
+                nodes,
+                expected,
+                seconds: depth_start.elapsed().as_secs_f64(),
+                mismatch: None,
+            });
+            if nodes != expected {
+                break;
+            }
+        }
+
+        let Some(mut result) = result else {
+            continue;
+        };
+
+        println!(
+            "{:>4} {:>5} {:>14} {:>9.3} {:>8.2}  {:<6} {}",
+            results.len() + 1,
+            result.depth,
+            result.nodes,
+            result.seconds,
+            result.nodes as f64 / result.seconds.max(1e-9) / 1e6,
+            if result.passed() { "ok" } else { "FAIL" },
+            fen
+        );
+
+        if !result.passed() {
+            println!("  Expected {} nodes", result.expected);
+            if let Some(reference) = &mut reference {
+                result.mismatch =
+                    find_mismatch(&board, result.depth, reference, threads, table.as_ref());
+            }
+            if let Some(m) = &result.mismatch {
+                print_mismatch(m);
+            }
+        }
+
+        results.push(result);
+    }
+
+    let seconds: f64 = results.iter().map(|r| r.seconds).sum();
+    let nodes: u64 = results.iter().map(|r| r.nodes).sum();
+    let passed = results.iter().filter(|r| r.passed()).count();
+    let failed = results.len() - passed;
+
+    println!(
+        "Passed: {}, failed: {}, nodes: {}, time: {:.2} s ({:.2} Mnps)",
+        passed,
+        failed,
+        nodes,
+        seconds,
+        nodes as f64 / seconds.max(1e-9) / 1e6
+    );
+
+    if let Some(path) = json_path {
+        let positions: Vec<String> = results.iter().map(|r| r.to_json()).collect();
+        let json = format!(
+            "{{\"passed\": {}, \"failed\": {}, \"nodes\": {}, \"seconds\": {:.3}, \
+             \"positions\": [\n  {}\n]}}\n",
+            passed,
+            failed,
+            nodes,
+            seconds,
+            positions.join(",\n  ")
+        );
+
+        let written = match path.as_str() {
+            "-" => io::stdout().write_all(json.as_bytes()),
+            _ => std::fs::write(&path, json),
+        };
+        if let Err(e) = written {
+            eprintln!("Could not write '{}': {}", path, e);
+            exit(1);
+        }
+    }
+
+    if let Some(Reference::Engine(mut engine)) = reference {
+        let _ = engine.quit(Duration::from_secs(1));
+    }
+
+    if failed > 0 {
+        exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Our own divide of `board` as a line of a divides file, changed by `edit`.
+    fn divide_line(board: &Board, depth: usize, edit: impl Fn(&mut Vec<(String, u64)>)) -> String {
+        let mut divide: Vec<(String, u64)> = perft_parallel_divide(board, depth, 1, None)
+            .into_iter()
+            .map(|(mv, nodes)| (mv.to_string(), nodes))
+            .collect();
+        edit(&mut divide);
+
+        let moves: Vec<String> = divide
+            .iter()
+            .map(|(mv, n)| format!("{}: {}", mv, n))
+            .collect();
+        format!("{}, {}, {}", board.fen(), depth, moves.join(", "))
+    }
+
+    #[test]
+    fn parse_divides() {
+        let lines = [
+            "# comment",
+            "",
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1, 1, e2e4: 1, g1f3: 1",
+        ]
+        .map(String::from);
+        let Ok(Reference::Divides(divides)) = Reference::parse_divides(&lines) else {
+            panic!("divides not parsed");
+        };
+        // Move counters are ignored
+        let key =
+            position_key(&Board::from_fen(&Board::default().fen().replace("0 1", "5 9")).unwrap());
+        assert_eq!(
+            divides[&(key, 1)],
+            [("e2e4".to_owned(), 1), ("g1f3".to_owned(), 1)]
+        );
+
+        for invalid in [
+            "not a fen, 1, e2e4: 1",
+            "8/8/8/8/8/8/8/K6k w - - 0 1, x",
+            "8/8/8/8/8/8/8/K6k w - - 0 1, 1, a1a2 3",
+        ] {
+            let lines = ["".to_owned(), invalid.to_owned()];
+            assert!(matches!(Reference::parse_divides(&lines), Err(2)));
+        }
+    }
+
+    #[test]
+    fn mismatch_from_divides() {
+        let board = Board::default();
+        let after = board.do_move(
+            board
+                .legal_moves()
+                .into_iter()
+                .find(|mv| mv.to_string() == "e2e4")
+                .unwrap(),
+        );
+
+        // The reference counts one node more after e2e4, where it knows a move we don't
+        let lines = [
+            divide_line(&board, 2, |divide| {
+                divide
+                    .iter_mut()
+                    .filter(|(mv, _)| mv == "e2e4")
+                    .for_each(|(_, n)| *n += 1)
+            }),
+            divide_line(&after, 1, |divide| {
+                divide.retain(|(mv, _)| mv != "a7a6");
+                divide.push(("e8e6".to_owned(), 1));
+                divide.push(("e8e7".to_owned(), 1));
+            }),
+        ];
+        let mut reference = Reference::parse_divides(&lines).unwrap();
+        let m = find_mismatch(&board, 2, &mut reference, 1, None).unwrap();
+
+        assert_eq!(m.fen, after.fen());
+        assert_eq!(m.depth, 1);
+        assert_eq!(m.path, ["e2e4"]);
+        assert_eq!(m.missing, ["e8e6", "e8e7"]);
+        assert_eq!(m.extra, ["a7a6"]);
+        assert!(m.differing.is_empty());
+
+        // Without a deeper reference the search stops at the differing node count
+        let mut reference = Reference::parse_divides(&lines[..1]).unwrap();
+        let m = find_mismatch(&board, 2, &mut reference, 1, None).unwrap();
+        assert_eq!((m.depth, m.path.len()), (2, 0));
+        assert_eq!(m.differing, [("e2e4".to_owned(), 20, 21)]);
+
+        let mut reference = Reference::parse_divides(&[]).unwrap();
+        assert!(find_mismatch(&board, 2, &mut reference, 1, None).is_none());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn mismatch_from_engine() {
+        // Knows a pawn move we don't and misses h2h3
+        const SCRIPT: &str = r#"
+            while read command rest; do
+                case $command in
+                    uci) echo "id name Reference"; echo "uciok" ;;
+                    isready) echo "readyok" ;;
+                    go)
+                        for mv in a2a3 a2a4 b2b3 b2b4 c2c3 c2c4 d2d3 d2d4 e2e3 e2e4 f2f3 f2f4 \
+                                  g2g3 g2g4 h2h4 b1a3 b1c3 g1f3 g1h3 a2a5; do
+                            echo "$mv: 1"
+                        done ;;
+                    quit) exit 0 ;;
+                esac
+            done
+        "#;
+        let engine = UciClient::spawn("sh", ["-c", SCRIPT], Duration::from_secs(5)).unwrap();
+        let mut reference = Reference::Engine(engine);
+        let m = find_mismatch(&Board::default(), 1, &mut reference, 1, None).unwrap();
+
+        assert_eq!(m.depth, 1);
+        assert_eq!(m.missing, ["a2a5"]);
+        assert_eq!(m.extra, ["h2h3"]);
+        assert!(m.differing.is_empty());
+    }
+
+    #[test]
+    fn json_output() {
+        let mut result = PositionResult {
+            fen: "8/8/8/8/8/8/8/K6k w - - 0 1".to_owned(),
+            depth: 3,
+            nodes: 10,
+            expected: 10,
+            seconds: 0.25,
+            mismatch: None,
+        };
+        assert_eq!(
+            result.to_json(),
+            "{\"fen\": \"8/8/8/8/8/8/8/K6k w - - 0 1\", \"depth\": 3, \"nodes\": 10, \
+             \"expected\": 10, \"seconds\": 0.250, \"passed\": true, \"mismatch\": null}"
+        );
+
+        result.expected = 11;
+        result.mismatch = Some(Mismatch {
+            fen: "a\"b\\c".to_owned(),
+            depth: 1,
+            path: vec!["a1a2".to_owned()],
+            missing: Vec::new(),
+            extra: vec!["h1h2".to_owned()],
+            differing: vec![("a1b1".to_owned(), 2, 3)],
+        });
+        assert_eq!(
+            result.to_json(),
+            "{\"fen\": \"8/8/8/8/8/8/8/K6k w - - 0 1\", \"depth\": 3, \"nodes\": 10, \
+             \"expected\": 11, \"seconds\": 0.250, \"passed\": false, \"mismatch\": \
+             {\"fen\": \"a\\\"b\\\\c\", \"depth\": 1, \"path\": [\"a1a2\"], \"missing\": [], \
+             \"extra\": [\"h1h2\"], \"differing\": [{\"move\": \"a1b1\", \"nodes\": 2, \
+             \"expected\": 3}]}}"
+        );
+    }
+}
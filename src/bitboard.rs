@@ -18,7 +18,7 @@ pub enum Direction {
 }
 
 impl Direction {
-    pub(crate) fn to_index(&self) -> isize {
+    pub(crate) fn to_index(self) -> isize {
         match self {
             Direction::N => 8,
             Direction::NE => 9,
@@ -32,7 +32,8 @@ impl Direction {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+/// The default bitboard is empty.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
 pub struct Bitboard(u64);
 
 impl Bitboard {
@@ -202,6 +203,71 @@ impl Bitboard {
         // }
         // Bitboard::EMPTY
     }
+
+    /// Mirrors the ranks, so that a1 becomes a8.
+    /// # Example
+    /// ```
+    /// # use chess::{bitboard::Bitboard, square::Square};
+    /// assert_eq!(Bitboard::new(Square::B2).flip_vertical(), Bitboard::new(Square::B7));
+    /// ```
+    #[inline(always)]
+    pub const fn flip_vertical(self) -> Self {
+        Self(self.0.swap_bytes())
+    }
+
+    /// Mirrors the files, so that a1 becomes h1.
+    /// # Example
+    /// ```
+    /// # use chess::{bitboard::Bitboard, square::Square};
+    /// assert_eq!(Bitboard::new(Square::B2).flip_horizontal(), Bitboard::new(Square::G2));
+    /// ```
+    #[inline(always)]
+    pub const fn flip_horizontal(self) -> Self {
+        const K1: u64 = 0x5555555555555555;
+        const K2: u64 = 0x3333333333333333;
+        const K4: u64 = 0x0f0f0f0f0f0f0f0f;
+
+        let mut x = self.0;
+        x = ((x >> 1) & K1) | ((x & K1) << 1);
+        x = ((x >> 2) & K2) | ((x & K2) << 2);
+        x = ((x >> 4) & K4) | ((x & K4) << 4);
+        Self(x)
+    }
+
+    /// Mirrors the board along the a1-h8 diagonal, so that b1 becomes a2.
+    /// # Example
+    /// ```
+    /// # use chess::{bitboard::Bitboard, square::Square};
+    /// assert_eq!(Bitboard::new(Square::B1).flip_diagonal(), Bitboard::new(Square::A2));
+    /// assert_eq!(Bitboard::new(Square::C3).flip_diagonal(), Bitboard::new(Square::C3));
+    /// ```
+    #[inline(always)]
+    pub const fn flip_diagonal(self) -> Self {
+        // https://www.chessprogramming.org/Flipping_Mirroring_and_Rotating
+        const K1: u64 = 0x5500550055005500;
+        const K2: u64 = 0x3333000033330000;
+        const K4: u64 = 0x0f0f0f0f00000000;
+
+        let mut x = self.0;
+        let mut t = K4 & (x ^ (x << 28));
+        x ^= t ^ (t >> 28);
+        t = K2 & (x ^ (x << 14));
+        x ^= t ^ (t >> 14);
+        t = K1 & (x ^ (x << 7));
+        x ^= t ^ (t >> 7);
+        Self(x)
+    }
+
+    /// Rotates the board by 180 degrees, so that a1 becomes h8.
+    /// # Example
+    /// ```
+    /// # use chess::{bitboard::Bitboard, square::Square};
+    /// assert_eq!(Bitboard::new(Square::B2).rotate_180(), Bitboard::new(Square::G7));
+    /// ```
+    #[inline(always)]
+    pub const fn rotate_180(self) -> Self {
+        Self(self.0.reverse_bits())
+    }
 }
 
 impl ops::BitAnd for Bitboard {
@@ -485,4 +551,33 @@ mod tests {
         assert_eq!(it.next(), Some(Square::new(Rank::First, File::H)));
         assert_eq!(it.next(), None);
     }
+
+    #[test]
+    fn bitboard_transforms() {
+        let bb = Bitboard(0x0123456789abcdef);
+
+        for transform in [
+            Bitboard::flip_vertical,
+            Bitboard::flip_horizontal,
+            Bitboard::flip_diagonal,
+            Bitboard::rotate_180,
+        ] {
+            assert_eq!(transform(transform(bb)), bb);
+            assert_eq!(transform(bb).popcount(), bb.popcount());
+        }
+
+        assert_eq!(bb.flip_vertical().flip_horizontal(), bb.rotate_180());
+
+        for i in 0..64 {
+            let square = Square::from_index(i);
+            let (rank, file) = (square.rank().to_index(), square.file().to_index());
+            let at = |rank, file| Bitboard::new(Square::from_index(rank * 8 + file));
+
+            let bb = Bitboard::new(square);
+            assert_eq!(bb.flip_vertical(), at(7 - rank, file));
+            assert_eq!(bb.flip_horizontal(), at(rank, 7 - file));
+            assert_eq!(bb.flip_diagonal(), at(file, rank));
+            assert_eq!(bb.rotate_180(), at(7 - rank, 7 - file));
+        }
+    }
 }
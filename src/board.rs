@@ -1,21 +1,156 @@
 use core::fmt;
-use std::str::FromStr;
 
 use crate::{
     bitboard::Bitboard,
     castling_rights::CastlingRights,
     color::Color,
-    moves::{generate_moves, Move, MoveKind},
+    moves::{
+        castling_moves, generate_moves, generate_pseudo_legal_moves, has_legal_moves, Move,
+        MoveKind, Movelist,
+    },
     piece::{Piece, PieceKind},
     square::{File, Rank, Square},
+    zobrist,
 };
 
+/// Why a FEN string was rejected.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FenErrorReason {
+    MissingField,
+    TooManyFields,
+    UnexpectedCharacter(char),
+    /// A rank, numbered 1 to 8, that does not have 8 squares
+    InvalidRank(usize),
+    /// A piece placement that does not have 8 ranks
+    InvalidRankCount(usize),
+    InvalidSquare(String),
+    InvalidNumber(String),
+    IllegalPosition(PositionError),
+}
+
+impl fmt::Display for FenErrorReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FenErrorReason::MissingField => write!(f, "missing field"),
+            FenErrorReason::TooManyFields => write!(f, "too many fields"),
+            FenErrorReason::UnexpectedCharacter(c) => write!(f, "unexpected character '{}'", c),
+            FenErrorReason::InvalidRank(rank) => write!(f, "invalid rank {}", rank),
+            FenErrorReason::InvalidRankCount(n) => write!(f, "expected 8 ranks, found {}", n),
+            FenErrorReason::InvalidSquare(s) => write!(f, "invalid square '{}'", s),
+            FenErrorReason::InvalidNumber(s) => write!(f, "invalid number '{}'", s),
+            FenErrorReason::IllegalPosition(e) => write!(f, "{}", e),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct ParseFenError {
-    msg: String,
+    field: Option<usize>,
+    reason: FenErrorReason,
 }
 
 impl ParseFenError {
+    const FIELDS: [&'static str; 6] = [
+        "piece placement",
+        "side to move",
+        "castling rights",
+        "en passant square",
+        "halfmove clock",
+        "fullmove number",
+    ];
+
+    pub fn new(field: Option<usize>, reason: FenErrorReason) -> Self {
+        Self { field, reason }
+    }
+
+    /// Index of the offending field, from 0 for the piece placement to 5 for the fullmove
+    /// number. `None` if the position as a whole is illegal.
+    pub fn field(&self) -> Option<usize> {
+        self.field
+    }
+
+    pub fn reason(&self) -> &FenErrorReason {
+        &self.reason
+    }
+}
+
+impl From<PositionError> for ParseFenError {
+    fn from(e: PositionError) -> Self {
+        Self::new(None, FenErrorReason::IllegalPosition(e))
+    }
+}
+
+impl fmt::Display for ParseFenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self
+            .field
+            .map(|i| Self::FIELDS.get(i).copied().unwrap_or("extra field"))
+        {
+            Some(field) => write!(f, "Error parsing FEN: {}: {}.", field, self.reason),
+            None => write!(f, "Error parsing FEN: {}.", self.reason),
+        }
+    }
+}
+
+/// How strictly `Board::from_fen_with_mode` checks its input.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FenMode {
+    /// Requires all six fields in canonical form and a legal position.
+    Strict,
+    /// Accepts missing or malformed move counters and does not check legality.
+    Lenient,
+}
+
+/// Defects that make a position impossible to reach or to play from.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PositionError {
+    MissingKing(Color),
+    TooManyKings(Color),
+    TooManyPawns(Color),
+    TooManyPieces(Color),
+    PawnOnBackRank(Square),
+    /// The king or the rook has left its starting square.
+    InvalidCastlingRights(CastlingRights),
+    /// No pawn can have double pushed past the square.
+    InvalidEnPassant(Square),
+    /// The side not to move is in check.
+    OpponentInCheck,
+}
+
+impl fmt::Display for PositionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = |color: &Color| match color {
+            Color::White => "white",
+            Color::Black => "black",
+        };
+        let square = |s: &Square| format!("{}{}", char::from(s.file()), char::from(s.rank()));
+
+        match self {
+            PositionError::MissingKing(c) => write!(f, "{} has no king", name(c)),
+            PositionError::TooManyKings(c) => write!(f, "{} has more than one king", name(c)),
+            PositionError::TooManyPawns(c) => write!(f, "{} has more than 8 pawns", name(c)),
+            PositionError::TooManyPieces(c) => write!(f, "{} has more than 16 pieces", name(c)),
+            PositionError::PawnOnBackRank(s) => write!(f, "pawn on back rank at {}", square(s)),
+            PositionError::InvalidCastlingRights(CastlingRights::Kingside(c)) => {
+                write!(f, "{} cannot castle kingside", name(c))
+            }
+            PositionError::InvalidCastlingRights(CastlingRights::Queenside(c)) => {
+                write!(f, "{} cannot castle queenside", name(c))
+            }
+            PositionError::InvalidEnPassant(s) => {
+                write!(f, "invalid en passant square {}", square(s))
+            }
+            PositionError::OpponentInCheck => write!(f, "side not to move is in check"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ParsePackedError {
+    msg: String,
+}
+
+impl ParsePackedError {
     pub fn new<S: AsRef<str>>(msg: S) -> Self {
         Self {
             msg: msg.as_ref().to_owned(),
@@ -23,36 +158,22 @@ impl ParseFenError {
     }
 }
 
-impl fmt::Display for ParseFenError {
+impl fmt::Display for ParsePackedError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Error parsing FEN: {}.", self.msg)
+        write!(f, "Error unpacking position: {}.", self.msg)
     }
 }
 
-#[derive(Clone)]
+/// Boards are equal when their FENs are, see `same_position` to ignore the move counters.
+#[derive(Clone, PartialEq, Eq, Hash)]
 pub struct Board {
     bb: [Bitboard; 4],
     state: u32,
 }
 
-impl Board {
-    /// Creates an empty chessboard.
-    pub fn new() -> Self {
-        let mut board = Self {
-            bb: [Bitboard::EMPTY; 4],
-            state: 0,
-        };
-
-        board.set_color_to_move(Color::White);
-        board.set_en_passant_square(None);
-        board.set_halfmove_clock(0);
-        board.set_fullmove_number(1);
-
-        board
-    }
-
+impl Default for Board {
     /// Creates a new chessboard with the standard starting position.
-    pub fn default() -> Self {
+    fn default() -> Self {
         let mut board = Board::new();
 
         for i in 0..8 {
@@ -86,8 +207,32 @@ impl Board {
 
         board
     }
+}
+
+impl fmt::Debug for Board {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Board").field(&self.fen()).finish()
+    }
+}
+
+impl Board {
+    /// Creates an empty chessboard.
+    pub fn new() -> Self {
+        let mut board = Self {
+            bb: [Bitboard::EMPTY; 4],
+            state: 0,
+        };
+
+        board.set_color_to_move(Color::White);
+        board.set_en_passant_square(None);
+        board.set_halfmove_clock(0);
+        board.set_fullmove_number(1);
+
+        board
+    }
 
-    /// Creates a new chessboard from FEN string.
+    /// Creates a new chessboard from FEN string. Missing or malformed move counters are
+    /// ignored, see `from_fen_with_mode` for a strict parser.
     /// # Example
     /// ```
     /// # use chess::{board::Board};
@@ -97,9 +242,36 @@ impl Board {
     /// assert_eq!(board.fen(), "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1");
     /// ```
     pub fn from_fen(fen: &str) -> Result<Board, ParseFenError> {
+        Self::from_fen_with_mode(fen, FenMode::Lenient)
+    }
+
+    /// Creates a new chessboard from FEN string. In strict mode all six fields must be
+    /// present and well formed, and the position must pass `validate`.
+    /// # Example
+    /// ```
+    /// # use chess::board::{Board, FenErrorReason, FenMode};
+    /// let fen = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - x 1";
+    /// assert!(Board::from_fen_with_mode(fen, FenMode::Lenient).is_ok());
+    /// let e = Board::from_fen_with_mode(fen, FenMode::Strict).err().unwrap();
+    /// assert_eq!(e.field(), Some(4));
+    /// assert_eq!(e.reason(), &FenErrorReason::InvalidNumber("x".to_owned()));
+    /// ```
+    pub fn from_fen_with_mode(fen: &str, mode: FenMode) -> Result<Board, ParseFenError> {
+        let strict = mode == FenMode::Strict;
         let mut board = Board::new();
 
-        let mut fen = fen.split_whitespace();
+        let fields: Vec<&str> = fen.split_whitespace().collect();
+        let error = |field, reason| ParseFenError::new(Some(field), reason);
+        let field = |i| {
+            fields
+                .get(i)
+                .copied()
+                .ok_or(error(i, FenErrorReason::MissingField))
+        };
+
+        if fields.len() > 6 {
+            return Err(error(6, FenErrorReason::TooManyFields));
+        }
 
         fn parse_piece(c: char) -> Option<Piece> {
             match c {
@@ -119,94 +291,189 @@ impl Board {
             }
         }
 
-        // pieces
-        let pieces = fen
-            .next()
-            .ok_or(ParseFenError::new("No pieces specified"))?;
-        let mut rank = Rank::iter().rev().peekable();
-        let mut file = File::iter().peekable();
+        // pieces, rank 8 first
+        let ranks: Vec<&str> = field(0)?.split('/').collect();
+        if ranks.len() > 8 || (strict && ranks.len() < 8) {
+            return Err(error(0, FenErrorReason::InvalidRankCount(ranks.len())));
+        }
 
-        for c in pieces.chars() {
-            match c {
-                '1'..='8' => {
-                    let n = c as u8 - '0' as u8;
-                    for _ in 0..n {
-                        file.next();
-                    }
-                }
-                '/' => {
-                    rank.next();
-                    file = File::iter().peekable();
+        for (i, pieces) in ranks.iter().enumerate() {
+            let rank = Rank::from_index(7 - i);
+            let mut file = 0;
+
+            for c in pieces.chars() {
+                match c {
+                    '1'..='8' => file += (c as u8 - b'0') as usize,
+                    c => match parse_piece(c) {
+                        Some(piece) if file < 8 => {
+                            board.put(piece, Square::new(rank, File::from_index(file)));
+                            file += 1;
+                        }
+                        Some(_) => return Err(error(0, FenErrorReason::InvalidRank(8 - i))),
+                        None => return Err(error(0, FenErrorReason::UnexpectedCharacter(c))),
+                    },
                 }
-                c => match parse_piece(c) {
-                    Some(piece) => {
-                        let &r = rank
-                            .peek()
-                            .ok_or(ParseFenError::new("Invalid rank in pieces"))?;
-                        let &f = file
-                            .peek()
-                            .ok_or(ParseFenError::new("Invalid file in pieces"))?;
-                        let square = Square::new(r, f);
-                        board.put(piece, square);
-                        file.next();
-                    }
-                    None => {
-                        return Err(ParseFenError::new(format!("Unexpected character '{}'", c)))
-                    }
-                },
+            }
+
+            if file > 8 || (strict && file != 8) {
+                return Err(error(0, FenErrorReason::InvalidRank(8 - i)));
             }
         }
 
         // color
-        let color = fen.next().ok_or(ParseFenError::new("No color specified"))?;
-        let color = match color {
+        let color = match field(1)? {
             "w" => Color::White,
             "b" => Color::Black,
-            _ => return Err(ParseFenError::new(format!("Unexpected color '{}'", color))),
+            s => {
+                let c = s.chars().find(|&c| c != 'w' && c != 'b');
+                let c = c.or(s.chars().nth(1)).unwrap_or_default();
+                return Err(error(1, FenErrorReason::UnexpectedCharacter(c)));
+            }
         };
         board.set_color_to_move(color);
 
-        // castling rights
-        for c in fen
-            .next()
-            .ok_or(ParseFenError::new("No castling rights specified"))?
-            .chars()
-        {
+        // castling rights, in KQkq order without repetitions when strict
+        let castling = field(2)?;
+        let mut last = None;
+        for c in castling.chars() {
+            let order = "KQkq".find(c);
+            if strict && ((castling == "-") != (c == '-') || order.is_some() && order <= last) {
+                return Err(error(2, FenErrorReason::UnexpectedCharacter(c)));
+            }
+            last = order.or(last);
+
             match c {
                 'K' => board.add_castling_rights(CastlingRights::Kingside(Color::White)),
                 'Q' => board.add_castling_rights(CastlingRights::Queenside(Color::White)),
                 'k' => board.add_castling_rights(CastlingRights::Kingside(Color::Black)),
                 'q' => board.add_castling_rights(CastlingRights::Queenside(Color::Black)),
                 '-' => (),
-                _ => return Err(ParseFenError::new(format!("Unexpected character '{}'", c))),
+                _ => return Err(error(2, FenErrorReason::UnexpectedCharacter(c))),
             }
         }
 
-        // en passant square
-        let ep = fen
-            .next()
-            .ok_or(ParseFenError::new("No en passant square specified"))?;
-
+        // en passant square, which is on the third or sixth rank depending on the color
+        let ep = field(3)?;
         board.set_en_passant_square(if ep == "-" {
             None
         } else {
-            Some(
-                ep.parse()
-                    .map_err(|_| ParseFenError::new("Invalid en passant square"))?,
-            )
+            let rank = match color {
+                Color::White => Rank::Sixth,
+                Color::Black => Rank::Third,
+            };
+            match ep.parse::<Square>() {
+                Ok(square) if ep.len() == 2 && (!strict || square.rank() == rank) => Some(square),
+                _ => return Err(error(3, FenErrorReason::InvalidSquare(ep.to_owned()))),
+            }
         });
 
-        if let Some(s) = fen.next() {
-            board.set_halfmove_clock(s.parse::<usize>().unwrap());
+        // move counters, limited by the bits available for them
+        for (i, max) in [(4, 127), (5, u16::MAX as usize)] {
+            let value = match fields.get(i) {
+                Some(s) => match s.parse::<usize>() {
+                    Ok(n) if n <= max && (i == 4 || n > 0) => Some(n),
+                    Ok(n) if !strict => Some(n.clamp(1, max)),
+                    _ if !strict => None,
+                    _ => return Err(error(i, FenErrorReason::InvalidNumber(s.to_string()))),
+                },
+                None if strict => return Err(error(i, FenErrorReason::MissingField)),
+                None => None,
+            };
+
+            match (i, value) {
+                (4, Some(n)) => board.set_halfmove_clock(n),
+                (5, Some(n)) => board.set_fullmove_number(n),
+                _ => (),
+            }
         }
 
-        if let Some(s) = fen.next() {
-            board.set_fullmove_number(s.parse::<usize>().unwrap());
+        if strict {
+            board.validate()?;
         }
 
         Ok(board)
     }
 
+    /// Checks that the position could occur in a game: one king per side, at most 8 pawns
+    /// and 16 pieces per side, no pawns on the back ranks, castling rights matching the
+    /// king and rook squares, a plausible en passant square and the side not to move not
+    /// in check.
+    /// # Example
+    /// ```
+    /// # use chess::board::{Board, PositionError};
+    /// assert!(Board::default().validate().is_ok());
+    /// let board = Board::from_fen("4k3/8/8/8/8/8/8/4K3 w K - 0 1").unwrap();
+    /// assert!(matches!(board.validate(), Err(PositionError::InvalidCastlingRights(_))));
+    /// ```
+    pub fn validate(&self) -> Result<(), PositionError> {
+        for color in [Color::White, Color::Black] {
+            let pieces = self.pieces_by_color(color);
+
+            match (self.pieces_by_kind(PieceKind::King) & pieces).popcount() {
+                0 => return Err(PositionError::MissingKing(color)),
+                1 => (),
+                _ => return Err(PositionError::TooManyKings(color)),
+            }
+            if (self.pieces_by_kind(PieceKind::Pawn) & pieces).popcount() > 8 {
+                return Err(PositionError::TooManyPawns(color));
+            }
+            if pieces.popcount() > 16 {
+                return Err(PositionError::TooManyPieces(color));
+            }
+        }
+
+        let back_ranks = Bitboard::rank(Rank::First) | Bitboard::rank(Rank::Eighth);
+        if let Some(square) = (self.pieces_by_kind(PieceKind::Pawn) & back_ranks).first() {
+            return Err(PositionError::PawnOnBackRank(square));
+        }
+
+        for color in [Color::White, Color::Black] {
+            let rank = match color {
+                Color::White => Rank::First,
+                Color::Black => Rank::Eighth,
+            };
+            let king =
+                self.at(Square::new(rank, File::E)) == Some(Piece::new(PieceKind::King, color));
+
+            for (cr, file) in [
+                (CastlingRights::Kingside(color), File::H),
+                (CastlingRights::Queenside(color), File::A),
+            ] {
+                let rook =
+                    self.at(Square::new(rank, file)) == Some(Piece::new(PieceKind::Rook, color));
+                if self.has_castling_rights(cr) && !(king && rook) {
+                    return Err(PositionError::InvalidCastlingRights(cr));
+                }
+            }
+        }
+
+        if let Some(ep) = self.en_passant_square() {
+            // The pawn that just moved two squares stands in front of the square, and the
+            // square it came from is empty
+            let (from, to) = match self.color_to_move() {
+                Color::White => (Rank::Seventh, Rank::Fifth),
+                Color::Black => (Rank::Second, Rank::Fourth),
+            };
+            let pawn = Piece::new(PieceKind::Pawn, !self.color_to_move());
+
+            if self.at(Square::new(to, ep.file())) != Some(pawn)
+                || self.at(ep).is_some()
+                || self.at(Square::new(from, ep.file())).is_some()
+            {
+                return Err(PositionError::InvalidEnPassant(ep));
+            }
+        }
+
+        let mut opponent = self.clone();
+        opponent.set_color_to_move(!self.color_to_move());
+        opponent.set_en_passant_square(None);
+        if opponent.is_in_check() {
+            return Err(PositionError::OpponentInCheck);
+        }
+
+        Ok(())
+    }
+
     /// Returns the position in FEN.
     /// # Example
     /// ```
@@ -226,7 +493,7 @@ impl Board {
                 match self.at(square) {
                     Some(piece) => {
                         if empty_squares > 0 {
-                            result.push(('0' as u8 + empty_squares) as char);
+                            result.push((b'0' + empty_squares) as char);
                             empty_squares = 0;
                         }
                         let c = match piece {
@@ -249,7 +516,7 @@ impl Board {
                 }
             }
             if empty_squares > 0 {
-                result.push(('0' as u8 + empty_squares) as char);
+                result.push((b'0' + empty_squares) as char);
             }
             if rank != Rank::First {
                 result.push('/');
@@ -289,8 +556,8 @@ impl Board {
         match self.en_passant_square() {
             None => result.push('-'),
             Some(square) => {
-                result.push(('a' as u8 + square.file().to_index() as u8) as char);
-                result.push(('1' as u8 + square.rank().to_index() as u8) as char);
+                result.push((b'a' + square.file().to_index() as u8) as char);
+                result.push((b'1' + square.rank().to_index() as u8) as char);
             }
         }
 
@@ -300,6 +567,237 @@ impl Board {
         result
     }
 
+    /// Version of the packed format, stored in its first byte.
+    pub const PACKED_VERSION: u8 = 1;
+
+    /// Size of a packed position in bytes.
+    pub const PACKED_SIZE: usize = 32;
+
+    /// Castling rights in the order of their bits in the packed format.
+    const CASTLING_RIGHTS: [CastlingRights; 4] = [
+        CastlingRights::Kingside(Color::White),
+        CastlingRights::Queenside(Color::White),
+        CastlingRights::Kingside(Color::Black),
+        CastlingRights::Queenside(Color::Black),
+    ];
+
+    /// Packs the position into 32 bytes. Positions with more than 32 pieces don't fit and are
+    /// rejected with [`PositionError::TooManyPieces`]. The layout of version 1 is:
+    ///
+    /// - byte 0: format version
+    /// - bytes 1-8: occupancy bitboard, little endian
+    /// - bytes 9-24: one nibble per piece in square order, low nibble first. The nibble is the
+    ///   piece's quad-bitboard code, i.e. the color bit plus the three kind bits.
+    /// - bytes 25-28: side to move (bit 0), castling rights KQkq (bits 1-4), en passant file
+    ///   or 8 for none (bits 5-8), halfmove clock (bits 9-15) and fullmove number (bits
+    ///   16-31), little endian
+    /// - bytes 29-31: reserved, zero
+    /// # Example
+    /// ```
+    /// # use chess::board::Board;
+    /// let board = Board::default();
+    /// let packed = board.to_packed().unwrap();
+    /// assert_eq!(Board::from_packed(&packed).unwrap().fen(), board.fen());
+    /// ```
+    pub fn to_packed(&self) -> Result<[u8; Self::PACKED_SIZE], PositionError> {
+        // More than 32 pieces means more than 16 for one side
+        if self.pieces().popcount() > 32 {
+            let color = match self.pieces_by_color(Color::White).popcount() > 16 {
+                true => Color::White,
+                false => Color::Black,
+            };
+            return Err(PositionError::TooManyPieces(color));
+        }
+
+        let mut bytes = [0; Self::PACKED_SIZE];
+        bytes[0] = Self::PACKED_VERSION;
+        bytes[1..9].copy_from_slice(&self.pieces().to_u64().to_le_bytes());
+
+        for (n, square) in self.pieces().into_iter().enumerate() {
+            let i = square.to_index();
+            let code = (0..4).fold(0, |code, b| code | ((self.bb[b].to_u64() >> i) & 1) << b);
+            bytes[9 + n / 2] |= (code as u8) << (4 * (n % 2));
+        }
+
+        let mut state = (self.color_to_move() == Color::Black) as u32;
+        for (i, cr) in Self::CASTLING_RIGHTS.into_iter().enumerate() {
+            state |= (self.has_castling_rights(cr) as u32) << (i + 1);
+        }
+        state |= self
+            .en_passant_square()
+            .map_or(8, |ep| ep.file().to_index() as u32)
+            << 5;
+        state |= (self.halfmove_clock() as u32) << 9;
+        state |= (self.fullmove_number() as u32) << 16;
+        bytes[25..29].copy_from_slice(&state.to_le_bytes());
+
+        Ok(bytes)
+    }
+
+    /// Unpacks a position packed by [`Board::to_packed`]. Malformed input is rejected, but
+    /// the position itself is not checked for legality.
+    pub fn from_packed(bytes: &[u8; Self::PACKED_SIZE]) -> Result<Board, ParsePackedError> {
+        if bytes[0] != Self::PACKED_VERSION {
+            return Err(ParsePackedError::new(format!(
+                "Unsupported version {}",
+                bytes[0]
+            )));
+        }
+        if bytes[29..].iter().any(|&b| b != 0) {
+            return Err(ParsePackedError::new("Reserved bytes are not zero"));
+        }
+
+        let occupancy = u64::from_le_bytes(bytes[1..9].try_into().unwrap());
+        let count = occupancy.count_ones() as usize;
+        if count > 32 {
+            return Err(ParsePackedError::new("More than 32 pieces"));
+        }
+
+        let mut board = Board::new();
+        for (n, square) in Bitboard::from_u64(occupancy).into_iter().enumerate() {
+            let code = (bytes[9 + n / 2] >> (4 * (n % 2))) & 15;
+            if !(2..=13).contains(&code) {
+                return Err(ParsePackedError::new(format!(
+                    "Invalid piece code {}",
+                    code
+                )));
+            }
+            for b in 0..4 {
+                if code & (1 << b) != 0 {
+                    board.bb[b] |= Bitboard::new(square);
+                }
+            }
+        }
+
+        // Nibbles after the last piece must be empty so that every position has exactly one
+        // packed form
+        if (count..32).any(|n| (bytes[9 + n / 2] >> (4 * (n % 2))) & 15 != 0) {
+            return Err(ParsePackedError::new("Data after the last piece"));
+        }
+
+        let state = u32::from_le_bytes(bytes[25..29].try_into().unwrap());
+        if state & 1 != 0 {
+            board.set_color_to_move(Color::Black);
+        }
+        for (i, cr) in Self::CASTLING_RIGHTS.into_iter().enumerate() {
+            if state & (1 << (i + 1)) != 0 {
+                board.add_castling_rights(cr);
+            }
+        }
+        board.set_en_passant_square(match (state >> 5) & 15 {
+            8 => None,
+            file @ 0..=7 => Some(Square::new(
+                match board.color_to_move() {
+                    Color::White => Rank::Sixth,
+                    Color::Black => Rank::Third,
+                },
+                File::from_index(file as usize),
+            )),
+            _ => return Err(ParsePackedError::new("Invalid en passant file")),
+        });
+        board.set_halfmove_clock(((state >> 9) & 127) as usize);
+        board.set_fullmove_number((state >> 16) as usize);
+
+        Ok(board)
+    }
+
+    /// Compares the positions, ignoring the halfmove clock and fullmove number.
+    /// # Example
+    /// ```
+    /// # use chess::board::Board;
+    /// let board = Board::from_fen("4k3/8/8/8/8/8/8/R3K3 w Q - 0 1").unwrap();
+    /// let later = Board::from_fen("4k3/8/8/8/8/8/8/R3K3 w Q - 12 40").unwrap();
+    /// assert_ne!(board, later);
+    /// assert!(board.same_position(&later));
+    /// ```
+    pub fn same_position(&self, other: &Board) -> bool {
+        // Side to move, castling rights and en passant file
+        const POSITION_STATE: u32 = 0x1ff;
+
+        self.bb == other.bb && self.state & POSITION_STATE == other.state & POSITION_STATE
+    }
+
+    /// Returns the Polyglot hash key of the position.
+    /// # Example
+    /// ```
+    /// # use chess::{board::Board};
+    /// assert_eq!(Board::default().polyglot_key(), 0x463b96181691fc9c);
+    /// ```
+    pub fn polyglot_key(&self) -> u64 {
+        let mut key = self.castling_key() ^ self.en_passant_key() ^ self.side_key();
+
+        for square in self.pieces() {
+            key ^= zobrist::piece(self.at(square).unwrap(), square);
+        }
+
+        key
+    }
+
+    /// Returns the Polyglot key of `next` given `key`, the key of this position, by hashing
+    /// only what differs between the two. Much cheaper than `polyglot_key()` after a move.
+    /// # Example
+    /// ```
+    /// # use chess::board::Board;
+    /// let board = Board::default();
+    /// let next = board.do_move(board.legal_moves()[0]);
+    /// assert_eq!(board.polyglot_key_after(board.polyglot_key(), &next), next.polyglot_key());
+    /// ```
+    pub fn polyglot_key_after(&self, key: u64, next: &Board) -> u64 {
+        let mut key = key
+            ^ self.castling_key()
+            ^ next.castling_key()
+            ^ self.en_passant_key()
+            ^ next.en_passant_key()
+            ^ self.side_key()
+            ^ next.side_key();
+
+        let changed = (0..4).fold(Bitboard::EMPTY, |changed, i| {
+            changed | (self.bb[i] ^ next.bb[i])
+        });
+        for square in changed {
+            if let Some(piece) = self.at(square) {
+                key ^= zobrist::piece(piece, square);
+            }
+            if let Some(piece) = next.at(square) {
+                key ^= zobrist::piece(piece, square);
+            }
+        }
+
+        key
+    }
+
+    fn castling_key(&self) -> u64 {
+        [
+            CastlingRights::Kingside(Color::White),
+            CastlingRights::Queenside(Color::White),
+            CastlingRights::Kingside(Color::Black),
+            CastlingRights::Queenside(Color::Black),
+        ]
+        .into_iter()
+        .filter(|&cr| self.has_castling_rights(cr))
+        .fold(0, |key, cr| key ^ zobrist::castling(cr))
+    }
+
+    fn en_passant_key(&self) -> u64 {
+        // Polyglot only hashes the en passant file if a pawn can actually capture
+        if let Some(ep) = self.en_passant_square() {
+            let us = self.color_to_move();
+            let pawns = self.pieces_by_kind(PieceKind::Pawn) & self.pieces_by_color(us);
+            if (Bitboard::pawn_attacks(Bitboard::new(ep), !us) & pawns).is_non_empty() {
+                return zobrist::en_passant(ep.file());
+            }
+        }
+
+        0
+    }
+
+    fn side_key(&self) -> u64 {
+        match self.color_to_move() {
+            Color::White => zobrist::white_to_move(),
+            Color::Black => 0,
+        }
+    }
+
     /// Returns the possible piece on `square`.
     /// # Example
     /// ```
@@ -349,9 +847,8 @@ impl Board {
     #[inline(always)]
     pub fn put(&mut self, piece: Piece, square: Square) {
         let bb = Bitboard::new(square);
-        match piece.color() {
-            Color::Black => self.bb[0] |= bb,
-            _ => (),
+        if piece.color() == Color::Black {
+            self.bb[0] |= bb;
         }
 
         match piece.kind() {
@@ -429,13 +926,15 @@ impl Board {
         }
     }
 
+    /// Returns all occupied squares.
     #[inline(always)]
-    pub(crate) fn pieces(&self) -> Bitboard {
+    pub fn pieces(&self) -> Bitboard {
         self.bb[1] | self.bb[2] | self.bb[3]
     }
 
+    /// Returns the squares occupied by pieces of `color`.
     #[inline(always)]
-    pub(crate) fn pieces_by_color(&self, color: Color) -> Bitboard {
+    pub fn pieces_by_color(&self, color: Color) -> Bitboard {
         match color {
             Color::Black => self.bb[0],
             Color::White => self.bb[0] ^ self.pieces(),
@@ -447,8 +946,9 @@ impl Board {
         self.bb[1] ^ self.bb[2] ^ self.bb[3]
     }
 
+    /// Returns the squares occupied by pieces of `kind` of either color.
     #[inline(always)]
-    pub(crate) fn pieces_by_kind(&self, kind: PieceKind) -> Bitboard {
+    pub fn pieces_by_kind(&self, kind: PieceKind) -> Bitboard {
         match kind {
             PieceKind::Pawn => self.bb[1] & self.odd_pieces(),
             PieceKind::Knight => self.bb[2] & self.odd_pieces(),
@@ -512,6 +1012,57 @@ impl Board {
         self.state = self.state & !(15 << 5) | (bits << 5);
     }
 
+    /// Returns the same position with colors swapped, as seen by the other player. The ranks
+    /// are mirrored and the side to move, castling rights and en passant square follow along.
+    /// # Example
+    /// ```
+    /// # use chess::board::Board;
+    /// let board = Board::from_fen("4k3/8/8/3pP3/8/8/8/R3K3 w Q d6 0 2").unwrap();
+    /// assert_eq!(board.flip_vertical().fen(), "r3k3/8/8/8/3Pp3/8/8/4K3 b q d3 0 2");
+    /// ```
+    pub fn flip_vertical(&self) -> Board {
+        let mut board = self.clone();
+        for bb in &mut board.bb {
+            *bb = bb.flip_vertical();
+        }
+        // Black pieces become the white ones
+        board.bb[0] ^= board.pieces();
+
+        let castling = (self.state >> 1) & 15;
+        let castling = (castling >> 2) | ((castling & 3) << 2);
+        board.state = board.state & !(15 << 1) | (castling << 1);
+        board.set_color_to_move(!self.color_to_move());
+
+        board
+    }
+
+    /// Returns the position with files mirrored, so that a1 becomes h1. Positions with
+    /// castling rights have no mirrored counterpart and return `None`.
+    /// # Example
+    /// ```
+    /// # use chess::board::Board;
+    /// let board = Board::from_fen("4k3/8/8/3pP3/8/8/8/R3K3 w - d6 0 2").unwrap();
+    /// assert_eq!(board.mirror_horizontal().unwrap().fen(), "3k4/8/8/3Pp3/8/8/8/3K3R w - e6 0 2");
+    /// assert!(Board::default().mirror_horizontal().is_none());
+    /// ```
+    pub fn mirror_horizontal(&self) -> Option<Board> {
+        if !self.no_castlings() {
+            return None;
+        }
+
+        let mut board = self.clone();
+        for bb in &mut board.bb {
+            *bb = bb.flip_horizontal();
+        }
+
+        let ep = self
+            .en_passant_square()
+            .map(|ep| Square::new(ep.rank(), File::from_index(7 - ep.file().to_index())));
+        board.set_en_passant_square(ep);
+
+        Some(board)
+    }
+
     /// Executes a move and updates the board state.
     /// Returns an undo object which is used for unmaking the move.
     pub fn do_move(&self, mv: Move) -> Board {
@@ -613,9 +1164,8 @@ impl Board {
 
         board.state = board.state & !(15 << 1) | ((new_cr as u32) << 1);
 
-        match board.color_to_move() {
-            Color::Black => board.increment_fullmove_number(),
-            _ => (),
+        if board.color_to_move() == Color::Black {
+            board.increment_fullmove_number();
         }
 
         board.set_color_to_move(!board.color_to_move());
@@ -636,10 +1186,11 @@ impl Board {
     }
 
     pub fn is_in_check(&self) -> bool {
-        let king_square = (self.pieces_by_kind(PieceKind::King)
+        let Some(king_square) = (self.pieces_by_kind(PieceKind::King)
             & self.pieces_by_color(self.color_to_move()))
-        .first()
-        .unwrap();
+        .first() else {
+            return false;
+        };
 
         let enemy = self.pieces_by_color(!self.color_to_move());
 
@@ -671,14 +1222,139 @@ impl Board {
         false
     }
 
+    fn king_square(&self, color: Color) -> Option<Square> {
+        (self.pieces_by_kind(PieceKind::King) & self.pieces_by_color(color)).first()
+    }
+
+    /// Returns the pieces of `color` attacking `square`.
+    /// # Example
+    /// ```
+    /// # use chess::{board::Board, color::Color, square::Square};
+    /// let board = Board::from_fen("4k3/8/8/3p4/8/2N5/8/R3K3 w - - 0 1").unwrap();
+    /// let attackers: Vec<Square> = board.attackers_to(Square::D5, Color::White).into_iter().collect();
+    /// assert_eq!(attackers, vec![Square::C3]);
+    /// assert!(board.attackers_to(Square::A8, Color::White).is_non_empty());
+    /// ```
+    pub fn attackers_to(&self, square: Square, color: Color) -> Bitboard {
+        let all = self.pieces();
+        let bishops =
+            self.pieces_by_kind(PieceKind::Bishop) | self.pieces_by_kind(PieceKind::Queen);
+        let rooks = self.pieces_by_kind(PieceKind::Rook) | self.pieces_by_kind(PieceKind::Queen);
+
+        let attackers = (Bitboard::pawn_attacks(Bitboard::new(square), !color)
+            & self.pieces_by_kind(PieceKind::Pawn))
+            | (Bitboard::knight_attacks(square) & self.pieces_by_kind(PieceKind::Knight))
+            | (Bitboard::king_attacks(square) & self.pieces_by_kind(PieceKind::King))
+            | (Bitboard::bishop_attacks(square, all) & bishops)
+            | (Bitboard::rook_attacks(square, all) & rooks);
+
+        attackers & self.pieces_by_color(color)
+    }
+
+    pub fn is_square_attacked(&self, square: Square, color: Color) -> bool {
+        self.attackers_to(square, color).is_non_empty()
+    }
+
+    /// Returns all squares attacked by `color`, including squares of its own pieces.
+    pub fn attacked_squares(&self, color: Color) -> Bitboard {
+        let all = self.pieces();
+        let pieces = self.pieces_by_color(color);
+        let mut attacks =
+            Bitboard::pawn_attacks(self.pieces_by_kind(PieceKind::Pawn) & pieces, color);
+
+        for from in self.pieces_by_kind(PieceKind::Knight) & pieces {
+            attacks |= Bitboard::knight_attacks(from);
+        }
+        for from in self.pieces_by_kind(PieceKind::King) & pieces {
+            attacks |= Bitboard::king_attacks(from);
+        }
+        for from in (self.pieces_by_kind(PieceKind::Bishop) | self.pieces_by_kind(PieceKind::Queen))
+            & pieces
+        {
+            attacks |= Bitboard::bishop_attacks(from, all);
+        }
+        for from in
+            (self.pieces_by_kind(PieceKind::Rook) | self.pieces_by_kind(PieceKind::Queen)) & pieces
+        {
+            attacks |= Bitboard::rook_attacks(from, all);
+        }
+
+        attacks
+    }
+
+    /// Returns the pieces giving check to the side to move.
+    pub fn checkers(&self) -> Bitboard {
+        match self.king_square(self.color_to_move()) {
+            Some(king) => self.attackers_to(king, !self.color_to_move()),
+            None => Bitboard::EMPTY,
+        }
+    }
+
+    /// Returns the pieces standing alone between `square` and a slider of `color` that
+    /// would otherwise attack it.
+    fn slider_blockers(&self, square: Square, color: Color) -> Bitboard {
+        let sliders = (((self.pieces_by_kind(PieceKind::Bishop)
+            | self.pieces_by_kind(PieceKind::Queen))
+            & Bitboard::bishop_mask(square))
+            | ((self.pieces_by_kind(PieceKind::Rook) | self.pieces_by_kind(PieceKind::Queen))
+                & Bitboard::rook_mask(square)))
+            & self.pieces_by_color(color);
+
+        let mut blockers = Bitboard::EMPTY;
+        for from in sliders {
+            let between = Bitboard::between(square, from) & self.pieces();
+            if between.popcount() == 1 {
+                blockers |= between;
+            }
+        }
+
+        blockers
+    }
+
+    /// Returns the pieces of `color` that are pinned to their king.
+    /// # Example
+    /// ```
+    /// # use chess::{board::Board, color::Color, square::Square};
+    /// let board = Board::from_fen("4k3/4r3/8/8/4N3/8/8/4K3 w - - 0 1").unwrap();
+    /// assert_eq!(board.pinned(Color::White).first(), Some(Square::E4));
+    /// assert!(board.pinned(Color::Black).is_empty());
+    /// ```
+    pub fn pinned(&self, color: Color) -> Bitboard {
+        match self.king_square(color) {
+            Some(king) => self.slider_blockers(king, !color) & self.pieces_by_color(color),
+            None => Bitboard::EMPTY,
+        }
+    }
+
+    /// Returns the pieces of the side to move that give a discovered check by moving off
+    /// the line between one of its sliders and the enemy king.
+    pub fn discovered_check_candidates(&self) -> Bitboard {
+        let us = self.color_to_move();
+        match self.king_square(!us) {
+            Some(king) => self.slider_blockers(king, us) & self.pieces_by_color(us),
+            None => Bitboard::EMPTY,
+        }
+    }
+
+    /// Returns true if the legal move `mv` checks the opponent.
+    /// # Example
+    /// ```
+    /// # use chess::{board::Board, uci};
+    /// let board = Board::from_fen("4k3/8/8/8/8/8/8/R3K3 w Q - 0 1").unwrap();
+    /// assert!(board.gives_check(uci::parse_move("a1a8", &board).unwrap()));
+    /// assert!(!board.gives_check(uci::parse_move("e1c1", &board).unwrap()));
+    /// ```
+    pub fn gives_check(&self, mv: Move) -> bool {
+        self.do_move(mv).is_in_check()
+    }
+
     /// Removes and returns the piece on `square`.
     fn take_piece(&mut self, square: Square) -> Option<Piece> {
         let piece = self.at(square).unwrap();
 
         let bb = Bitboard::new(square);
-        match piece.color() {
-            Color::Black => self.bb[0] ^= bb,
-            _ => (),
+        if piece.color() == Color::Black {
+            self.bb[0] ^= bb;
         }
 
         match piece.kind() {
@@ -707,9 +1383,8 @@ impl Board {
         let piece = self.at(from).unwrap();
 
         let bb = Bitboard::new(from) | to;
-        match piece.color() {
-            Color::Black => self.bb[0] ^= bb,
-            _ => (),
+        if piece.color() == Color::Black {
+            self.bb[0] ^= bb;
         }
 
         match piece.kind() {
@@ -734,6 +1409,139 @@ impl Board {
     pub fn legal_moves(&self) -> Vec<Move> {
         generate_moves(self).into_iter().collect()
     }
+
+    /// Returns true unless the side to move is mated or stalemated. Stops generating moves
+    /// as soon as one is found.
+    /// # Example
+    /// ```
+    /// # use chess::board::Board;
+    /// assert!(Board::default().has_legal_moves());
+    /// let board = Board::from_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+    /// assert!(!board.has_legal_moves());
+    /// ```
+    pub fn has_legal_moves(&self) -> bool {
+        has_legal_moves(self)
+    }
+
+    /// Moves that may leave the own king in check, see `generate_pseudo_legal_moves`.
+    pub fn pseudo_legal_moves(&self) -> Vec<Move> {
+        generate_pseudo_legal_moves(self).into_iter().collect()
+    }
+
+    /// Returns true if `mv` is generated by `pseudo_legal_moves`, i.e. it moves a piece of
+    /// the side to move according to its movement rules. Any 16-bit move is accepted.
+    pub fn is_pseudo_legal(&self, mv: Move) -> bool {
+        // Move kinds 13 to 15 do not exist
+        if mv.to_index() >> 12 > 12 {
+            return false;
+        }
+
+        let us = self.color_to_move();
+        let (from, to) = (mv.from(), mv.to());
+        let Some(piece) = self.at(from).filter(|p| p.color() == us) else {
+            return false;
+        };
+        let target = self.at(to);
+        if target.is_some_and(|p| p.color() == us) {
+            return false;
+        }
+
+        let promotion_rank = match us {
+            Color::White => Rank::Eighth,
+            Color::Black => Rank::First,
+        };
+        let push = match piece.kind() {
+            PieceKind::Pawn => Bitboard::new(from).shift(us.up()),
+            _ => Bitboard::EMPTY,
+        };
+        let pawn_captures = Bitboard::pawn_attacks(Bitboard::new(from), us);
+        let promotes = to.rank() == promotion_rank;
+
+        let attacks = match piece.kind() {
+            PieceKind::Pawn => Bitboard::EMPTY,
+            PieceKind::Knight => Bitboard::knight_attacks(from),
+            PieceKind::Bishop => Bitboard::bishop_attacks(from, self.pieces()),
+            PieceKind::Rook => Bitboard::rook_attacks(from, self.pieces()),
+            PieceKind::Queen => {
+                Bitboard::bishop_attacks(from, self.pieces())
+                    | Bitboard::rook_attacks(from, self.pieces())
+            }
+            PieceKind::King => Bitboard::king_attacks(from),
+        };
+        let is_pawn = piece.kind() == PieceKind::Pawn;
+
+        match mv.kind() {
+            MoveKind::Quiet if is_pawn => {
+                (push & to).is_non_empty() && !promotes && target.is_none()
+            }
+            MoveKind::Quiet => (attacks & to).is_non_empty() && target.is_none(),
+            MoveKind::Cap if is_pawn => {
+                (pawn_captures & to).is_non_empty() && !promotes && target.is_some()
+            }
+            MoveKind::Cap => (attacks & to).is_non_empty() && target.is_some(),
+            MoveKind::Double => {
+                let home_rank = match us {
+                    Color::White => Rank::Second,
+                    Color::Black => Rank::Seventh,
+                };
+                is_pawn
+                    && from.rank() == home_rank
+                    && (push.shift(us.up()) & to).is_non_empty()
+                    && (self.pieces() & (push | to)).is_empty()
+            }
+            MoveKind::EnPassant => {
+                is_pawn
+                    && self.en_passant_square() == Some(to)
+                    && (pawn_captures & to).is_non_empty()
+            }
+            MoveKind::Castling => {
+                let mut moves = Movelist::new();
+                if piece.kind() == PieceKind::King {
+                    castling_moves(
+                        self,
+                        from,
+                        self.attacked_squares(!us),
+                        Bitboard::FULL,
+                        &mut moves,
+                    );
+                }
+                moves.into_iter().any(|m| m == mv)
+            }
+            MoveKind::PromKnight
+            | MoveKind::PromBishop
+            | MoveKind::PromRook
+            | MoveKind::PromQueen => {
+                is_pawn && promotes && (push & to).is_non_empty() && target.is_none()
+            }
+            MoveKind::PromCapKnight
+            | MoveKind::PromCapBishop
+            | MoveKind::PromCapRook
+            | MoveKind::PromCapQueen => {
+                is_pawn && promotes && (pawn_captures & to).is_non_empty() && target.is_some()
+            }
+        }
+    }
+
+    /// Returns true if `mv` is one of the legal moves, without generating them.
+    /// # Example
+    /// ```
+    /// # use chess::{board::Board, moves::{Move, MoveKind}, square::Square};
+    /// let board = Board::from_fen("4k3/8/8/8/8/8/4r3/4K3 w - - 0 1").unwrap();
+    /// assert!(board.is_legal(Move::new(Square::E1, Square::E2, MoveKind::Cap)));
+    /// assert!(!board.is_legal(Move::new(Square::E1, Square::D2, MoveKind::Quiet)));
+    /// assert!(board.is_pseudo_legal(Move::new(Square::E1, Square::D2, MoveKind::Quiet)));
+    /// ```
+    pub fn is_legal(&self, mv: Move) -> bool {
+        if !self.is_pseudo_legal(mv) {
+            return false;
+        }
+
+        let us = self.color_to_move();
+        let after = self.do_move(mv);
+        after
+            .king_square(us)
+            .is_none_or(|king| !after.is_square_attacked(king, !us))
+    }
 }
 
 #[cfg(test)]
@@ -777,4 +1585,532 @@ mod tests {
             Some(Piece::BlackPawn)
         );
     }
+
+    /// Checks that every position reachable in `depth` plies survives packing.
+    fn packed_round_trip(board: &Board, depth: usize) -> usize {
+        let packed = board.to_packed().unwrap();
+        let unpacked = Board::from_packed(&packed).unwrap();
+        assert_eq!(unpacked, *board);
+        assert_eq!(unpacked.polyglot_key(), board.polyglot_key());
+        assert_eq!(unpacked.to_packed().unwrap(), packed);
+
+        if depth == 0 {
+            return 1;
+        }
+
+        board
+            .legal_moves()
+            .into_iter()
+            .map(|mv| packed_round_trip(&board.do_move(mv), depth - 1))
+            .sum()
+    }
+
+    #[test]
+    fn packed_positions() {
+        let fens = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+        ];
+
+        let positions: usize = fens
+            .iter()
+            .map(|fen| packed_round_trip(&Board::from_fen(fen).unwrap(), 3))
+            .sum();
+        assert!(positions > 100_000);
+    }
+
+    #[test]
+    fn packed_malformed_input() {
+        let mut rng = crate::rng::Rng::new(1);
+        let valid =
+            Board::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R b Kq - 3 17")
+                .unwrap()
+                .to_packed()
+                .unwrap();
+
+        for _ in 0..100_000 {
+            // Random bytes, and valid input with a few bits flipped
+            let mut bytes = valid;
+            if rng.below(2) == 0 {
+                bytes.iter_mut().for_each(|b| *b = rng.next_u64() as u8);
+                bytes[0] = Board::PACKED_VERSION;
+            } else {
+                for _ in 0..1 + rng.below(3) {
+                    let bit = rng.below(8 * Board::PACKED_SIZE as u64);
+                    bytes[bit as usize / 8] ^= 1 << (bit % 8);
+                }
+            }
+
+            // Anything that unpacks packs back to the same bytes
+            if let Ok(board) = Board::from_packed(&bytes) {
+                assert_eq!(board.to_packed().unwrap(), bytes);
+            }
+        }
+
+        let mut bytes = valid;
+        bytes[0] = 0;
+        assert!(Board::from_packed(&bytes).is_err());
+        bytes = valid;
+        bytes[31] = 1;
+        assert!(Board::from_packed(&bytes).is_err());
+    }
+
+    #[test]
+    fn packed_too_many_pieces() {
+        let full =
+            Board::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w - - 0 1").unwrap();
+        assert!(full.to_packed().is_ok());
+
+        let board =
+            Board::from_fen("rnbqkbnr/pppppppp/8/8/8/7N/PPPPPPPP/RNBQKBNR w - - 0 1").unwrap();
+        assert_eq!(
+            board.to_packed(),
+            Err(PositionError::TooManyPieces(Color::White))
+        );
+
+        // Enough pieces to overflow the piece nibbles
+        let board =
+            Board::from_fen("qqqqkqqq/qqqqqqqq/qqqqqqqq/8/8/NNNNNNNN/NNNNNNNN/NNNNKNNN b - - 0 1")
+                .unwrap();
+        assert_eq!(
+            board.to_packed(),
+            Err(PositionError::TooManyPieces(Color::White))
+        );
+    }
+
+    #[test]
+    fn validate_positions() {
+        let valid = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+            "8/8/8/8/3Pp3/8/8/k6K b - d3 0 70",
+        ];
+        for fen in valid {
+            let board = Board::from_fen_with_mode(fen, FenMode::Strict).unwrap();
+            assert_eq!(board.fen(), fen);
+        }
+
+        let invalid = [
+            (
+                "8/8/8/8/8/8/8/4K3 w - - 0 1",
+                PositionError::MissingKing(Color::Black),
+            ),
+            (
+                "4k3/8/8/8/8/8/8/3KK3 w - - 0 1",
+                PositionError::TooManyKings(Color::White),
+            ),
+            (
+                "4k3/pppppppp/p7/8/8/8/8/4K3 w - - 0 1",
+                PositionError::TooManyPawns(Color::Black),
+            ),
+            (
+                "4k3/8/8/8/8/8/8/4K2P w - - 0 1",
+                PositionError::PawnOnBackRank(Square::H1),
+            ),
+            (
+                "4k3/8/8/8/8/8/8/4K3 w K - 0 1",
+                PositionError::InvalidCastlingRights(CastlingRights::Kingside(Color::White)),
+            ),
+            (
+                "4k2r/8/8/8/8/8/8/4K3 w q - 0 1",
+                PositionError::InvalidCastlingRights(CastlingRights::Queenside(Color::Black)),
+            ),
+            (
+                "4k3/8/8/8/8/8/8/4K3 w - e6 0 1",
+                PositionError::InvalidEnPassant(Square::E6),
+            ),
+            (
+                "4k3/8/8/8/4R3/8/8/4K3 w - - 0 1",
+                PositionError::OpponentInCheck,
+            ),
+        ];
+        for (fen, error) in invalid {
+            let board = Board::from_fen(fen).unwrap();
+            assert_eq!(board.validate(), Err(error.clone()));
+
+            let e = Board::from_fen_with_mode(fen, FenMode::Strict)
+                .err()
+                .unwrap();
+            assert_eq!(e.field(), None);
+            assert_eq!(e.reason(), &FenErrorReason::IllegalPosition(error));
+        }
+
+        // Lenient boards without kings must not panic
+        assert!(!Board::from_fen("8/8/8/8/8/8/8/8 w - - 0 1")
+            .unwrap()
+            .is_in_check());
+    }
+
+    #[test]
+    fn strict_fen_errors() {
+        let errors = [
+            ("", 0, FenErrorReason::MissingField),
+            ("4k3/8/8/8/8/8/8/4K3", 1, FenErrorReason::MissingField),
+            (
+                "4k3/8/8/8/8/8/4K3 w - - 0 1",
+                0,
+                FenErrorReason::InvalidRankCount(7),
+            ),
+            (
+                "4k3/8/8/8/8/8/8/4K3/8 w - - 0 1",
+                0,
+                FenErrorReason::InvalidRankCount(9),
+            ),
+            (
+                "4k3/8/8/8/8/8/8/4K4 w - - 0 1",
+                0,
+                FenErrorReason::InvalidRank(1),
+            ),
+            (
+                "4k2/8/8/8/8/8/8/4K3 w - - 0 1",
+                0,
+                FenErrorReason::InvalidRank(8),
+            ),
+            (
+                "4k3/8/8/8/8/8/8/4X3 w - - 0 1",
+                0,
+                FenErrorReason::UnexpectedCharacter('X'),
+            ),
+            (
+                "4k3/8/8/8/8/8/8/4K3 x - - 0 1",
+                1,
+                FenErrorReason::UnexpectedCharacter('x'),
+            ),
+            (
+                "r3k2r/8/8/8/8/8/8/R3K2R w QK - 0 1",
+                2,
+                FenErrorReason::UnexpectedCharacter('K'),
+            ),
+            (
+                "r3k2r/8/8/8/8/8/8/R3K2R w K- - 0 1",
+                2,
+                FenErrorReason::UnexpectedCharacter('-'),
+            ),
+            (
+                "4k3/8/8/8/8/8/8/4K3 w - e3 0 1",
+                3,
+                FenErrorReason::InvalidSquare("e3".to_owned()),
+            ),
+            (
+                "4k3/8/8/8/8/8/8/4K3 w - z9 0 1",
+                3,
+                FenErrorReason::InvalidSquare("z9".to_owned()),
+            ),
+            (
+                "4k3/8/8/8/8/8/8/4K3 w - - 0",
+                5,
+                FenErrorReason::MissingField,
+            ),
+            (
+                "4k3/8/8/8/8/8/8/4K3 w - - -1 1",
+                4,
+                FenErrorReason::InvalidNumber("-1".to_owned()),
+            ),
+            (
+                "4k3/8/8/8/8/8/8/4K3 w - - 0 0",
+                5,
+                FenErrorReason::InvalidNumber("0".to_owned()),
+            ),
+            (
+                "4k3/8/8/8/8/8/8/4K3 w - - 0 1 x",
+                6,
+                FenErrorReason::TooManyFields,
+            ),
+        ];
+
+        for (fen, field, reason) in errors {
+            let e = Board::from_fen_with_mode(fen, FenMode::Strict)
+                .err()
+                .unwrap();
+            assert_eq!((e.field(), e.reason()), (Some(field), &reason), "{}", fen);
+        }
+
+        // Lenient parsing falls back to the default move counters
+        let board = Board::from_fen("4k3/8/8/8/8/8/8/4K3 w - - x 0").unwrap();
+        assert_eq!(board.fen(), "4k3/8/8/8/8/8/8/4K3 w - - 0 1");
+        let board = Board::from_fen("4k3/8/8/8/8/8/8/4K3 b - - 500").unwrap();
+        assert_eq!(board.fen(), "4k3/8/8/8/8/8/8/4K3 b - - 127 1");
+    }
+
+    #[test]
+    fn malformed_fen_never_panics() {
+        let mut rng = crate::rng::Rng::new(41);
+        let alphabet: Vec<char> = "pnbrqkPNBRQKwb-/ 0123456789aceghx".chars().collect();
+        let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+
+        for _ in 0..20_000 {
+            let mut chars: Vec<char> = fen.chars().collect();
+            for _ in 0..1 + rng.below(4) {
+                let i = rng.below(chars.len() as u64) as usize;
+                let c = alphabet[rng.below(alphabet.len() as u64) as usize];
+                match rng.below(3) {
+                    0 => chars[i] = c,
+                    1 => chars.insert(i, c),
+                    _ => {
+                        chars.remove(i);
+                    }
+                }
+            }
+            let input: String = chars.into_iter().collect();
+
+            if let Ok(board) = Board::from_fen_with_mode(&input, FenMode::Strict) {
+                assert!(board.validate().is_ok());
+                board.is_in_check();
+                board.legal_moves();
+            }
+            let _ = Board::from_fen(&input);
+        }
+    }
+
+    /// Squares attacked by the piece on `from`, computed square by square.
+    fn naive_attacks(board: &Board, from: Square) -> Vec<usize> {
+        let piece = board.at(from).unwrap();
+        let (rank, file) = ((from.to_index() / 8) as i32, (from.to_index() % 8) as i32);
+        let up = if piece.color() == Color::White { 1 } else { -1 };
+
+        let diagonal = [(1, 1), (1, -1), (-1, 1), (-1, -1)];
+        let straight = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+        let knight = [
+            (1, 2),
+            (2, 1),
+            (2, -1),
+            (1, -2),
+            (-1, -2),
+            (-2, -1),
+            (-2, 1),
+            (-1, 2),
+        ];
+        let (steps, slides): (Vec<(i32, i32)>, bool) = match piece.kind() {
+            PieceKind::Pawn => (vec![(up, 1), (up, -1)], false),
+            PieceKind::Knight => (knight.to_vec(), false),
+            PieceKind::Bishop => (diagonal.to_vec(), true),
+            PieceKind::Rook => (straight.to_vec(), true),
+            PieceKind::Queen => ([diagonal, straight].concat(), true),
+            PieceKind::King => ([diagonal, straight].concat(), false),
+        };
+
+        let mut squares = Vec::new();
+        for (dr, df) in steps {
+            let (mut r, mut f) = (rank + dr, file + df);
+            while (0..8).contains(&r) && (0..8).contains(&f) {
+                let square = (r * 8 + f) as usize;
+                squares.push(square);
+                if !slides || board.at(Square::from_index(square)).is_some() {
+                    break;
+                }
+                r += dr;
+                f += df;
+            }
+        }
+        squares
+    }
+
+    fn naive_attackers(board: &Board, square: Square, color: Color) -> Vec<usize> {
+        (0..64)
+            .filter(|&i| {
+                let from = Square::from_index(i);
+                board.at(from).is_some_and(|p| p.color() == color)
+                    && naive_attacks(board, from).contains(&square.to_index())
+            })
+            .collect()
+    }
+
+    /// Pieces of `color` whose removal lets a slider of `attacker` reach `target`.
+    fn naive_blockers(board: &Board, target: Square, color: Color, attacker: Color) -> Vec<usize> {
+        let before = naive_attackers(board, target, attacker);
+        (0..64)
+            .filter(|&i| {
+                let square = Square::from_index(i);
+                if square == target || board.at(square).is_none_or(|p| p.color() != color) {
+                    return false;
+                }
+                let mut removed = board.clone();
+                removed.take_piece(square);
+                naive_attackers(&removed, target, attacker)
+                    .iter()
+                    .any(|s| !before.contains(s))
+            })
+            .collect()
+    }
+
+    fn indices(bb: Bitboard) -> Vec<usize> {
+        bb.into_iter().map(|s| s.to_index()).collect()
+    }
+
+    #[test]
+    fn attack_queries() {
+        let fens = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+        ];
+        let mut rng = crate::rng::Rng::new(42);
+
+        for fen in fens {
+            let mut board = Board::from_fen(fen).unwrap();
+
+            for _ in 0..60 {
+                let us = board.color_to_move();
+                let our_king = board.king_square(us).unwrap();
+                let their_king = board.king_square(!us).unwrap();
+
+                for color in [Color::White, Color::Black] {
+                    let mut attacked = Vec::new();
+                    for i in 0..64 {
+                        let square = Square::from_index(i);
+                        let naive = naive_attackers(&board, square, color);
+                        assert_eq!(indices(board.attackers_to(square, color)), naive);
+                        assert_eq!(board.is_square_attacked(square, color), !naive.is_empty());
+                        if !naive.is_empty() {
+                            attacked.push(i);
+                        }
+                    }
+                    assert_eq!(indices(board.attacked_squares(color)), attacked);
+                }
+
+                assert_eq!(
+                    indices(board.checkers()),
+                    naive_attackers(&board, our_king, !us)
+                );
+                assert_eq!(board.checkers().is_non_empty(), board.is_in_check());
+                assert_eq!(
+                    indices(board.pinned(us)),
+                    naive_blockers(&board, our_king, us, !us)
+                );
+                assert_eq!(
+                    indices(board.pinned(!us)),
+                    naive_blockers(&board, their_king, !us, us)
+                );
+                assert_eq!(
+                    indices(board.discovered_check_candidates()),
+                    naive_blockers(&board, their_king, us, us)
+                );
+
+                let moves = board.legal_moves();
+                for &mv in &moves {
+                    let after = board.do_move(mv);
+                    let king = after.king_square(!us).unwrap();
+                    let checks = !naive_attackers(&after, king, us).is_empty();
+                    assert_eq!(board.gives_check(mv), checks);
+                }
+
+                if moves.is_empty() {
+                    break;
+                }
+                board = board.do_move(moves[rng.below(moves.len() as u64) as usize]);
+            }
+        }
+    }
+
+    #[test]
+    fn move_legality() {
+        let fens = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "8/8/8/2k5/3Pp3/8/8/4K2R b K d3 0 1",
+        ];
+        let mut rng = crate::rng::Rng::new(43);
+
+        for fen in fens {
+            let mut board = Board::from_fen(fen).unwrap();
+
+            for _ in 0..40 {
+                let legal: Vec<u16> = board.legal_moves().iter().map(|m| m.to_index()).collect();
+                let pseudo: Vec<u16> = board
+                    .pseudo_legal_moves()
+                    .iter()
+                    .map(|m| m.to_index())
+                    .collect();
+
+                let mut filtered: Vec<u16> = pseudo
+                    .iter()
+                    .copied()
+                    .filter(|&i| board.is_legal(Move::from_index(i)))
+                    .collect();
+                let mut sorted = legal.clone();
+                filtered.sort();
+                sorted.sort();
+                assert_eq!(filtered, sorted, "{}", board.fen());
+
+                for _ in 0..5000 {
+                    let i = rng.below(1 << 16) as u16;
+                    let mv = Move::from_index(i);
+                    assert_eq!(board.is_pseudo_legal(mv), pseudo.contains(&i), "{}", mv);
+                    assert_eq!(board.is_legal(mv), legal.contains(&i), "{}", mv);
+                }
+
+                if legal.is_empty() {
+                    break;
+                }
+                let i = legal[rng.below(legal.len() as u64) as usize];
+                board = board.do_move(Move::from_index(i));
+            }
+        }
+    }
+
+    #[test]
+    fn equality_and_hashing() {
+        use std::collections::HashSet;
+
+        let play = |board: &Board, moves: &str| {
+            moves.split_whitespace().fold(board.clone(), |b, s| {
+                let moves = b.legal_moves();
+                b.do_move(*moves.iter().find(|mv| mv.to_string() == s).unwrap())
+            })
+        };
+
+        let start = Board::default();
+        let fen = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        assert_eq!(start, Board::from_fen(fen).unwrap());
+
+        // Transpositions are equal, returning to a position only has the same placement
+        let a = play(&start, "e2e3 d7d6 d2d3 e7e6");
+        let b = play(&start, "d2d3 e7e6 e2e3 d7d6");
+        let c = play(&start, "g1f3 g8f6 f3g1 f6g8");
+        assert_eq!(a, b);
+        assert_ne!(c, start);
+        assert!(c.same_position(&start));
+        assert!(!a.same_position(&start));
+
+        let positions: HashSet<Board> = [a, b, c, start.clone()].into_iter().collect();
+        assert_eq!(positions.len(), 3);
+
+        let moves: HashSet<Move> = start.legal_moves().into_iter().collect();
+        assert_eq!(moves.len(), 20);
+        assert!(start.legal_moves().iter().all(|mv| moves.contains(mv)));
+        assert!(!moves.contains(&Move::default()));
+        assert_eq!(Move::default(), Move::null());
+        assert_eq!(Bitboard::default(), Bitboard::EMPTY);
+
+        let mut squares = vec![Square::A2, Square::H1, Square::A1, Square::H8];
+        squares.sort();
+        assert_eq!(squares, [Square::A1, Square::H1, Square::A2, Square::H8]);
+        assert!(Rank::First < Rank::Eighth && File::A < File::H);
+        assert!(Piece::WhitePawn < Piece::WhiteKing && Piece::WhiteKing < Piece::BlackPawn);
+    }
+
+    #[test]
+    fn incremental_polyglot_key() {
+        let mut rng = crate::rng::Rng::new(7);
+        for _ in 0..50 {
+            let mut board = Board::default();
+            let mut key = board.polyglot_key();
+            for _ in 0..200 {
+                let moves = board.legal_moves();
+                if moves.is_empty() {
+                    break;
+                }
+                let next = board.do_move(moves[rng.below(moves.len() as u64) as usize]);
+                key = board.polyglot_key_after(key, &next);
+                assert_eq!(key, next.polyglot_key(), "{next:?}");
+                board = next;
+            }
+        }
+    }
 }
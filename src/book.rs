@@ -0,0 +1,423 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, Write},
+    path::Path,
+};
+
+use crate::{
+    board::Board,
+    color::Color,
+    moves::{generate_moves, Move, MoveKind},
+    pgn::{GameResult, ParsePgnError, PgnGame},
+    piece::PieceKind,
+    rng::Rng,
+};
+
+/// A single entry of a Polyglot opening book.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BookEntry {
+    pub key: u64,
+    pub mv: u16,
+    pub weight: u16,
+    pub learn: u32,
+}
+
+impl BookEntry {
+    /// Size of an entry in a book file, in bytes.
+    pub const SIZE: usize = 16;
+
+    /// Decodes an entry from its big-endian on-disk representation.
+    pub fn from_bytes(bytes: &[u8; Self::SIZE]) -> Self {
+        Self {
+            key: u64::from_be_bytes(bytes[0..8].try_into().unwrap()),
+            mv: u16::from_be_bytes(bytes[8..10].try_into().unwrap()),
+            weight: u16::from_be_bytes(bytes[10..12].try_into().unwrap()),
+            learn: u32::from_be_bytes(bytes[12..16].try_into().unwrap()),
+        }
+    }
+
+    /// Encodes the entry in its big-endian on-disk representation.
+    pub fn to_bytes(self) -> [u8; Self::SIZE] {
+        let mut bytes = [0; Self::SIZE];
+        bytes[0..8].copy_from_slice(&self.key.to_be_bytes());
+        bytes[8..10].copy_from_slice(&self.mv.to_be_bytes());
+        bytes[10..12].copy_from_slice(&self.weight.to_be_bytes());
+        bytes[12..16].copy_from_slice(&self.learn.to_be_bytes());
+        bytes
+    }
+
+    /// Returns the legal move in `board` this entry refers to, if any.
+    pub fn to_move(self, board: &Board) -> Option<Move> {
+        generate_moves(board)
+            .into_iter()
+            .find(|&mv| encode_move(mv) == self.mv)
+    }
+}
+
+/// Encodes a move in the Polyglot format.
+///
+/// Polyglot encodes castling as the king capturing its own rook, e.g. e1h1 instead of e1g1.
+/// # Example
+/// ```
+/// # use chess::{book::encode_move, moves::{Move, MoveKind}, square::Square};
+/// assert_eq!(encode_move(Move::new(Square::E2, Square::E4, MoveKind::Double)), 0x031c);
+/// let castling = Move::new(Square::E1, Square::G1, MoveKind::Castling);
+/// assert_eq!(encode_move(castling), encode_move(Move::new(Square::E1, Square::H1, MoveKind::Quiet)));
+/// ```
+pub fn encode_move(mv: Move) -> u16 {
+    let from = mv.from().to_index();
+    let to = match mv.kind() {
+        MoveKind::Castling if mv.to().to_index() > from => from + 3,
+        MoveKind::Castling => from - 4,
+        _ => mv.to().to_index(),
+    };
+
+    let promotion = match mv.promotion_kind() {
+        Some(PieceKind::Knight) => 1,
+        Some(PieceKind::Bishop) => 2,
+        Some(PieceKind::Rook) => 3,
+        Some(PieceKind::Queen) => 4,
+        _ => 0,
+    };
+
+    (to | (from << 6) | (promotion << 12)) as u16
+}
+
+/// How to choose between several book moves.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BookSelection {
+    /// Always play the move with the highest weight.
+    Best,
+    /// Pick randomly with probability proportional to the weight.
+    WeightedRandom,
+}
+
+/// Polyglot opening book.
+#[derive(Debug, Clone, Default)]
+pub struct Book {
+    entries: Vec<BookEntry>,
+}
+
+impl Book {
+    /// Reads a Polyglot `.bin` book.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Ok(Self::from_bytes(&fs::read(path)?))
+    }
+
+    /// Reads book entries from raw bytes. A truncated trailing entry is ignored.
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        Self::from_entries(
+            bytes
+                .chunks_exact(BookEntry::SIZE)
+                .map(|chunk| BookEntry::from_bytes(chunk.try_into().unwrap()))
+                .collect(),
+        )
+    }
+
+    pub fn from_entries(mut entries: Vec<BookEntry>) -> Self {
+        // Books should already be sorted, but lookups rely on it
+        entries.sort_by_key(|e| e.key);
+
+        Self { entries }
+    }
+
+    /// Writes the book in Polyglot format.
+    pub fn write<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        for entry in &self.entries {
+            writer.write_all(&entry.to_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Writes the book to a Polyglot `.bin` file.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut writer = io::BufWriter::new(fs::File::create(path)?);
+        self.write(&mut writer)?;
+        writer.flush()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns all entries with Polyglot key `key`.
+    pub fn entries(&self, key: u64) -> &[BookEntry] {
+        let start = self.entries.partition_point(|e| e.key < key);
+        let end = self.entries.partition_point(|e| e.key <= key);
+        &self.entries[start..end]
+    }
+
+    /// Returns the legal book moves and their weights in `board`.
+    pub fn moves(&self, board: &Board) -> Vec<(Move, u16)> {
+        self.entries(board.polyglot_key())
+            .iter()
+            .filter_map(|e| e.to_move(board).map(|mv| (mv, e.weight)))
+            .collect()
+    }
+
+    /// Chooses a book move for `board`, or `None` if the position is not in the book.
+    pub fn pick(&self, board: &Board, selection: BookSelection, rng: &mut Rng) -> Option<Move> {
+        let moves = self.moves(board);
+
+        match selection {
+            BookSelection::Best => moves.iter().max_by_key(|(_, w)| *w).map(|&(mv, _)| mv),
+            BookSelection::WeightedRandom => {
+                let total: u64 = moves.iter().map(|&(_, w)| w as u64).sum();
+                if total == 0 {
+                    // All weights are zero, treat the moves as equal
+                    return match moves.len() {
+                        0 => None,
+                        n => Some(moves[rng.below(n as u64) as usize].0),
+                    };
+                }
+
+                let mut r = rng.below(total);
+                for &(mv, w) in &moves {
+                    if r < w as u64 {
+                        return Some(mv);
+                    }
+                    r -= w as u64;
+                }
+                None
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct MoveStats {
+    games: u32,
+    // Half points from the perspective of the side making the move
+    score: u32,
+}
+
+/// Builds a Polyglot book from game collections.
+///
+/// Moves are weighted by result like Polyglot does: two points per win and one per draw
+/// for the side playing the move.
+#[derive(Debug, Clone)]
+pub struct BookBuilder {
+    /// Only the first `max_plies` moves of each game are added.
+    pub max_plies: usize,
+    /// Moves played in fewer games than this are left out of the book.
+    pub min_games: u32,
+    /// Games where either player is rated below this (or unrated) are skipped.
+    pub min_elo: u32,
+    stats: HashMap<(u64, u16), MoveStats>,
+}
+
+impl Default for BookBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BookBuilder {
+    pub fn new() -> Self {
+        Self {
+            max_plies: 20,
+            min_games: 1,
+            min_elo: 0,
+            stats: HashMap::new(),
+        }
+    }
+
+    fn elo_ok(&self, game: &PgnGame, tag: &str) -> bool {
+        self.min_elo == 0
+            || game
+                .tag(tag)
+                .and_then(|elo| elo.parse::<u32>().ok())
+                .is_some_and(|elo| elo >= self.min_elo)
+    }
+
+    /// Adds the moves of `game` to the book statistics. Returns `false` if the game was
+    /// skipped, either due to the Elo filter or because it has no result.
+    pub fn add_game(&mut self, game: &PgnGame) -> Result<bool, ParsePgnError> {
+        let result = match game.result {
+            Some(result) => result,
+            None => return Ok(false),
+        };
+
+        if !self.elo_ok(game, "WhiteElo") || !self.elo_ok(game, "BlackElo") {
+            return Ok(false);
+        }
+
+        for (board, mv) in game.replay(self.max_plies)? {
+            let score = match (result, board.color_to_move()) {
+                (GameResult::Draw, _) => 1,
+                (GameResult::WhiteWins, Color::White) | (GameResult::BlackWins, Color::Black) => 2,
+                _ => 0,
+            };
+
+            let stats = self
+                .stats
+                .entry((board.polyglot_key(), encode_move(mv)))
+                .or_default();
+            stats.games += 1;
+            stats.score += score;
+        }
+
+        Ok(true)
+    }
+
+    /// Creates the book from the collected statistics.
+    pub fn build(&self) -> Book {
+        let mut entries: Vec<(u64, u16, u32)> = self
+            .stats
+            .iter()
+            .filter(|(_, s)| s.games >= self.min_games && s.score > 0)
+            .map(|(&(key, mv), s)| (key, mv, s.score))
+            .collect();
+
+        // Sorted by key, best moves first
+        entries.sort_by(|a, b| a.0.cmp(&b.0).then(b.2.cmp(&a.2)).then(a.1.cmp(&b.1)));
+
+        let mut book = Vec::with_capacity(entries.len());
+        for group in entries.chunk_by(|a, b| a.0 == b.0) {
+            // Scale weights of a position to fit 16 bits
+            let max = group[0].2 as u64;
+            for &(key, mv, score) in group {
+                let weight = if max > u16::MAX as u64 {
+                    (score as u64 * u16::MAX as u64 / max).max(1)
+                } else {
+                    score as u64
+                };
+
+                book.push(BookEntry {
+                    key,
+                    mv,
+                    weight: weight as u16,
+                    learn: 0,
+                });
+            }
+        }
+
+        Book { entries: book }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::square::Square;
+
+    fn entry_bytes(key: u64, from: Square, to: Square, weight: u16) -> Vec<u8> {
+        let mv = (to.to_index() | (from.to_index() << 6)) as u16;
+        let mut bytes = key.to_be_bytes().to_vec();
+        bytes.extend_from_slice(&mv.to_be_bytes());
+        bytes.extend_from_slice(&weight.to_be_bytes());
+        bytes.extend_from_slice(&0u32.to_be_bytes());
+        bytes
+    }
+
+    #[test]
+    fn book_lookup() {
+        let board = Board::default();
+        let key = board.polyglot_key();
+
+        let mut bytes = entry_bytes(1, Square::A2, Square::A3, 1);
+        bytes.extend(entry_bytes(key, Square::E2, Square::E4, 10));
+        bytes.extend(entry_bytes(key, Square::D2, Square::D4, 5));
+        bytes.extend(entry_bytes(u64::MAX, Square::A2, Square::A3, 1));
+
+        let book = Book::from_bytes(&bytes);
+        assert_eq!(book.len(), 4);
+        assert_eq!(book.entries(key).len(), 2);
+        assert!(book.entries(2).is_empty());
+
+        let moves: Vec<String> = book
+            .moves(&board)
+            .iter()
+            .map(|(mv, _)| mv.to_string())
+            .collect();
+        assert_eq!(moves, ["e2e4", "d2d4"]);
+
+        let mut rng = Rng::new(0);
+        let best = book.pick(&board, BookSelection::Best, &mut rng).unwrap();
+        assert_eq!(best.to_string(), "e2e4");
+
+        for _ in 0..20 {
+            let mv = book
+                .pick(&board, BookSelection::WeightedRandom, &mut rng)
+                .unwrap();
+            assert!(["e2e4", "d2d4"].contains(&mv.to_string().as_str()));
+        }
+
+        assert!(book
+            .pick(&board.do_move(best), BookSelection::Best, &mut rng)
+            .is_none());
+    }
+
+    #[test]
+    fn book_castling() {
+        let board = Board::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        let bytes = entry_bytes(board.polyglot_key(), Square::E1, Square::A1, 1);
+        let book = Book::from_bytes(&bytes);
+
+        let (mv, _) = book.moves(&board)[0];
+        assert!(matches!(mv.kind(), MoveKind::Castling));
+        assert_eq!(mv.to_string(), "e1c1");
+    }
+
+    #[test]
+    fn build_book() {
+        let pgn = r#"[WhiteElo "2500"]
+[BlackElo "2400"]
+
+1. e4 e5 2. Nf3 1-0
+
+[WhiteElo "2500"]
+[BlackElo "2400"]
+
+1. e4 c5 1/2-1/2
+
+[WhiteElo "2500"]
+[BlackElo "2400"]
+
+1. d4 d5 0-1
+
+[WhiteElo "1200"]
+[BlackElo "2400"]
+
+1. c4 e5 1-0
+"#;
+        let mut builder = BookBuilder::new();
+        builder.max_plies = 2;
+        builder.min_elo = 2000;
+
+        for game in crate::pgn::PgnReader::new(pgn.as_bytes()) {
+            builder.add_game(&game.unwrap()).unwrap();
+        }
+
+        let mut bytes = Vec::new();
+        builder.build().write(&mut bytes).unwrap();
+        let book = Book::from_bytes(&bytes);
+
+        // 1. e4 scored 2 + 1, 1. d4 lost and 1. c4 was filtered out by Elo
+        let board = Board::default();
+        let moves: Vec<(String, u16)> = book
+            .moves(&board)
+            .iter()
+            .map(|(mv, w)| (mv.to_string(), *w))
+            .collect();
+        assert_eq!(moves, [("e2e4".to_owned(), 3)]);
+
+        // 1... e5 lost, so only the draw with 1... c5 remains
+        let board = board.do_move(book.moves(&board)[0].0);
+        let moves = book.moves(&board);
+        assert_eq!(moves.len(), 1);
+        assert_eq!(moves[0].0.to_string(), "c7c5");
+
+        // 2. Nf3 is beyond the ply limit
+        let board = board.do_move(crate::uci::parse_move("e7e5", &board).unwrap());
+        assert!(book.moves(&board).is_empty());
+
+        builder.min_games = 2;
+        assert_eq!(builder.build().len(), 1);
+    }
+}
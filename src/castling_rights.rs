@@ -1,7 +1,7 @@
 use crate::color::Color;
 
 #[repr(u8)]
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum CastlingRights {
     Kingside(Color) = 1,
     Queenside(Color) = 2,
@@ -0,0 +1,402 @@
+use std::{
+    fmt,
+    io::{self, Read},
+    str::FromStr,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use crate::{
+    board::{Board, PositionError},
+    book::{Book, BookSelection},
+    color::Color,
+    eval::MATE,
+    game::{Game, Termination},
+    moves::Move,
+    pgn::GameResult,
+    rng::Rng,
+    search::{SearchOptions, SearchStats, Searcher},
+    tt::TranspositionTable,
+};
+
+#[derive(Debug)]
+pub struct ParseDataError {
+    msg: String,
+}
+
+impl ParseDataError {
+    pub fn new<S: AsRef<str>>(msg: S) -> Self {
+        Self {
+            msg: msg.as_ref().to_owned(),
+        }
+    }
+}
+
+impl fmt::Display for ParseDataError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Error parsing training data: {}", self.msg)
+    }
+}
+
+/// A searched position labelled with the result of the game it was played in.
+#[derive(Clone)]
+pub struct DataPoint {
+    pub board: Board,
+    /// Search score in centipawns from white's point of view
+    pub score: i16,
+    pub result: GameResult,
+}
+
+impl DataPoint {
+    /// Size of a packed data point in bytes.
+    pub const SIZE: usize = Board::PACKED_SIZE + 4;
+
+    /// Version of the binary format, stored in the last byte of each data point. The
+    /// unversioned 32-byte records written before can't be read.
+    pub const VERSION: u8 = 1;
+
+    /// Packs the data point as the packed position followed by the score, little endian,
+    /// the result and the format version. Fails for boards that can't be packed.
+    pub fn to_bytes(&self) -> Result<[u8; Self::SIZE], PositionError> {
+        let mut bytes = [0; Self::SIZE];
+        let n = Board::PACKED_SIZE;
+
+        bytes[..n].copy_from_slice(&self.board.to_packed()?);
+        bytes[n..n + 2].copy_from_slice(&self.score.to_le_bytes());
+        bytes[n + 2] = match self.result {
+            GameResult::BlackWins => 0,
+            GameResult::Draw => 1,
+            GameResult::WhiteWins => 2,
+        };
+        bytes[n + 3] = Self::VERSION;
+
+        Ok(bytes)
+    }
+
+    pub fn from_bytes(bytes: &[u8; Self::SIZE]) -> Result<Self, ParseDataError> {
+        let n = Board::PACKED_SIZE;
+        if bytes[n + 3] != Self::VERSION {
+            return Err(ParseDataError::new(format!(
+                "Unsupported format version {}",
+                bytes[n + 3]
+            )));
+        }
+        let board = Board::from_packed(bytes[..n].try_into().unwrap())
+            .map_err(|e| ParseDataError::new(e.to_string()))?;
+
+        let result = match bytes[n + 2] {
+            0 => GameResult::BlackWins,
+            1 => GameResult::Draw,
+            2 => GameResult::WhiteWins,
+            _ => return Err(ParseDataError::new("Invalid result")),
+        };
+
+        Ok(Self {
+            board,
+            score: i16::from_le_bytes([bytes[n], bytes[n + 1]]),
+            result,
+        })
+    }
+}
+
+/// Text format, one position per line: `<fen> | <score> | <result>`, where the result is
+/// 1.0, 0.5 or 0.0 from white's point of view.
+impl fmt::Display for DataPoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let result = match self.result {
+            GameResult::WhiteWins => "1.0",
+            GameResult::Draw => "0.5",
+            GameResult::BlackWins => "0.0",
+        };
+        write!(f, "{} | {} | {}", self.board.fen(), self.score, result)
+    }
+}
+
+impl FromStr for DataPoint {
+    type Err = ParseDataError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<&str> = s.split('|').map(|f| f.trim()).collect();
+        let [fen, score, result] = fields[..] else {
+            return Err(ParseDataError::new(format!("Expected 3 fields in '{}'", s)));
+        };
+
+        Ok(Self {
+            board: Board::from_fen(fen).map_err(|e| ParseDataError::new(e.to_string()))?,
+            score: score
+                .parse()
+                .map_err(|_| ParseDataError::new(format!("Invalid score '{}'", score)))?,
+            result: match result {
+                "1.0" | "1" => GameResult::WhiteWins,
+                "0.5" => GameResult::Draw,
+                "0.0" | "0" => GameResult::BlackWins,
+                _ => return Err(ParseDataError::new(format!("Invalid result '{}'", result))),
+            },
+        })
+    }
+}
+
+/// Reads packed data points one by one.
+pub struct DataReader<R: Read> {
+    reader: R,
+}
+
+impl<R: Read> DataReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self { reader }
+    }
+}
+
+impl<R: Read> Iterator for DataReader<R> {
+    type Item = io::Result<DataPoint>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut bytes = [0; DataPoint::SIZE];
+
+        // A clean end of file ends the iteration, a partial record is an error
+        match self.reader.read(&mut bytes[..1]) {
+            Ok(0) => return None,
+            Ok(_) => (),
+            Err(e) => return Some(Err(e)),
+        }
+        if let Err(e) = self.reader.read_exact(&mut bytes[1..]) {
+            return Some(Err(e));
+        }
+
+        Some(
+            DataPoint::from_bytes(&bytes)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string())),
+        )
+    }
+}
+
+/// Settings for self-play games.
+#[derive(Debug, Clone)]
+pub struct DatagenOptions {
+    /// Nodes searched per move
+    pub nodes: u64,
+    /// Random moves played after the book, so that games differ
+    pub random_plies: usize,
+    /// Games longer than this are drawn
+    pub max_plies: usize,
+    /// Games are won once a side has a score beyond this
+    pub win_score: i32,
+}
+
+impl Default for DatagenOptions {
+    fn default() -> Self {
+        Self {
+            nodes: 5000,
+            random_plies: 8,
+            max_plies: 400,
+            win_score: 2500,
+        }
+    }
+}
+
+/// Plays the opening from `book` and then random moves. Returns `None` if the game ended
+/// during the opening.
+fn play_opening(options: &DatagenOptions, book: Option<&Book>, rng: &mut Rng) -> Option<Game> {
+    let mut game = Game::new(Board::default());
+
+    // Leave the book before it ends the game, e.g. by going round a cycle of book moves
+    while let Some(mv) = book.and_then(|b| b.pick(game.board(), BookSelection::WeightedRandom, rng))
+    {
+        let mut next = game.clone();
+        next.push(mv, None);
+        if next.outcome().is_some() {
+            break;
+        }
+        game = next;
+    }
+
+    for _ in 0..options.random_plies {
+        let moves = game.board().legal_moves();
+        if moves.is_empty() {
+            return None;
+        }
+        game.push(moves[rng.below(moves.len() as u64) as usize], None);
+    }
+
+    match game.outcome() {
+        Some(_) => None,
+        None => Some(game),
+    }
+}
+
+/// Plays a self-play game with fixed-node searches and returns its quiet positions, i.e.
+/// positions where the side to move is not in check and the best move is not a capture.
+pub fn play_game(
+    options: &DatagenOptions,
+    tt: &TranspositionTable,
+    book: Option<&Book>,
+    rng: &mut Rng,
+) -> Vec<DataPoint> {
+    let mut game = loop {
+        if let Some(game) = play_opening(options, book, rng) {
+            break game;
+        }
+    };
+
+    // The node limit only applies once the first iteration has found a move
+    let searched = AtomicBool::new(false);
+    let should_stop =
+        |stats: &SearchStats| searched.load(Ordering::Relaxed) && stats.nodes >= options.nodes;
+    let search_options = SearchOptions::default();
+    let mut points = Vec::new();
+
+    tt.clear();
+    while game.outcome().is_none() {
+        tt.new_search();
+        searched.store(false, Ordering::Relaxed);
+        let result = Searcher::new(tt, &should_stop).search(game.board(), &search_options, |_| {
+            searched.store(true, Ordering::Relaxed)
+        });
+        let line = &result.lines[0];
+
+        let board = game.board().clone();
+        let mv: Move = line.pv[0];
+        let score = match board.color_to_move() {
+            Color::White => line.score,
+            Color::Black => -line.score,
+        };
+
+        let is_mate = line.score.abs() >= MATE - 1000;
+        if !board.is_in_check() && !mv.kind().is_capture() && !is_mate {
+            points.push((board, score));
+        }
+
+        game.push(mv, Some(score));
+
+        if game.outcome().is_none() {
+            if score.abs() >= options.win_score {
+                let winner = if score > 0 {
+                    Color::White
+                } else {
+                    Color::Black
+                };
+                game.finish(GameResult::win_for(winner), Termination::Adjudication);
+            } else if game.moves().len() >= options.max_plies {
+                game.finish(GameResult::Draw, Termination::Adjudication);
+            }
+        }
+    }
+
+    let (result, _) = game.outcome().expect("game is over");
+
+    points
+        .into_iter()
+        .map(|(board, score)| DataPoint {
+            board,
+            score: score.clamp(i16::MIN as i32, i16::MAX as i32) as i16,
+            result,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        book::{encode_move, BookEntry},
+        uci::parse_move,
+    };
+
+    #[test]
+    fn packed_round_trip() {
+        let fens = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w Kq - 12 40",
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+            "8/8/8/8/3Pp3/8/8/k6K b - d3 0 70",
+        ];
+
+        for (i, fen) in fens.iter().enumerate() {
+            let point = DataPoint {
+                board: Board::from_fen(fen).unwrap(),
+                score: -250 + i as i16 * 100,
+                result: GameResult::Draw,
+            };
+
+            let decoded = DataPoint::from_bytes(&point.to_bytes().unwrap()).unwrap();
+            assert_eq!(decoded.to_string(), point.to_string());
+
+            let parsed: DataPoint = point.to_string().parse().unwrap();
+            assert_eq!(parsed.to_bytes().unwrap(), point.to_bytes().unwrap());
+        }
+
+        let point = DataPoint {
+            board: Board::default(),
+            score: 0,
+            result: GameResult::Draw,
+        };
+        let mut bytes = point.to_bytes().unwrap();
+        bytes[Board::PACKED_SIZE + 2] = 3;
+        assert!(DataPoint::from_bytes(&bytes).is_err());
+        bytes = point.to_bytes().unwrap();
+        bytes[DataPoint::SIZE - 1] = 0;
+        assert!(DataPoint::from_bytes(&bytes).is_err());
+
+        let crowded: DataPoint = "rnbqkbnr/pppppppp/8/8/8/7N/PPPPPPPP/RNBQKBNR w - - 0 1 | 0 | 0.5"
+            .parse()
+            .unwrap();
+        assert!(crowded.to_bytes().is_err());
+        assert!("8/8/8/8/8/8/8/8 w - - 0 1 | 10"
+            .parse::<DataPoint>()
+            .is_err());
+    }
+
+    #[test]
+    fn self_play() {
+        let options = DatagenOptions {
+            nodes: 1000,
+            max_plies: 40,
+            ..Default::default()
+        };
+        let tt = TranspositionTable::new(1);
+        let points = play_game(&options, &tt, None, &mut Rng::new(7));
+
+        assert!(!points.is_empty());
+        assert!(points.iter().all(|p| !p.board.is_in_check()));
+        assert!(points.iter().all(|p| p.result == points[0].result));
+
+        let bytes: Vec<u8> = points.iter().flat_map(|p| p.to_bytes().unwrap()).collect();
+        let read: Vec<DataPoint> = DataReader::new(&bytes[..])
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(read.len(), points.len());
+        assert_eq!(read[0].to_string(), points[0].to_string());
+
+        // A truncated record is an error
+        assert!(DataReader::new(&bytes[..DataPoint::SIZE + 5])
+            .nth(1)
+            .unwrap()
+            .is_err());
+    }
+
+    #[test]
+    fn book_with_cycle() {
+        let mut board = Board::default();
+        let mut entries = Vec::new();
+        for mv in ["g1f3", "g8f6", "f3g1", "f6g8"] {
+            let mv = parse_move(mv, &board).unwrap();
+            entries.push(BookEntry {
+                key: board.polyglot_key(),
+                mv: encode_move(mv),
+                weight: 1,
+                learn: 0,
+            });
+            board = board.do_move(mv);
+        }
+        let book = Book::from_entries(entries);
+
+        let options = DatagenOptions {
+            random_plies: 0,
+            ..Default::default()
+        };
+        let game = play_opening(&options, Some(&book), &mut Rng::new(1)).unwrap();
+
+        // The eighth move would repeat the start position a third time
+        assert_eq!(game.moves().len(), 7);
+        assert!(game.outcome().is_none());
+    }
+}
@@ -1,6 +1,17 @@
-use std::fmt;
-
-use crate::{bitboard::Bitboard, board::Board, moves::generate_moves, square::Square};
+use std::{
+    fmt, ops,
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+    thread,
+};
+
+use crate::{
+    bitboard::Bitboard,
+    board::Board,
+    color::Color,
+    moves::{generate_moves, generate_pseudo_legal_moves, Move, MoveKind},
+    piece::PieceKind,
+    square::Square,
+};
 
 /// Counts the number of possible leaf nodes in a game tree of `depth`.
 pub fn perft(board: &Board, depth: usize) -> u64 {
@@ -24,6 +35,21 @@ pub fn perft(board: &Board, depth: usize) -> u64 {
     count
 }
 
+/// Returns `perft_stats()` of the subtree of every current move.
+pub fn perft_stats_divide(board: &Board, depth: usize) -> Vec<(Move, PerftStats)> {
+    generate_moves(board)
+        .into_iter()
+        .map(|mv| {
+            let next = board.do_move(mv);
+            let stats = match depth {
+                0 | 1 => move_stats(mv, &next),
+                _ => perft_stats(&next, depth - 1),
+            };
+            (mv, stats)
+        })
+        .collect()
+}
+
 /// Similar to `perft()`, but also shows the node counts for all current moves.
 pub fn perft_divide(board: &Board, depth: usize) -> u64 {
     if depth == 0 {
@@ -41,57 +67,464 @@ pub fn perft_divide(board: &Board, depth: usize) -> u64 {
     total_nodes
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// Node counts of subtrees, shared by perft threads. Like the transposition table, entries
+/// are stored lock-free as the key xored with the data.
+pub struct PerftTable {
+    entries: Vec<[AtomicU64; 2]>,
+}
 
-    use std::{io::BufRead, time::Instant};
+impl PerftTable {
+    const ENTRY_SIZE: usize = 16;
 
-    #[test]
-    fn perft_test() {
-        let f = std::fs::File::open("perftsuite.txt").unwrap();
+    /// Largest table size in megabytes, the same as the `Hash` option allows.
+    pub const MAX_MB: usize = 65536;
 
-        let reader = std::io::BufReader::new(f);
+    /// Creates a table of `mb` megabytes, at most `MAX_MB`.
+    pub fn new(mb: usize) -> Self {
+        let len = (mb.min(Self::MAX_MB) * 1024 * 1024 / Self::ENTRY_SIZE).max(1);
 
-        let t = Instant::now();
-        let mut total_nodes = 0;
+        Self {
+            entries: (0..len)
+                .map(|_| [AtomicU64::new(0), AtomicU64::new(0)])
+                .collect(),
+        }
+    }
 
-        for line in reader.lines() {
-            let line = line.unwrap();
+    fn index(&self, key: u64) -> usize {
+        ((key as u128 * self.entries.len() as u128) >> 64) as usize
+    }
 
-            let mut tokens = line.split(',');
-            let fen = tokens.next().unwrap();
-            let mut board = Board::from_fen(fen).unwrap();
+    // Layout: depth 8 bits, node count 56 bits
+    fn probe(&self, key: u64, depth: usize) -> Option<u64> {
+        let [stored_key, data] = &self.entries[self.index(key)];
+        let data = data.load(Ordering::Relaxed);
 
-            print!("FEN: {: <80}|", fen);
+        if stored_key.load(Ordering::Relaxed) ^ data != key || data & 255 != depth as u64 {
+            return None;
+        }
 
-            for (depth, token) in tokens.enumerate() {
-                let actual_nodes = token.trim().parse::<u64>().unwrap();
-                let nodes = perft(&mut board, depth + 1);
-                total_nodes += nodes;
+        Some(data >> 8)
+    }
 
-                assert_eq!(nodes, actual_nodes, "FEN: {}, depth={}", fen, depth + 1);
-                print!(" {}", nodes);
-            }
-            println!("");
+    fn store(&self, key: u64, depth: usize, nodes: u64) {
+        let [stored_key, data] = &self.entries[self.index(key)];
+        let bits = nodes << 8 | depth as u64;
+
+        stored_key.store(key ^ bits, Ordering::Relaxed);
+        data.store(bits, Ordering::Relaxed);
+    }
+}
+
+/// Same as `perft()`, but looks up the node counts of transposed subtrees in `table`.
+/// # Example
+/// ```
+/// # use chess::{board::Board, debug::{perft_hashed, PerftTable}};
+/// let table = PerftTable::new(1);
+/// assert_eq!(perft_hashed(&Board::default(), 4, &table), 197281);
+/// ```
+pub fn perft_hashed(board: &Board, depth: usize, table: &PerftTable) -> u64 {
+    if depth <= 1 {
+        return perft(board, depth);
+    }
+
+    let key = board.polyglot_key();
+    if let Some(nodes) = table.probe(key, depth) {
+        return nodes;
+    }
+
+    let mut count = 0;
+    for mv in &generate_moves(board) {
+        count += perft_hashed(&board.do_move(mv), depth - 1, table);
+    }
+
+    table.store(key, depth, count);
+    count
+}
+
+/// Counts the leaf nodes below every current move, splitting the moves across `threads`
+/// threads. The subtrees are counted with `perft_hashed()` if a table is given.
+pub fn perft_parallel_divide(
+    board: &Board,
+    depth: usize,
+    threads: usize,
+    table: Option<&PerftTable>,
+) -> Vec<(Move, u64)> {
+    let moves = generate_moves(board);
+    let counts: Vec<AtomicU64> = (0..moves.count()).map(|_| AtomicU64::new(0)).collect();
+    let next = AtomicUsize::new(0);
+
+    thread::scope(|s| {
+        for _ in 0..threads.clamp(1, moves.count().max(1)) {
+            s.spawn(|| loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                if i >= moves.count() {
+                    break;
+                }
+
+                let child = board.do_move(moves[i]);
+                let depth = depth.saturating_sub(1);
+                let nodes = match table {
+                    Some(table) => perft_hashed(&child, depth, table),
+                    None => perft(&child, depth),
+                };
+                counts[i].store(nodes, Ordering::Relaxed);
+            });
         }
+    });
 
-        let elapsed = (Instant::now() - t).as_secs_f64();
-        let nps = total_nodes as f64 / elapsed;
+    moves
+        .into_iter()
+        .zip(counts)
+        .map(|(mv, nodes)| (mv, nodes.into_inner()))
+        .collect()
+}
 
-        let (nps, prefix) = if nps > 1e6 {
-            (nps / 1e6, "Mnps")
-        } else if nps > 1e3 {
-            (nps / 1e3, "knps")
+/// Same as `perft()`, but counts the subtrees of the current moves in parallel.
+/// # Example
+/// ```
+/// # use chess::{board::Board, debug::perft_parallel};
+/// assert_eq!(perft_parallel(&Board::default(), 4, 2, None), 197281);
+/// ```
+pub fn perft_parallel(
+    board: &Board,
+    depth: usize,
+    threads: usize,
+    table: Option<&PerftTable>,
+) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+
+    perft_parallel_divide(board, depth, threads, table)
+        .iter()
+        .map(|(_, nodes)| nodes)
+        .sum()
+}
+
+/// Leaf node counts by move type, as in the detailed perft tables of the Chessprogramming
+/// wiki.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PerftStats {
+    pub nodes: u64,
+    pub captures: u64,
+    pub en_passants: u64,
+    pub castles: u64,
+    pub promotions: u64,
+    pub checks: u64,
+    /// Checks not given by the moved piece
+    pub discovered_checks: u64,
+    pub double_checks: u64,
+    pub checkmates: u64,
+}
+
+impl ops::AddAssign for PerftStats {
+    fn add_assign(&mut self, rhs: Self) {
+        self.nodes += rhs.nodes;
+        self.captures += rhs.captures;
+        self.en_passants += rhs.en_passants;
+        self.castles += rhs.castles;
+        self.promotions += rhs.promotions;
+        self.checks += rhs.checks;
+        self.discovered_checks += rhs.discovered_checks;
+        self.double_checks += rhs.double_checks;
+        self.checkmates += rhs.checkmates;
+    }
+}
+
+impl fmt::Display for PerftStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Nodes: {}, captures: {}, e.p.: {}, castles: {}, promotions: {}, checks: {}, \
+             discovery checks: {}, double checks: {}, checkmates: {}",
+            self.nodes,
+            self.captures,
+            self.en_passants,
+            self.castles,
+            self.promotions,
+            self.checks,
+            self.discovered_checks,
+            self.double_checks,
+            self.checkmates
+        )
+    }
+}
+
+/// Classifies the move leading to the leaf node `next`.
+fn move_stats(mv: Move, next: &Board) -> PerftStats {
+    let mut stats = PerftStats {
+        nodes: 1,
+        ..Default::default()
+    };
+
+    if mv.kind().is_capture() {
+        stats.captures += 1;
+    }
+    match mv.kind() {
+        MoveKind::EnPassant => stats.en_passants += 1,
+        MoveKind::Castling => stats.castles += 1,
+        _ => (),
+    }
+    if mv.promotion_kind().is_some() {
+        stats.promotions += 1;
+    }
+
+    let checkers = next.checkers();
+    if checkers.is_non_empty() {
+        stats.checks += 1;
+        if (checkers & mv.to()).is_empty() {
+            stats.discovered_checks += 1;
+        }
+        if checkers.popcount() > 1 {
+            stats.double_checks += 1;
+        }
+        if !next.has_legal_moves() {
+            stats.checkmates += 1;
+        }
+    }
+
+    stats
+}
+
+/// Like `perft()`, but also classifies the moves leading to the leaf nodes.
+/// # Example
+/// ```
+/// # use chess::{board::Board, debug::perft_stats};
+/// let stats = perft_stats(&Board::default(), 3);
+/// assert_eq!((stats.nodes, stats.captures, stats.checks), (8902, 34, 12));
+/// ```
+pub fn perft_stats(board: &Board, depth: usize) -> PerftStats {
+    let mut stats = PerftStats::default();
+
+    if depth == 0 {
+        stats.nodes = 1;
+        return stats;
+    }
+
+    for mv in &generate_moves(board) {
+        let next = board.do_move(mv);
+
+        if depth > 1 {
+            stats += perft_stats(&next, depth - 1);
         } else {
-            (nps, "nps")
+            stats += move_stats(mv, &next);
+        }
+    }
+
+    stats
+}
+
+/// A broken board invariant, found by `check_position()` or `random_walk()`.
+#[derive(Debug)]
+pub struct InvariantError {
+    fen: String,
+    msg: String,
+}
+
+impl InvariantError {
+    pub fn new<S: AsRef<str>>(board: &Board, msg: S) -> Self {
+        Self {
+            fen: board.fen(),
+            msg: msg.as_ref().to_owned(),
+        }
+    }
+
+    /// The first position where the invariant does not hold.
+    pub fn fen(&self) -> &str {
+        &self.fen
+    }
+}
+
+impl fmt::Display for InvariantError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invariant broken in {}: {}", self.fen, self.msg)
+    }
+}
+
+/// Squares attacked by the piece on `square`, looked up piece by piece instead of with
+/// the reverse lookups of `Board::attackers_to()`.
+fn piece_attacks(board: &Board, square: Square) -> Bitboard {
+    let Some(piece) = board.at(square) else {
+        return Bitboard::EMPTY;
+    };
+
+    match piece.kind() {
+        PieceKind::Pawn => Bitboard::pawn_attacks(Bitboard::new(square), piece.color()),
+        PieceKind::Knight => Bitboard::knight_attacks(square),
+        PieceKind::Bishop => Bitboard::bishop_attacks(square, board.pieces()),
+        PieceKind::Rook => Bitboard::rook_attacks(square, board.pieces()),
+        PieceKind::Queen => {
+            Bitboard::bishop_attacks(square, board.pieces())
+                | Bitboard::rook_attacks(square, board.pieces())
+        }
+        PieceKind::King => Bitboard::king_attacks(square),
+    }
+}
+
+fn is_king_attacked(board: &Board, color: Color) -> bool {
+    let king = board.pieces_by_kind(PieceKind::King) & board.pieces_by_color(color);
+
+    board
+        .pieces_by_color(!color)
+        .into_iter()
+        .any(|square| (piece_attacks(board, square) & king).is_non_empty())
+}
+
+/// Builds the position again from scratch, square by square.
+fn rebuild(board: &Board) -> Board {
+    let mut rebuilt = Board::new();
+
+    for i in 0..64 {
+        let square = Square::from_index(i);
+        if let Some(piece) = board.at(square) {
+            rebuilt.put(piece, square);
+        }
+    }
+    for color in [Color::White, Color::Black] {
+        if board.can_castle_kingside(color) {
+            rebuilt.add_castling_kingside(color);
+        }
+        if board.can_castle_queenside(color) {
+            rebuilt.add_castling_queenside(color);
+        }
+    }
+    rebuilt.set_color_to_move(board.color_to_move());
+    rebuilt.set_en_passant_square(board.en_passant_square());
+    rebuilt.set_halfmove_clock(board.halfmove_clock());
+    rebuilt.set_fullmove_number(board.fullmove_number());
+
+    rebuilt
+}
+
+/// Checks that a legal move keeps the number of pieces consistent with what it captures.
+fn check_piece_counts(board: &Board, mv: Move, next: &Board) -> Result<(), InvariantError> {
+    let us = board.color_to_move();
+    let count = |b: &Board, color, kind| {
+        (b.pieces_by_color(color) & b.pieces_by_kind(kind)).popcount() as isize
+    };
+
+    for kind in [
+        PieceKind::Pawn,
+        PieceKind::Knight,
+        PieceKind::Bishop,
+        PieceKind::Rook,
+        PieceKind::Queen,
+        PieceKind::King,
+    ] {
+        let mut ours = count(board, us, kind);
+        let mut theirs = count(board, !us, kind);
+
+        if mv.promotion_kind() == Some(kind) {
+            ours += 1;
+        }
+        if kind == PieceKind::Pawn && mv.promotion_kind().is_some() {
+            ours -= 1;
+        }
+        let captured = match mv.kind() {
+            MoveKind::EnPassant => Some(PieceKind::Pawn),
+            _ => board.at(mv.to()).map(|piece| piece.kind()),
         };
+        if mv.kind().is_capture() && captured == Some(kind) {
+            theirs -= 1;
+        }
 
-        println!(
-            "Leaf nodes searched: {}, elapsed time: {:.2} s ({:.2} {})",
-            total_nodes, elapsed, nps, prefix
-        );
+        if (ours, theirs) != (count(next, us, kind), count(next, !us, kind)) {
+            return Err(InvariantError::new(
+                board,
+                format!("{} changes the number of {:?} pieces", mv, kind),
+            ));
+        }
     }
+
+    Ok(())
+}
+
+/// Cross-checks the board representation, check detection and move generation of a valid
+/// position and of every legal move in it. The representation must be canonical, i.e. equal
+/// to the same position built from scratch, and the incremental Polyglot key after every
+/// legal move must match the key computed from scratch.
+pub fn check_position(board: &Board) -> Result<(), InvariantError> {
+    let fail = |msg: &str| Err(InvariantError::new(board, msg));
+
+    if let Err(e) = board.validate() {
+        return fail(&format!("invalid position: {}", e));
+    }
+
+    let fen = board.fen();
+    match Board::from_fen(&fen) {
+        Ok(parsed) if parsed == *board => (),
+        _ => return fail("FEN does not round-trip"),
+    }
+
+    if rebuild(board) != *board {
+        return fail("board differs from the same position built from scratch");
+    }
+
+    let in_check = is_king_attacked(board, board.color_to_move());
+    if board.is_in_check() != in_check || board.checkers().is_non_empty() != in_check {
+        return fail("check detection disagrees with attackers");
+    }
+
+    let moves = generate_moves(board);
+    if board.has_legal_moves() == moves.is_empty() {
+        return fail("has_legal_moves() disagrees with the generated moves");
+    }
+    let pseudo_legal = generate_pseudo_legal_moves(board);
+    let legal = pseudo_legal.into_iter().filter(|&mv| board.is_legal(mv));
+    if legal.count() != moves.count() {
+        return fail("legal pseudo-legal moves differ from the generated moves");
+    }
+
+    let key = board.polyglot_key();
+    for mv in &moves {
+        if !board.is_legal(mv) {
+            return fail(&format!("generated move {} is not legal", mv));
+        }
+
+        let next = board.do_move(mv);
+        if board.polyglot_key_after(key, &next) != next.polyglot_key() {
+            return fail(&format!(
+                "incremental hash after {} differs from the full hash",
+                mv
+            ));
+        }
+        if is_king_attacked(&next, board.color_to_move()) {
+            return fail(&format!("{} leaves the king attacked", mv));
+        }
+        check_piece_counts(board, mv, &next)?;
+    }
+
+    Ok(())
+}
+
+/// Plays a game from `board` and runs `check_position()` at every ply. Each choice picks one
+/// of the legal moves, and the game ends when the choices or the moves run out. Returns the
+/// final position, or the first position that breaks an invariant. That position is simply
+/// where the walk stopped; nothing shrinks the choices to a shorter failing walk.
+/// # Example
+/// ```
+/// # use chess::{board::Board, debug::random_walk};
+/// let board = random_walk(&Board::default(), [12, 0, 5]).unwrap();
+/// assert_eq!(board.fullmove_number(), 2);
+/// ```
+pub fn random_walk(
+    board: &Board,
+    choices: impl IntoIterator<Item = u64>,
+) -> Result<Board, InvariantError> {
+    let mut board = board.clone();
+
+    for choice in choices {
+        check_position(&board)?;
+
+        let moves = generate_moves(&board);
+        if moves.is_empty() {
+            return Ok(board);
+        }
+        board = board.do_move(moves[choice as usize % moves.count()]);
+    }
+
+    check_position(&board)?;
+    Ok(board)
 }
 
 impl fmt::Display for Board {
@@ -145,3 +578,189 @@ impl fmt::Display for Bitboard {
         writeln!(f, "   | a | b | c | d | e | f | g | h |")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::Rng;
+
+    use std::{io::BufRead, time::Instant};
+
+    #[test]
+    fn perft_test() {
+        let f = std::fs::File::open("perftsuite.txt").unwrap();
+
+        let reader = std::io::BufReader::new(f);
+
+        let t = Instant::now();
+        let mut total_nodes = 0;
+
+        for line in reader.lines() {
+            let line = line.unwrap();
+
+            let mut tokens = line.split(',');
+            let fen = tokens.next().unwrap();
+            let board = Board::from_fen(fen).unwrap();
+
+            print!("FEN: {: <80}|", fen);
+
+            for (depth, token) in tokens.enumerate() {
+                let actual_nodes = token.trim().parse::<u64>().unwrap();
+                let nodes = perft(&board, depth + 1);
+                total_nodes += nodes;
+
+                assert_eq!(nodes, actual_nodes, "FEN: {}, depth={}", fen, depth + 1);
+                print!(" {}", nodes);
+            }
+            println!();
+        }
+
+        let elapsed = (Instant::now() - t).as_secs_f64();
+        let nps = total_nodes as f64 / elapsed;
+
+        let (nps, prefix) = if nps > 1e6 {
+            (nps / 1e6, "Mnps")
+        } else if nps > 1e3 {
+            (nps / 1e3, "knps")
+        } else {
+            (nps, "nps")
+        };
+
+        println!(
+            "Leaf nodes searched: {}, elapsed time: {:.2} s ({:.2} {})",
+            total_nodes, elapsed, nps, prefix
+        );
+    }
+
+    #[test]
+    fn perft_color_flip() {
+        let f = std::fs::File::open("perftsuite.txt").unwrap();
+
+        for line in std::io::BufReader::new(f).lines() {
+            let line = line.unwrap();
+
+            let mut tokens = line.split(',');
+            let board = Board::from_fen(tokens.next().unwrap()).unwrap();
+            let flipped = board.flip_vertical();
+            assert_eq!(flipped.flip_vertical(), board);
+
+            for (depth, token) in tokens.enumerate() {
+                let expected = token.trim().parse::<u64>().unwrap();
+                // Keep the test fast, the full suite is covered by perft_test
+                if expected > 10_000_000 {
+                    break;
+                }
+
+                assert_eq!(perft(&flipped, depth + 1), expected, "{}", flipped.fen());
+                if let Some(mirrored) = board.mirror_horizontal() {
+                    assert_eq!(perft(&mirrored, depth + 1), expected, "{}", mirrored.fen());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn perft_stats_reference() {
+        // Nodes, captures, e.p., castles, promotions, checks, discovery checks, double checks
+        // and checkmates from the Chessprogramming wiki
+        let positions = [
+            (
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+                5,
+                [4865609, 82719, 258, 0, 0, 27351, 6, 0, 347],
+            ),
+            (
+                "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+                4,
+                [4085603, 757163, 1929, 128013, 15172, 25523, 42, 6, 43],
+            ),
+            (
+                "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+                5,
+                [674624, 52051, 1165, 0, 0, 52950, 1292, 3, 0],
+            ),
+            (
+                "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+                4,
+                // The wiki does not list discovery and double checks here, these are our own
+                [422333, 131393, 0, 7795, 60032, 15492, 19, 0, 5],
+            ),
+        ];
+
+        for (fen, depth, expected) in positions {
+            let s = perft_stats(&Board::from_fen(fen).unwrap(), depth);
+            let actual = [
+                s.nodes,
+                s.captures,
+                s.en_passants,
+                s.castles,
+                s.promotions,
+                s.checks,
+                s.discovered_checks,
+                s.double_checks,
+                s.checkmates,
+            ];
+            assert_eq!(actual, expected, "{}", fen);
+            assert_eq!(s.nodes, perft(&Board::from_fen(fen).unwrap(), depth));
+        }
+    }
+
+    #[test]
+    fn parallel_and_hashed_perft() {
+        let fens = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "8/8/8/2k5/3Pp3/8/8/4K2R b K d3 0 1",
+        ];
+
+        // A tiny table makes entries get replaced all the time
+        let table = PerftTable::new(0);
+
+        for fen in fens {
+            let board = Board::from_fen(fen).unwrap();
+
+            for depth in 0..=4 {
+                let expected = perft(&board, depth);
+                assert_eq!(perft_hashed(&board, depth, &table), expected);
+                assert_eq!(perft_parallel(&board, depth, 3, None), expected);
+                assert_eq!(perft_parallel(&board, depth, 3, Some(&table)), expected);
+            }
+        }
+
+        let table = PerftTable::new(16);
+        let board = Board::default();
+        assert_eq!(perft_parallel(&board, 5, 4, Some(&table)), 4865609);
+        assert_eq!(perft_hashed(&board, 5, &table), 4865609);
+    }
+
+    #[test]
+    fn random_walk_invariants() {
+        let fens = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1",
+        ];
+
+        for fen in fens {
+            let board = Board::from_fen(fen).unwrap();
+
+            for seed in 0..20 {
+                let mut rng = Rng::new(seed);
+                let choices = std::iter::repeat_with(|| rng.next_u64()).take(300);
+
+                if let Err(e) = random_walk(&board, choices) {
+                    panic!("{} (seed {})", e, seed);
+                }
+            }
+        }
+
+        // Positions that are not valid are reported as such
+        let board = Board::from_fen("4k3/8/8/8/8/8/8/4K2R w KQ - 0 1").unwrap();
+        let e = check_position(&board).unwrap_err();
+        assert_eq!(e.fen(), board.fen());
+    }
+}
@@ -0,0 +1,84 @@
+pub mod test_engine;
+pub mod uci;
+pub mod xboard;
+
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use crate::{
+    board::Board,
+    moves::Move,
+    uci::{
+        options::{EngineOption, OptionValue},
+        Info, SearchParams,
+    },
+};
+
+/// Shared flags through which the driver controls a running search.
+#[derive(Debug, Clone, Default)]
+pub struct StopToken {
+    flag: Arc<AtomicBool>,
+    pondering: Arc<AtomicBool>,
+}
+
+impl StopToken {
+    pub fn new() -> Self {
+        Self {
+            flag: Arc::new(AtomicBool::new(false)),
+            pondering: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Token for a `go ponder` search, which ignores the clock until `ponderhit()`.
+    pub fn pondering() -> Self {
+        let token = Self::new();
+        token.pondering.store(true, Ordering::SeqCst);
+        token
+    }
+
+    pub fn stop(&self) {
+        self.flag.store(true, Ordering::SeqCst);
+    }
+
+    pub fn should_stop(&self) -> bool {
+        self.flag.load(Ordering::SeqCst)
+    }
+
+    /// The opponent played the expected move, so the search continues as a timed one.
+    pub fn ponderhit(&self) {
+        self.pondering.store(false, Ordering::SeqCst);
+    }
+
+    pub fn is_pondering(&self) -> bool {
+        self.pondering.load(Ordering::SeqCst)
+    }
+}
+
+pub trait Engine: Clone + Send + Sync {
+    fn name(&self) -> String;
+
+    fn author(&self) -> String;
+
+    /// Options the engine supports. These are announced to the GUI.
+    fn options(&self) -> Vec<EngineOption> {
+        Vec::new()
+    }
+
+    /// Called with an already validated value whenever the GUI changes one of `options()`.
+    fn set_option(&mut self, _name: &str, _value: &OptionValue) {}
+
+    /// Called when a new game starts.
+    fn new_game(&mut self) {}
+
+    /// Searches `board` within the limits of `params` or until `stop`. Progress is reported
+    /// through `info`. Returns the best move and the expected reply.
+    fn search(
+        &self,
+        board: &Board,
+        params: SearchParams,
+        stop: StopToken,
+        info: &mut dyn FnMut(Info),
+    ) -> (Move, Option<Move>);
+}
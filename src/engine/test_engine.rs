@@ -0,0 +1,242 @@
+use std::sync::Arc;
+
+use crate::{
+    board::Board,
+    moves::Move,
+    rng::Rng,
+    search::{search_parallel, SearchOptions, SearchStats, MAX_DEPTH},
+    skill::Skill,
+    timeman::TimeManager,
+    tt::TranspositionTable,
+    uci::{
+        self,
+        options::{EngineOption, OptionValue},
+        Info, SearchParams, UciScore,
+    },
+};
+
+use super::{Engine, StopToken};
+
+/// Engine built on the library's own search.
+#[derive(Clone)]
+pub struct TestEngine {
+    hash_mb: usize,
+    threads: usize,
+    multipv: usize,
+    move_overhead: u32,
+    /// Only advertises `go ponder` support, as the UCI protocol expects
+    ponder: bool,
+    skill_level: u8,
+    limit_strength: bool,
+    elo: i64,
+    /// Seed for picking weaker moves. Zero seeds from the clock.
+    seed: u64,
+    tt: Arc<TranspositionTable>,
+}
+
+impl TestEngine {
+    pub fn new() -> Self {
+        Self {
+            hash_mb: 16,
+            threads: 1,
+            multipv: 1,
+            move_overhead: 10,
+            ponder: false,
+            skill_level: Skill::MAX_LEVEL,
+            limit_strength: false,
+            elo: 1500,
+            seed: 0,
+            tt: Arc::new(TranspositionTable::new(16)),
+        }
+    }
+}
+
+impl Default for TestEngine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Engine for TestEngine {
+    fn name(&self) -> String {
+        String::from("Test engine")
+    }
+
+    fn author(&self) -> String {
+        String::from("Tester")
+    }
+
+    fn options(&self) -> Vec<EngineOption> {
+        vec![
+            EngineOption::spin("Hash", self.hash_mb as i64, 1, 65536),
+            EngineOption::button("Clear Hash"),
+            EngineOption::spin("Threads", self.threads as i64, 1, 256),
+            EngineOption::spin("MultiPV", self.multipv as i64, 1, 256),
+            EngineOption::spin("Move Overhead", self.move_overhead as i64, 0, 5000),
+            EngineOption::check("Ponder", self.ponder),
+            EngineOption::spin(
+                "Skill Level",
+                self.skill_level as i64,
+                0,
+                Skill::MAX_LEVEL as i64,
+            ),
+            EngineOption::check("UCI_LimitStrength", self.limit_strength),
+            EngineOption::spin("UCI_Elo", self.elo, Skill::MIN_ELO, Skill::MAX_ELO),
+            EngineOption::spin("Seed", self.seed as i64, 0, i32::MAX as i64),
+        ]
+    }
+
+    fn set_option(&mut self, name: &str, value: &OptionValue) {
+        match (name, value) {
+            ("Hash", &OptionValue::Spin(mb)) => {
+                self.hash_mb = mb as usize;
+                self.tt = Arc::new(TranspositionTable::new(self.hash_mb));
+            }
+            ("Clear Hash", OptionValue::Button) => self.tt.clear(),
+            ("Threads", &OptionValue::Spin(n)) => self.threads = n as usize,
+            ("MultiPV", &OptionValue::Spin(n)) => self.multipv = n as usize,
+            ("Move Overhead", &OptionValue::Spin(ms)) => self.move_overhead = ms as u32,
+            ("Ponder", &OptionValue::Check(on)) => self.ponder = on,
+            ("Skill Level", &OptionValue::Spin(level)) => self.skill_level = level as u8,
+            ("UCI_LimitStrength", &OptionValue::Check(on)) => self.limit_strength = on,
+            ("UCI_Elo", &OptionValue::Spin(elo)) => self.elo = elo,
+            ("Seed", &OptionValue::Spin(seed)) => self.seed = seed as u64,
+            _ => (),
+        }
+    }
+
+    fn new_game(&mut self) {
+        self.tt.clear();
+    }
+
+    fn search(
+        &self,
+        board: &Board,
+        params: SearchParams,
+        stop: StopToken,
+        info: &mut dyn FnMut(Info),
+    ) -> (Move, Option<Move>) {
+        // Started at `go`, so time spent pondering counts after `ponderhit`
+        let tm = TimeManager::new(&params, board.color_to_move(), self.move_overhead);
+
+        let skill = if self.limit_strength {
+            Skill::from_elo(self.elo)
+        } else {
+            Skill::new(self.skill_level)
+        };
+
+        let max_depth = params
+            .depth
+            .map_or(MAX_DEPTH, |d| (d as usize).clamp(1, MAX_DEPTH))
+            .min(skill.max_depth());
+        let max_nodes = params.nodes.into_iter().chain(skill.max_nodes()).min();
+
+        let should_stop = |stats: &SearchStats| {
+            stop.should_stop()
+                || (!stop.is_pondering() && tm.time_up())
+                || max_nodes.is_some_and(|n| stats.nodes >= n)
+        };
+
+        // A weakened engine picks its move among several candidates, but only the lines asked
+        // for are reported
+        let multipv = if skill.is_enabled() {
+            self.multipv.max(Skill::MULTIPV)
+        } else {
+            self.multipv
+        };
+
+        let legal_moves = board.legal_moves();
+        let mut searchmoves = Vec::new();
+        for mv in &params.searchmoves {
+            match uci::parse_move(mv, board) {
+                Ok(parsed) if legal_moves.contains(&parsed) => searchmoves.push(parsed),
+                _ => info(Info {
+                    string: Some(format!("ignoring illegal searchmove {mv}")),
+                    ..Info::new()
+                }),
+            }
+        }
+
+        // An empty list would search every move instead
+        if !params.searchmoves.is_empty() && searchmoves.is_empty() {
+            return (Move::null(), None);
+        }
+
+        let options = SearchOptions {
+            max_depth,
+            multipv,
+            threads: self.threads,
+            mate: params.mate.map(|n| n as usize),
+            searchmoves,
+        };
+
+        let result = search_parallel(board, &options, &self.tt, &should_stop, |result| {
+            let hashfull = self.tt.hashfull();
+            let elapsed = tm.elapsed().as_millis() as u64;
+            let nodes = result.stats.nodes;
+
+            for (i, line) in result.lines.iter().take(self.multipv).enumerate() {
+                info(Info {
+                    depth: Some(result.depth as u32),
+                    seldepth: Some(result.stats.seldepth as u32),
+                    multipv: Some(i as u32 + 1),
+                    score: Some(UciScore::from_score(line.score)),
+                    nodes: Some(nodes),
+                    nps: Some(nodes * 1000 / elapsed.max(1)),
+                    hashfull: Some(hashfull),
+                    time: Some(elapsed as u32),
+                    pv: line.pv.clone(),
+                    ..Info::new()
+                });
+            }
+        });
+
+        let mut rng = if self.seed == 0 {
+            Rng::from_time()
+        } else {
+            // Different positions still get different random numbers
+            Rng::new(self.seed ^ board.polyglot_key())
+        };
+
+        match skill.pick(&result.lines, &mut rng) {
+            Some(line) => (line.pv[0], line.pv.get(1).copied()),
+            // Stopped before a single move was searched
+            None => {
+                let mv = options
+                    .searchmoves
+                    .first()
+                    .copied()
+                    .or_else(|| legal_moves.first().copied())
+                    .unwrap_or(Move::null());
+                (mv, None)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limited_strength_multipv() {
+        let reported = |options: &[(&str, i64)]| {
+            let mut engine = TestEngine::new();
+            for &(name, value) in options {
+                engine.set_option(name, &OptionValue::Spin(value));
+            }
+            let mut params = SearchParams::new();
+            params.depth = Some(2);
+
+            let mut multipv = Vec::new();
+            engine.search(&Board::default(), params, StopToken::new(), &mut |info| {
+                multipv.extend(info.multipv)
+            });
+            multipv.into_iter().max()
+        };
+
+        // More candidates are searched than the lines the GUI asked for
+        assert_eq!(reported(&[("Skill Level", 5)]), Some(1));
+        assert_eq!(reported(&[("Skill Level", 5), ("MultiPV", 2)]), Some(2));
+    }
+}
@@ -0,0 +1,399 @@
+use std::{
+    io::{self, BufRead, Write},
+    sync::{Arc, Mutex},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    board::{Board, ParseFenError},
+    book::{Book, BookSelection},
+    debug::{perft, perft_parallel_divide, perft_stats_divide, PerftStats, PerftTable},
+    moves::{generate_moves, Move},
+    rng::Rng,
+    uci::{
+        self,
+        options::{EngineOption, OptionValue, Options},
+        SearchParams, UCICommand,
+    },
+};
+
+use super::{Engine, StopToken};
+
+/// Drives an engine over the UCI protocol.
+pub struct UCIEngine {
+    own_book: bool,
+    best_book_move: bool,
+    book: Option<Book>,
+    rng: Rng,
+}
+
+impl Default for UCIEngine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl UCIEngine {
+    pub fn new() -> Self {
+        Self {
+            own_book: false,
+            best_book_move: false,
+            book: None,
+            rng: Rng::from_time(),
+        }
+    }
+
+    /// Options handled by the driver itself rather than the engine.
+    fn options() -> Vec<EngineOption> {
+        vec![
+            EngineOption::check("OwnBook", false),
+            EngineOption::check("Best Book Move", false),
+            EngineOption::string("BookFile", ""),
+        ]
+    }
+
+    /// Applies a driver option. Returns `false` if the option belongs to the engine.
+    fn set_option(&mut self, name: &str, value: &OptionValue) -> bool {
+        match (name, value) {
+            ("OwnBook", &OptionValue::Check(on)) => self.own_book = on,
+            ("Best Book Move", &OptionValue::Check(on)) => self.best_book_move = on,
+            ("BookFile", OptionValue::String(path)) if path.is_empty() => self.book = None,
+            ("BookFile", OptionValue::String(path)) => {
+                self.book = match Book::open(path) {
+                    Ok(book) => Some(book),
+                    Err(e) => {
+                        eprintln!("Could not open book '{}': {}", path, e);
+                        None
+                    }
+                }
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    fn book_move(&mut self, board: &Board) -> Option<Move> {
+        if !self.own_book {
+            return None;
+        }
+
+        let selection = if self.best_book_move {
+            BookSelection::Best
+        } else {
+            BookSelection::WeightedRandom
+        };
+
+        self.book
+            .as_ref()
+            .and_then(|book| book.pick(board, selection, &mut self.rng))
+    }
+
+    pub fn run<E, R, W>(&mut self, mut engine: Arc<E>, input: R, output: W) -> io::Result<W>
+    where
+        E: Engine + 'static,
+        R: BufRead,
+        W: Write + Send + 'static,
+    {
+        let out = Arc::new(Mutex::new(output));
+
+        let mut options = Options::new(
+            Self::options()
+                .into_iter()
+                .chain(engine.options())
+                .collect(),
+        );
+
+        let mut search: Option<Search> = None;
+        let mut board = Board::default();
+
+        for line in input.lines() {
+            let line = line?;
+
+            let command = match uci::parse_command(&line) {
+                Ok(command) => command,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    continue;
+                }
+            };
+
+            match command {
+                UCICommand::Quit => break,
+                UCICommand::Stop => {
+                    if let Some(search) = search.take() {
+                        search.stop();
+                    }
+                }
+                UCICommand::PonderHit => {
+                    if let Some(search) = &search {
+                        search.stop.ponderhit();
+                    }
+                }
+                UCICommand::Display => eprintln!("{}", board),
+                UCICommand::Uci => {
+                    send(&out, UCICommand::Id("name".to_owned(), engine.name()))?;
+                    send(&out, UCICommand::Id("author".to_owned(), engine.author()))?;
+                    for option in options.iter() {
+                        send(&out, UCICommand::Option(option.clone()))?;
+                    }
+                    send(&out, UCICommand::UciOk)?;
+                }
+                UCICommand::IsReady => send(&out, UCICommand::ReadyOk)?,
+                UCICommand::Position(fen, moves) => match Board::from_fen(&fen)
+                    .and_then(|b| b.validate().map(|_| b).map_err(ParseFenError::from))
+                {
+                    Ok(b) => {
+                        board = b;
+                        for mv in moves {
+                            match uci::parse_move(&mv, &board) {
+                                Ok(m) => board = board.do_move(m),
+                                _ => {
+                                    eprintln!("Invalid move");
+                                    break;
+                                }
+                            }
+                        }
+                    }
+                    Err(e) => eprintln!("{}", e),
+                },
+                UCICommand::SetOption(name, value) => match options.set(&name, value.as_deref()) {
+                    Ok((option, value)) => {
+                        if !self.set_option(&option.name, value) {
+                            // Clones the engine if a search is still holding on to it
+                            Arc::make_mut(&mut engine).set_option(&option.name, value);
+                        }
+                    }
+                    Err(e) => eprintln!("{}", e),
+                },
+                UCICommand::UciNewGame => Arc::make_mut(&mut engine).new_game(),
+                UCICommand::Go(params) => {
+                    if let Some(search) = search.take() {
+                        search.stop();
+                    }
+
+                    // Play directly from the book without searching. When pondering,
+                    // bestmove has to wait for `ponderhit` or `stop`, so search instead.
+                    if !params.ponder {
+                        if let Some(mv) = self.book_move(&board) {
+                            send(&out, UCICommand::BestMove(mv, None))?;
+                            continue;
+                        }
+                    }
+
+                    search = Some(Search::start(&engine, &board, params, &out));
+                }
+                UCICommand::Perft(params) => {
+                    let depth = params.depth;
+                    let t0 = Instant::now();
+                    let mut nodes = 0;
+                    if params.stats {
+                        let mut total = PerftStats::default();
+                        for (mv, stats) in perft_stats_divide(&board, depth) {
+                            writeln!(out.lock().unwrap(), "{}: {}", mv, stats.nodes)?;
+                            total += stats;
+                        }
+                        writeln!(out.lock().unwrap(), "{}", total)?;
+                        nodes = total.nodes;
+                    } else if params.threads > 1 || params.hash > 0 {
+                        let table = (params.hash > 0).then(|| PerftTable::new(params.hash));
+                        for (mv, n) in
+                            perft_parallel_divide(&board, depth, params.threads, table.as_ref())
+                        {
+                            writeln!(out.lock().unwrap(), "{}: {}", mv, n)?;
+                            nodes += n;
+                        }
+                    } else {
+                        for mv in &generate_moves(&board) {
+                            let n = perft(&board.do_move(mv), depth.saturating_sub(1));
+                            writeln!(out.lock().unwrap(), "{}: {}", mv, n)?;
+                            nodes += n;
+                        }
+                    }
+                    let elapsed = (Instant::now() - t0).as_secs_f64();
+                    let nps = nodes as f64 / elapsed;
+                    let (nps, prefix) = if nps > 1e6 {
+                        (nps / 1e6, "M")
+                    } else if nps > 1e3 {
+                        (nps / 1e3, "k")
+                    } else {
+                        (nps, "")
+                    };
+                    eprintln!(
+                        "Leaf nodes searched: {}, time elapsed: {:.2} s, {:.2} {}nps",
+                        nodes, elapsed, nps, prefix
+                    );
+                }
+                _ => (),
+            }
+        }
+
+        if let Some(search) = search.take() {
+            search.stop();
+        }
+
+        match Arc::try_unwrap(out) {
+            Ok(out) => Ok(out.into_inner().unwrap()),
+            Err(_) => unreachable!("all search threads have been joined"),
+        }
+    }
+}
+
+fn send<W: Write>(out: &Mutex<W>, command: UCICommand) -> io::Result<()> {
+    let mut out = out.lock().unwrap();
+    writeln!(out, "{}", command)?;
+    out.flush()
+}
+
+/// A search running in its own thread.
+struct Search {
+    stop: StopToken,
+    handle: JoinHandle<()>,
+}
+
+impl Search {
+    fn start<E, W>(
+        engine: &Arc<E>,
+        board: &Board,
+        params: SearchParams,
+        out: &Arc<Mutex<W>>,
+    ) -> Self
+    where
+        E: Engine + 'static,
+        W: Write + Send + 'static,
+    {
+        let stop = if params.ponder {
+            StopToken::pondering()
+        } else {
+            StopToken::new()
+        };
+
+        let handle = thread::spawn({
+            let stop = stop.clone();
+            let board = board.clone();
+            let engine = Arc::clone(engine);
+            let out = Arc::clone(out);
+
+            move || {
+                let infinite = params.infinite;
+                let (mv, ponder) = engine.search(&board, params, stop.clone(), &mut |info| {
+                    // Nothing to do if the GUI is gone
+                    let _ = send(&out, UCICommand::Info(info));
+                });
+
+                // bestmove must not be sent before `stop` or `ponderhit` while pondering or
+                // in an infinite search, even if the search has finished
+                while (infinite || stop.is_pondering()) && !stop.should_stop() {
+                    thread::sleep(Duration::from_millis(1));
+                }
+
+                let _ = send(&out, UCICommand::BestMove(mv, ponder));
+            }
+        });
+
+        Self { stop, handle }
+    }
+
+    /// Stops the search and waits for it to send `bestmove`.
+    fn stop(self) {
+        self.stop.stop();
+        self.handle.join().expect("search thread panicked");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::test_engine::TestEngine;
+
+    fn session(input: &str) -> String {
+        let engine = Arc::new(TestEngine::new());
+        let output = UCIEngine::new()
+            .run(engine, input.as_bytes(), Vec::new())
+            .unwrap();
+
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn handshake() {
+        let output = session("uci\nisready\nquit\n");
+        let lines: Vec<&str> = output.lines().collect();
+
+        assert_eq!(lines[0], "id name Test engine");
+        assert!(lines.contains(&"option name OwnBook type check default false"));
+        assert!(lines.contains(&"option name Hash type spin default 16 min 1 max 65536"));
+        // Not supported, so not advertised
+        assert!(!output.contains("UCI_Chess960"));
+        assert!(!output.contains("SyzygyPath"));
+        assert_eq!(lines[lines.len() - 2..], ["uciok", "readyok"]);
+    }
+
+    #[test]
+    fn search_session() {
+        // Mate in one is found before the search checks whether to stop
+        let output = session(
+            "position fen 6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1\n\
+             go mate 1\n\
+             quit\n",
+        );
+        assert!(output.contains("score mate 1"));
+        assert_eq!(output.lines().last(), Some("bestmove a1a8"));
+
+        // bestmove waits for stop in infinite and ponder searches
+        let output = session("go infinite\nstop\ngo ponder depth 1\nponderhit\nisready\n");
+        let lines: Vec<&str> = output.lines().filter(|l| !l.starts_with("info")).collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("bestmove"));
+        assert!(lines[1..].contains(&"readyok"));
+    }
+
+    #[test]
+    fn perft_session() {
+        let divide_total = |output: &str| -> u64 {
+            output
+                .lines()
+                .filter_map(|l| l.split_once(": ")?.1.parse::<u64>().ok())
+                .sum()
+        };
+
+        let output = session("go perft 3 stats\nquit\n");
+        assert_eq!(output.lines().count(), 21);
+        assert_eq!(divide_total(&output), 8902);
+        assert_eq!(
+            output.lines().last(),
+            Some(
+                "Nodes: 8902, captures: 34, e.p.: 0, castles: 0, promotions: 0, checks: 12, \
+                 discovery checks: 0, double checks: 0, checkmates: 0"
+            )
+        );
+
+        let output = session(
+            "position fen r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1\n\
+             go perft 3 threads 2 hash 1\n\
+             quit\n",
+        );
+        assert_eq!(output.lines().count(), 48);
+        assert_eq!(divide_total(&output), 97862);
+    }
+
+    #[test]
+    fn illegal_searchmoves() {
+        let output = session(
+            "go depth 2 searchmoves e2e5 d2d4\n\
+             quit\n",
+        );
+        assert!(output.contains("info string ignoring illegal searchmove e2e5"));
+        // A ponder move is only sent if the search finishes before `quit`
+        assert!(output.lines().last().unwrap().starts_with("bestmove d2d4"));
+
+        // No legal move left to search
+        let output = session(
+            "go depth 2 searchmoves e2e5 a1a1\n\
+             quit\n",
+        );
+        assert!(output.contains("info string ignoring illegal searchmove a1a1"));
+        assert!(!output.contains("info depth"));
+        assert_eq!(output.lines().last(), Some("bestmove 0000"));
+    }
+}
@@ -0,0 +1,417 @@
+use std::{
+    io::{self, BufRead, Write},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+};
+
+use crate::{
+    board::{Board, ParseFenError},
+    color::Color,
+    moves::Move,
+    pgn,
+    uci::{self, SearchParams},
+    xboard::{self, Level, Thinking, XBoardCommand},
+};
+
+use super::{Engine, StopToken};
+
+/// Drives an engine over the CECP (XBoard/WinBoard) protocol.
+pub struct XBoardEngine {
+    board: Board,
+    /// Positions before each move, for `undo` and `remove`
+    history: Vec<Board>,
+    /// In force mode the engine only keeps track of the moves
+    force: bool,
+    engine_color: Color,
+    analyzing: bool,
+    post: bool,
+    level: Option<Level>,
+    /// Seconds per move set by `st`
+    move_time: Option<u32>,
+    depth: Option<u8>,
+    /// Clocks in milliseconds, set by `time` and `otim`
+    time: u32,
+    opponent_time: u32,
+}
+
+impl Default for XBoardEngine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl XBoardEngine {
+    pub fn new() -> Self {
+        Self {
+            board: Board::default(),
+            history: Vec::new(),
+            force: false,
+            engine_color: Color::Black,
+            analyzing: false,
+            post: false,
+            level: None,
+            move_time: None,
+            depth: None,
+            time: 0,
+            opponent_time: 0,
+        }
+    }
+
+    fn features<E: Engine>(engine: &E) -> XBoardCommand {
+        let features = [
+            ("myname", format!("\"{}\"", engine.name())),
+            ("setboard", "1".to_owned()),
+            ("usermove", "1".to_owned()),
+            ("ping", "1".to_owned()),
+            ("analyze", "1".to_owned()),
+            ("colors", "0".to_owned()),
+            ("sigint", "0".to_owned()),
+            ("sigterm", "0".to_owned()),
+            ("reuse", "1".to_owned()),
+            ("done", "1".to_owned()),
+        ];
+
+        XBoardCommand::Features(
+            features
+                .into_iter()
+                .map(|(name, value)| (name.to_owned(), value))
+                .collect(),
+        )
+    }
+
+    fn search_params(&self) -> SearchParams {
+        let mut params = SearchParams::new();
+        params.depth = self.depth;
+
+        if self.analyzing {
+            params.infinite = true;
+        } else if let Some(seconds) = self.move_time {
+            params.movetime = Some(seconds * 1000);
+        } else if self.time > 0 {
+            let (wtime, btime) = match self.engine_color {
+                Color::White => (self.time, self.opponent_time),
+                Color::Black => (self.opponent_time, self.time),
+            };
+            params.wtime = wtime;
+            params.btime = btime;
+
+            if let Some(level) = self.level {
+                let inc = level.increment.min(u16::MAX as u32) as u16;
+                params.winc = inc;
+                params.binc = inc;
+
+                if level.moves > 0 {
+                    let played = (self.board.fullmove_number() as u32 - 1) % level.moves;
+                    params.movestogo = Some((level.moves - played) as u16);
+                }
+            }
+        }
+
+        params
+    }
+
+    fn do_move(&mut self, mv: Move) {
+        self.history.push(self.board.clone());
+        self.board = self.board.do_move(mv);
+    }
+
+    fn undo(&mut self, plies: usize) {
+        for _ in 0..plies {
+            if let Some(board) = self.history.pop() {
+                self.board = board;
+            }
+        }
+    }
+
+    /// Ends the running search, if any. The move is kept only if it was sent to the GUI.
+    fn finish(&mut self, search: &mut Option<Search>, cancel: bool) {
+        if let Some(search) = search.take() {
+            if let Some(mv) = search.finish(cancel) {
+                self.do_move(mv);
+            }
+        }
+    }
+
+    /// Starts thinking if it is the engine's turn, or analyzing in analyze mode.
+    fn start<E, W>(&self, engine: &Arc<E>, out: &Arc<Mutex<W>>) -> Option<Search>
+    where
+        E: Engine + 'static,
+        W: Write + Send + 'static,
+    {
+        let thinking = !self.force && self.board.color_to_move() == self.engine_color;
+        if !self.analyzing && !thinking {
+            return None;
+        }
+
+        Some(Search::start(
+            engine,
+            &self.board,
+            self.search_params(),
+            !self.analyzing,
+            self.post,
+            out,
+        ))
+    }
+
+    pub fn run<E, R, W>(&mut self, mut engine: Arc<E>, input: R, output: W) -> io::Result<W>
+    where
+        E: Engine + 'static,
+        R: BufRead,
+        W: Write + Send + 'static,
+    {
+        let out = Arc::new(Mutex::new(output));
+        let mut search: Option<Search> = None;
+
+        for line in input.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let command = match xboard::parse_command(&line) {
+                Ok(command) => command,
+                Err(e) => {
+                    send(&out, e.reply())?;
+                    continue;
+                }
+            };
+
+            match command {
+                XBoardCommand::Quit => break,
+                XBoardCommand::Protover(_) => send(&out, Self::features(&*engine))?,
+                XBoardCommand::Ping(n) => send(&out, XBoardCommand::Pong(n))?,
+                XBoardCommand::New => {
+                    self.finish(&mut search, true);
+                    *self = Self {
+                        post: self.post,
+                        ..Self::new()
+                    };
+                    Arc::make_mut(&mut engine).new_game();
+                }
+                XBoardCommand::Force => {
+                    self.finish(&mut search, true);
+                    self.force = true;
+                }
+                XBoardCommand::Result(_) => {
+                    self.finish(&mut search, true);
+                    self.force = true;
+                }
+                XBoardCommand::Go => {
+                    self.finish(&mut search, true);
+                    self.force = false;
+                    self.engine_color = self.board.color_to_move();
+                    search = self.start(&engine, &out);
+                }
+                XBoardCommand::MoveNow if !self.analyzing => self.finish(&mut search, false),
+                XBoardCommand::UserMove(text) => {
+                    // The engine's own move has been sent if the GUI is already replying
+                    self.finish(&mut search, self.analyzing);
+
+                    let mv = uci::parse_move(&text, &self.board)
+                        .ok()
+                        .or_else(|| pgn::parse_san(&text, &self.board).ok());
+                    match mv {
+                        Some(mv) => {
+                            self.do_move(mv);
+                            search = self.start(&engine, &out);
+                        }
+                        None => {
+                            send(&out, XBoardCommand::IllegalMove(text))?;
+                            search = self.start(&engine, &out);
+                        }
+                    }
+                }
+                XBoardCommand::SetBoard(fen) => {
+                    self.finish(&mut search, true);
+                    match Board::from_fen(&fen)
+                        .and_then(|b| b.validate().map(|_| b).map_err(ParseFenError::from))
+                    {
+                        Ok(board) => {
+                            self.board = board;
+                            self.history.clear();
+                        }
+                        Err(e) => send(&out, XBoardCommand::Error(e.to_string(), fen))?,
+                    }
+                    search = self.start(&engine, &out);
+                }
+                XBoardCommand::Undo | XBoardCommand::Remove => {
+                    self.finish(&mut search, true);
+                    let plies = if matches!(command, XBoardCommand::Undo) {
+                        1
+                    } else {
+                        2
+                    };
+                    self.undo(plies);
+                    search = self.start(&engine, &out);
+                }
+                XBoardCommand::Analyze => {
+                    self.finish(&mut search, true);
+                    self.analyzing = true;
+                    search = self.start(&engine, &out);
+                }
+                XBoardCommand::Exit => {
+                    self.finish(&mut search, true);
+                    self.analyzing = false;
+                }
+                XBoardCommand::Level(level) => {
+                    self.level = Some(level);
+                    self.move_time = None;
+                }
+                XBoardCommand::St(seconds) => self.move_time = Some(seconds),
+                XBoardCommand::Sd(depth) => self.depth = Some(depth),
+                XBoardCommand::Time(cs) => self.time = cs * 10,
+                XBoardCommand::Otim(cs) => self.opponent_time = cs * 10,
+                XBoardCommand::Post => self.post = true,
+                XBoardCommand::NoPost => self.post = false,
+                _ => (),
+            }
+        }
+
+        self.finish(&mut search, true);
+
+        match Arc::try_unwrap(out) {
+            Ok(out) => Ok(out.into_inner().unwrap()),
+            Err(_) => unreachable!("all search threads have been joined"),
+        }
+    }
+}
+
+fn send<W: Write>(out: &Mutex<W>, command: XBoardCommand) -> io::Result<()> {
+    let mut out = out.lock().unwrap();
+    writeln!(out, "{}", command)?;
+    out.flush()
+}
+
+/// A search running in its own thread. Unless analyzing, the thread sends the move and
+/// returns it.
+struct Search {
+    stop: StopToken,
+    cancelled: Arc<AtomicBool>,
+    handle: JoinHandle<Option<Move>>,
+}
+
+impl Search {
+    fn start<E, W>(
+        engine: &Arc<E>,
+        board: &Board,
+        params: SearchParams,
+        play: bool,
+        post: bool,
+        out: &Arc<Mutex<W>>,
+    ) -> Self
+    where
+        E: Engine + 'static,
+        W: Write + Send + 'static,
+    {
+        let stop = StopToken::new();
+        let cancelled = Arc::new(AtomicBool::new(false));
+
+        let handle = thread::spawn({
+            let stop = stop.clone();
+            let cancelled = Arc::clone(&cancelled);
+            let board = board.clone();
+            let engine = Arc::clone(engine);
+            let out = Arc::clone(out);
+
+            move || {
+                let (mv, _) = engine.search(&board, params, stop, &mut |info| {
+                    let thinking = match (info.depth, info.score) {
+                        (Some(depth), Some(score)) if post && info.multipv.unwrap_or(1) == 1 => {
+                            Thinking {
+                                depth,
+                                score,
+                                time: info.time.unwrap_or(0) / 10,
+                                nodes: info.nodes.unwrap_or(0),
+                                pv: info.pv,
+                            }
+                        }
+                        _ => return,
+                    };
+                    // Nothing to do if the GUI is gone
+                    let _ = send(&out, XBoardCommand::Thinking(thinking));
+                });
+
+                // Checked under the lock, so that the move counts exactly when it was sent
+                let mut out = out.lock().unwrap();
+                let legal = board.legal_moves().contains(&mv);
+                if !play || !legal || cancelled.load(Ordering::SeqCst) {
+                    return None;
+                }
+
+                let _ = writeln!(out, "{}", XBoardCommand::Move(mv)).and_then(|_| out.flush());
+                Some(mv)
+            }
+        });
+
+        Self {
+            stop,
+            cancelled,
+            handle,
+        }
+    }
+
+    /// Stops the search if `cancel`, otherwise waits for it to finish. Returns the move if one
+    /// was sent.
+    fn finish(self, cancel: bool) -> Option<Move> {
+        if cancel {
+            self.cancelled.store(true, Ordering::SeqCst);
+        }
+        self.stop.stop();
+        self.handle.join().expect("search thread panicked")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::test_engine::TestEngine;
+
+    fn session(input: &str) -> String {
+        let engine = Arc::new(TestEngine::new());
+        let output = XBoardEngine::new()
+            .run(engine, input.as_bytes(), Vec::new())
+            .unwrap();
+
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn feature_negotiation() {
+        let output = session("xboard\nprotover 2\nping 1\nfly\n");
+        let lines: Vec<&str> = output.lines().collect();
+
+        assert!(lines[0].starts_with("feature myname=\"Test engine\""));
+        assert!(lines[0].ends_with("done=1"));
+        assert_eq!(lines[1..], ["pong 1", "Error (unknown command): fly"]);
+    }
+
+    #[test]
+    fn play_moves() {
+        // The engine plays black and replies to the move, then both moves are taken back
+        let output = session(
+            "new\nsd 1\npost\nusermove e2e4\n?\nremove\nforce\n\
+             setboard 6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1\ngo\n?\nping 2\n",
+        );
+        let lines: Vec<&str> = output.lines().collect();
+
+        let moves: Vec<&str> = lines
+            .iter()
+            .copied()
+            .filter(|l| l.starts_with("move"))
+            .collect();
+        assert_eq!(moves.len(), 2);
+        assert_eq!(moves[1], "move a1a8");
+        assert!(lines.iter().any(|l| l.starts_with("1 100001 ")));
+        assert_eq!(lines.last(), Some(&"pong 2"));
+    }
+
+    #[test]
+    fn analyze() {
+        let output = session("post\nanalyze\nusermove e2e4\nundo\nexit\nping 1\n");
+        assert!(!output.contains("move"));
+        assert!(output.ends_with("pong 1\n"));
+    }
+}
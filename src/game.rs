@@ -0,0 +1,505 @@
+use std::fmt;
+
+use crate::{
+    board::Board,
+    color::Color,
+    moves::Move,
+    pgn::{self, GameResult},
+    piece::PieceKind,
+    square::Square,
+};
+
+/// Why a game ended.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Termination {
+    Checkmate,
+    Stalemate,
+    Repetition,
+    FiftyMoves,
+    InsufficientMaterial,
+    /// Stopped early by an adjudication rule
+    Adjudication,
+    TimeForfeit,
+    IllegalMove,
+    /// The engine crashed or stopped responding
+    Disconnect,
+}
+
+impl fmt::Display for Termination {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Termination::Checkmate => write!(f, "checkmate"),
+            Termination::Stalemate => write!(f, "stalemate"),
+            Termination::Repetition => write!(f, "threefold repetition"),
+            Termination::FiftyMoves => write!(f, "fifty move rule"),
+            Termination::InsufficientMaterial => write!(f, "insufficient material"),
+            Termination::Adjudication => write!(f, "adjudication"),
+            Termination::TimeForfeit => write!(f, "time forfeit"),
+            Termination::IllegalMove => write!(f, "illegal move"),
+            Termination::Disconnect => write!(f, "disconnect"),
+        }
+    }
+}
+
+/// A game in progress. Keeps the moves played, their scores and the result once the game
+/// is over.
+#[derive(Clone)]
+pub struct Game {
+    start: Board,
+    board: Board,
+    moves: Vec<Move>,
+    /// Score of each move from white's point of view, as reported by the engine
+    scores: Vec<Option<i32>>,
+    /// Whether each score comes from a search that probed tablebases
+    tablebase: Vec<bool>,
+    /// Keys of all positions, for repetition detection
+    keys: Vec<u64>,
+    outcome: Option<(GameResult, Termination)>,
+}
+
+impl Game {
+    pub fn new(start: Board) -> Self {
+        Self {
+            keys: vec![start.polyglot_key()],
+            board: start.clone(),
+            start,
+            moves: Vec::new(),
+            scores: Vec::new(),
+            tablebase: Vec::new(),
+            outcome: None,
+        }
+    }
+
+    pub fn start(&self) -> &Board {
+        &self.start
+    }
+
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    pub fn moves(&self) -> &[Move] {
+        &self.moves
+    }
+
+    pub fn scores(&self) -> &[Option<i32>] {
+        &self.scores
+    }
+
+    pub fn outcome(&self) -> Option<(GameResult, Termination)> {
+        self.outcome
+    }
+
+    /// Plays a legal move with the score the engine gave it, from white's point of view, and
+    /// ends the game if the rules say so.
+    pub fn push(&mut self, mv: Move, score: Option<i32>) {
+        self.board = self.board.do_move(mv);
+        self.moves.push(mv);
+        self.scores.push(score);
+        self.tablebase.push(false);
+        self.keys.push(self.board.polyglot_key());
+
+        if self.outcome.is_none() {
+            self.outcome = self.rules_outcome();
+        }
+    }
+
+    /// Marks the score of the last move as coming from a search that probed tablebases.
+    pub fn mark_tablebase_score(&mut self) {
+        if let Some(tablebase) = self.tablebase.last_mut() {
+            *tablebase = true;
+        }
+    }
+
+    /// Ends the game for a reason outside the rules of chess, e.g. adjudication.
+    pub fn finish(&mut self, result: GameResult, termination: Termination) {
+        self.outcome = Some((result, termination));
+    }
+
+    fn rules_outcome(&self) -> Option<(GameResult, Termination)> {
+        if !self.board.has_legal_moves() {
+            return Some(if self.board.is_in_check() {
+                (
+                    GameResult::win_for(!self.board.color_to_move()),
+                    Termination::Checkmate,
+                )
+            } else {
+                (GameResult::Draw, Termination::Stalemate)
+            });
+        }
+
+        if self.board.halfmove_clock() >= 100 {
+            return Some((GameResult::Draw, Termination::FiftyMoves));
+        }
+
+        // Only positions since the last capture or pawn move can repeat
+        let key = self.board.polyglot_key();
+        let recent = self.keys.len().min(self.board.halfmove_clock() + 1);
+        let repetitions = self.keys[self.keys.len() - recent..]
+            .iter()
+            .filter(|&&k| k == key)
+            .count();
+        if repetitions >= 3 {
+            return Some((GameResult::Draw, Termination::Repetition));
+        }
+
+        if is_insufficient_material(&self.board) {
+            return Some((GameResult::Draw, Termination::InsufficientMaterial));
+        }
+
+        None
+    }
+
+    /// Writes the game as PGN with the given tags, which should include the seven tag roster
+    /// except `Result`.
+    pub fn to_pgn(&self, tags: &[(String, String)]) -> String {
+        let result = self.outcome.map_or("*".to_owned(), |(r, _)| r.to_string());
+
+        let mut pgn = String::new();
+        for (name, value) in tags {
+            pgn.push_str(&format!("[{} \"{}\"]\n", name, value.replace('"', "\\\"")));
+        }
+        pgn.push_str(&format!("[Result \"{}\"]\n", result));
+        if self.start.fen() != Board::default().fen() {
+            pgn.push_str(&format!("[SetUp \"1\"]\n[FEN \"{}\"]\n", self.start.fen()));
+        }
+        if let Some((_, termination)) = self.outcome {
+            pgn.push_str(&format!("[Termination \"{}\"]\n", termination));
+        }
+        pgn.push('\n');
+
+        let mut tokens = Vec::new();
+        let mut board = self.start.clone();
+        for (i, &mv) in self.moves.iter().enumerate() {
+            if board.color_to_move() == Color::White {
+                tokens.push(format!("{}.", board.fullmove_number()));
+            } else if i == 0 {
+                tokens.push(format!("{}...", board.fullmove_number()));
+            }
+            tokens.push(pgn::to_san(mv, &board));
+            board = board.do_move(mv);
+        }
+        tokens.push(result);
+
+        // Lines are kept below 80 characters
+        let mut line = String::new();
+        for token in tokens {
+            if !line.is_empty() && line.len() + token.len() >= 80 {
+                pgn.push_str(&line);
+                pgn.push('\n');
+                line.clear();
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(&token);
+        }
+        pgn.push_str(&line);
+        pgn.push('\n');
+
+        pgn
+    }
+}
+
+/// Neither side can mate: bare kings, or a single minor piece.
+fn is_insufficient_material(board: &Board) -> bool {
+    let pieces: Vec<PieceKind> = (0..64)
+        .filter_map(|i| board.at(Square::from_index(i)))
+        .map(|p| p.kind())
+        .filter(|&kind| kind != PieceKind::King)
+        .collect();
+
+    matches!(pieces[..], [] | [PieceKind::Knight] | [PieceKind::Bishop])
+}
+
+/// Rules for ending games early, as used by match runners. Scores are from white's point of
+/// view and in centipawns.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Adjudication {
+    /// Draw once both engines have reported a score close to zero for some moves in a row.
+    pub draw: Option<DrawRule>,
+    /// Win once both engines have agreed for some moves in a row that one side is far ahead.
+    pub resign: Option<ResignRule>,
+    /// Draw after this many full moves.
+    pub max_moves: Option<usize>,
+    /// Trust the score of an engine that probed tablebases in a position they cover.
+    pub tablebase: Option<TablebaseRule>,
+    /// Draw positions where neither side has the material to mate, including the endings with
+    /// only bishops on squares of one color that don't end the game by themselves.
+    pub insufficient_material: bool,
+}
+
+/// Draw after `move_count` moves by each side with scores within `score` of zero, from move
+/// `move_number` on.
+#[derive(Debug, Clone, Copy)]
+pub struct DrawRule {
+    pub move_number: usize,
+    pub move_count: usize,
+    pub score: i32,
+}
+
+/// Win after `move_count` moves by each side with scores of at least `score` for one side.
+#[derive(Debug, Clone, Copy)]
+pub struct ResignRule {
+    pub move_count: usize,
+    pub score: i32,
+}
+
+/// End the game with the tablebase result once an engine reports tablebase hits for a position
+/// with at most `max_pieces` pieces. A score of zero is a draw, a score of at least `score`
+/// for one side a win. Other scores are left alone, e.g. cursed wins.
+#[derive(Debug, Clone, Copy)]
+pub struct TablebaseRule {
+    pub max_pieces: usize,
+    pub score: i32,
+}
+
+impl Adjudication {
+    /// Returns the adjudicated result of `game`, if any rule applies.
+    pub fn adjudicate(&self, game: &Game) -> Option<GameResult> {
+        let plies = game.moves().len();
+        let board = game.board();
+
+        if self.insufficient_material && cannot_mate(board) {
+            return Some(GameResult::Draw);
+        }
+
+        if let Some(rule) = self.tablebase {
+            if let Some(result) = tablebase_result(game, rule) {
+                return Some(result);
+            }
+        }
+
+        if let Some(max_moves) = self.max_moves {
+            if plies >= 2 * max_moves {
+                return Some(GameResult::Draw);
+            }
+        }
+
+        if let Some((rule, scores)) = self
+            .resign
+            .and_then(|rule| Some((rule, last_scores(game, 2 * rule.move_count)?)))
+        {
+            if scores.iter().all(|&s| s >= rule.score) {
+                return Some(GameResult::WhiteWins);
+            }
+            if scores.iter().all(|&s| s <= -rule.score) {
+                return Some(GameResult::BlackWins);
+            }
+        }
+
+        if let Some(rule) = self.draw {
+            let scores = last_scores(game, 2 * rule.move_count);
+            if board.fullmove_number() >= rule.move_number
+                && scores.is_some_and(|scores| scores.iter().all(|&s| s.abs() <= rule.score))
+            {
+                return Some(GameResult::Draw);
+            }
+        }
+
+        None
+    }
+}
+
+/// Result of the tablebase score of the last move, if the position it was searched in is
+/// covered by the rule.
+fn tablebase_result(game: &Game, rule: TablebaseRule) -> Option<GameResult> {
+    let (&mv, &score) = (game.moves.last()?, game.scores.last()?);
+    if !*game.tablebase.last()? {
+        return None;
+    }
+
+    // The position before the move had the captured piece too
+    let pieces = game.board.pieces().popcount() + mv.kind().is_capture() as usize;
+    if pieces > rule.max_pieces {
+        return None;
+    }
+
+    match score? {
+        0 => Some(GameResult::Draw),
+        s if s >= rule.score => Some(GameResult::WhiteWins),
+        s if s <= -rule.score => Some(GameResult::BlackWins),
+        _ => None,
+    }
+}
+
+/// The last `n` scores of `game`, if there are that many and none are missing.
+fn last_scores(game: &Game, n: usize) -> Option<Vec<i32>> {
+    let scores = game.scores();
+    if n == 0 || scores.len() < n {
+        return None;
+    }
+
+    scores[scores.len() - n..].iter().copied().collect()
+}
+
+/// No sequence of legal moves leads to mate: bare kings, a single knight, or any number of
+/// bishops of either side that all stand on squares of one color.
+fn cannot_mate(board: &Board) -> bool {
+    let mut bishop_colors = [false, false];
+    let mut knights = 0;
+    for i in 0..64 {
+        let square = Square::from_index(i);
+        match board.at(square).map(|p| p.kind()) {
+            Some(PieceKind::Bishop) => {
+                let rank = square.rank().to_index();
+                let file = square.file().to_index();
+                bishop_colors[(rank + file) % 2] = true;
+            }
+            Some(PieceKind::Knight) => knights += 1,
+            Some(PieceKind::King) | None => (),
+            Some(_) => return false,
+        }
+    }
+
+    match bishop_colors {
+        [false, false] => knights <= 1,
+        [true, true] => false,
+        _ => knights == 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::uci::parse_move;
+
+    fn play(game: &mut Game, moves: &str) {
+        for mv in moves.split_whitespace() {
+            let mv = parse_move(mv, game.board()).unwrap();
+            game.push(mv, None);
+        }
+    }
+
+    #[test]
+    fn rules() {
+        let mut game = Game::new(Board::default());
+        play(&mut game, "f2f3 e7e5 g2g4");
+        assert!(game.outcome().is_none());
+        play(&mut game, "d8h4");
+        assert_eq!(
+            game.outcome(),
+            Some((GameResult::BlackWins, Termination::Checkmate))
+        );
+
+        let mut game = Game::new(Board::default());
+        play(&mut game, "g1f3 g8f6 f3g1 f6g8 g1f3 g8f6 f3g1");
+        assert!(game.outcome().is_none());
+        play(&mut game, "f6g8");
+        assert_eq!(
+            game.outcome(),
+            Some((GameResult::Draw, Termination::Repetition))
+        );
+
+        let board = Board::from_fen("4k3/8/8/8/8/8/3p4/4K1N1 w - - 0 1").unwrap();
+        let mut game = Game::new(board);
+        play(&mut game, "e1d2");
+        assert_eq!(
+            game.outcome(),
+            Some((GameResult::Draw, Termination::InsufficientMaterial))
+        );
+    }
+
+    #[test]
+    fn adjudication() {
+        let rules = Adjudication {
+            resign: Some(ResignRule {
+                move_count: 2,
+                score: 500,
+            }),
+            draw: Some(DrawRule {
+                move_number: 1,
+                move_count: 2,
+                score: 10,
+            }),
+            ..Default::default()
+        };
+
+        let mut game = Game::new(Board::default());
+        for (mv, score) in ["e2e4", "e7e5", "g1f3", "b8c6"].iter().zip([5, -5, 0, 8]) {
+            assert!(rules.adjudicate(&game).is_none());
+            let mv = parse_move(mv, game.board()).unwrap();
+            game.push(mv, Some(score));
+        }
+        assert_eq!(rules.adjudicate(&game), Some(GameResult::Draw));
+
+        let mut game = Game::new(Board::default());
+        for (mv, score) in ["e2e4", "e7e5", "g1f3", "b8c6"]
+            .iter()
+            .zip([600, 500, 700, 900])
+        {
+            let mv = parse_move(mv, game.board()).unwrap();
+            game.push(mv, Some(-score));
+        }
+        assert_eq!(rules.adjudicate(&game), Some(GameResult::BlackWins));
+
+        let material = Adjudication {
+            insufficient_material: true,
+            ..Default::default()
+        };
+        let adjudicate = |fen| material.adjudicate(&Game::new(Board::from_fen(fen).unwrap()));
+
+        // Bishops on one color, any number and either side
+        assert_eq!(
+            adjudicate("4k3/3b4/8/8/8/8/8/4KB2 w - - 0 1"),
+            Some(GameResult::Draw)
+        );
+        assert_eq!(
+            adjudicate("4k3/8/8/8/8/8/1B1B4/2b1K3 w - - 0 1"),
+            Some(GameResult::Draw)
+        );
+        // Mates exist, even if they can't be forced
+        assert_eq!(adjudicate("4k3/2n5/8/8/8/8/8/4KB2 w - - 0 1"), None);
+        assert_eq!(adjudicate("4k3/2b5/8/8/8/8/8/4KB2 w - - 0 1"), None);
+        assert_eq!(adjudicate("4k3/2n5/8/8/8/8/8/4KN2 w - - 0 1"), None);
+        assert_eq!(adjudicate("4k3/8/8/8/8/8/8/3NKN2 w - - 0 1"), None);
+        assert_eq!(adjudicate("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1"), None);
+    }
+
+    #[test]
+    fn tablebase_adjudication() {
+        let rules = Adjudication {
+            tablebase: Some(TablebaseRule {
+                max_pieces: 5,
+                score: 1000,
+            }),
+            ..Default::default()
+        };
+        let after = |fen, mv, score, tablebase| {
+            let mut game = Game::new(Board::from_fen(fen).unwrap());
+            let mv = parse_move(mv, game.board()).unwrap();
+            game.push(mv, Some(score));
+            if tablebase {
+                game.mark_tablebase_score();
+            }
+            rules.adjudicate(&game)
+        };
+        let kpk = "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1";
+
+        assert_eq!(after(kpk, "e2e4", 1500, true), Some(GameResult::WhiteWins));
+        assert_eq!(after(kpk, "e1d2", 0, true), Some(GameResult::Draw));
+        assert_eq!(after(kpk, "e1d1", -1500, true), Some(GameResult::BlackWins));
+        // Scores without tablebase hits, or not clearly decided, are not trusted
+        assert_eq!(after(kpk, "e2e4", 1500, false), None);
+        assert_eq!(after(kpk, "e2e4", 300, true), None);
+        // Six pieces were on the board when the engine searched
+        let capture = "4k3/8/8/3p4/4P3/8/8/R3K2R w - - 0 1";
+        assert_eq!(after(capture, "e4d5", 0, true), None);
+        assert_eq!(after(capture, "a1a2", 0, true), None);
+    }
+
+    #[test]
+    fn write_pgn() {
+        let board = Board::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 b - - 0 1").unwrap();
+        let mut game = Game::new(board);
+        play(&mut game, "g8h8 a1a8");
+
+        let tags = [("White".to_owned(), "A \"B\"".to_owned())];
+        assert_eq!(
+            game.to_pgn(&tags),
+            "[White \"A \\\"B\\\"\"]\n[Result \"1-0\"]\n[SetUp \"1\"]\n\
+             [FEN \"6k1/5ppp/8/8/8/8/8/R5K1 b - - 0 1\"]\n[Termination \"checkmate\"]\n\n\
+             1... Kh8 2. Ra8# 1-0\n"
+        );
+    }
+}
@@ -1,11 +1,23 @@
 pub mod bitboard;
 pub mod board;
+pub mod book;
 pub mod castling_rights;
 pub mod color;
+pub mod datagen;
 pub mod debug;
+pub mod engine;
 pub mod eval;
+pub mod game;
 pub mod moves;
+pub mod pgn;
 pub mod piece;
+pub mod rng;
 pub mod search;
+pub mod skill;
+pub mod sprt;
 pub mod square;
+pub mod timeman;
+pub mod tt;
 pub mod uci;
+pub mod xboard;
+mod zobrist;
@@ -1,4 +1,4 @@
-use std::{fmt, ops, str::FromStr};
+use std::{fmt, ops};
 
 use crate::{
     bitboard::{Bitboard, Direction},
@@ -6,10 +6,10 @@ use crate::{
     castling_rights::CastlingRights,
     color::Color,
     piece::PieceKind,
-    square::{File, Rank, Square},
+    square::{Rank, Square},
 };
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum MoveKind {
     Quiet,
     Cap,
@@ -39,19 +39,20 @@ impl MoveKind {
     }*/
 
     pub(crate) fn is_capture(self) -> bool {
-        match self {
+        matches!(
+            self,
             MoveKind::Cap
-            | MoveKind::EnPassant
-            | MoveKind::PromCapQueen
-            | MoveKind::PromCapRook
-            | MoveKind::PromCapBishop
-            | MoveKind::PromCapKnight => true,
-            _ => false,
-        }
+                | MoveKind::EnPassant
+                | MoveKind::PromCapQueen
+                | MoveKind::PromCapRook
+                | MoveKind::PromCapBishop
+                | MoveKind::PromCapKnight
+        )
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+/// The default move is the null move.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
 pub struct Move(u16);
 
 // #[derive(Debug, Clone, Copy)]
@@ -159,6 +160,12 @@ impl ops::Index<usize> for Movelist {
     }
 }
 
+impl Default for Movelist {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Movelist {
     pub fn new() -> Self {
         Self {
@@ -187,7 +194,7 @@ impl<'a> IntoIterator for &'a Movelist {
 
     fn into_iter(self) -> Self::IntoIter {
         MovelistIntoIter {
-            list: &self,
+            list: self,
             index: 0,
         }
     }
@@ -281,7 +288,7 @@ fn pawn_moves(board: &Board, pieces: Bitboard, allowed_squares: Bitboard, moves:
     // en passant
     let ep_bb = board
         .en_passant_square()
-        .map(|s| Bitboard::new(s))
+        .map(Bitboard::new)
         .unwrap_or(Bitboard::EMPTY);
 
     for dir in capture_directions {
@@ -412,13 +419,116 @@ fn pinned_moves(b: &Board, p: Bitboard, a: Bitboard, moves: &mut Movelist) {
     }
 }
 
-pub fn generate_moves(board: &Board) -> Movelist {
+/// Castlings of the side to move to squares on `to_mask`, given the squares attacked by the
+/// opponent.
+pub(crate) fn castling_moves(
+    board: &Board,
+    king_square: Square,
+    attacks: Bitboard,
+    to_mask: Bitboard,
+    moves: &mut Movelist,
+) {
+    let us = board.color_to_move();
+    let all = board.pieces();
+
+    let (king_travel, middle_squares) = match us {
+        Color::White => (Bitboard::from_u64(28), Bitboard::from_u64(14)),
+        Color::Black => (Bitboard::from_u64(28 << 56), Bitboard::from_u64(14 << 56)),
+    };
+
+    let can_castle = board.has_castling_rights(CastlingRights::Queenside(us))
+        && ((king_travel & attacks) | (middle_squares & all)).is_empty();
+
+    if can_castle {
+        let to = Square::from_index(king_square.to_index() - 2);
+        if (to_mask & to).is_non_empty() {
+            moves.push(Move::new(king_square, to, MoveKind::Castling));
+        }
+    }
+
+    let (king_travel, middle_squares) = match us {
+        Color::White => (Bitboard::from_u64(112), Bitboard::from_u64(96)),
+        Color::Black => (Bitboard::from_u64(112 << 56), Bitboard::from_u64(96 << 56)),
+    };
+
+    let can_castle = board.has_castling_rights(CastlingRights::Kingside(us))
+        && ((king_travel & attacks) | (middle_squares & all)).is_empty();
+
+    if can_castle {
+        let to = Square::from_index(king_square.to_index() + 2);
+        if (to_mask & to).is_non_empty() {
+            moves.push(Move::new(king_square, to, MoveKind::Castling));
+        }
+    }
+}
+
+/// Generates moves that follow the piece movement rules but may leave the own king in
+/// check. Castlings are only generated when they are legal.
+pub fn generate_pseudo_legal_moves(board: &Board) -> Movelist {
     let mut moves = Movelist::new();
 
+    let us = board.color_to_move();
+    let friendly = board.pieces_by_color(us);
+    let all = board.pieces();
+
+    let bishops = board.pieces_by_kind(PieceKind::Bishop) | board.pieces_by_kind(PieceKind::Queen);
+    let rooks = board.pieces_by_kind(PieceKind::Rook) | board.pieces_by_kind(PieceKind::Queen);
+    let kings = board.pieces_by_kind(PieceKind::King) & friendly;
+
+    let pieces = board.pieces_by_kind(PieceKind::Pawn) & friendly;
+    pawn_moves(board, pieces, !friendly, &mut moves);
+
+    let pieces = board.pieces_by_kind(PieceKind::Knight) & friendly;
+    add_moves(
+        board,
+        pieces,
+        !friendly,
+        Bitboard::knight_attacks,
+        &mut moves,
+    );
+
+    add_moves(
+        board,
+        bishops & friendly,
+        !friendly,
+        |from| Bitboard::bishop_attacks(from, all),
+        &mut moves,
+    );
+
+    add_moves(
+        board,
+        rooks & friendly,
+        !friendly,
+        |from| Bitboard::rook_attacks(from, all),
+        &mut moves,
+    );
+
+    add_moves(board, kings, !friendly, Bitboard::king_attacks, &mut moves);
+
+    if let Some(king_square) = kings.first() {
+        let (attacks, _, _) = calculate_attacks(board, !us);
+        castling_moves(board, king_square, attacks, Bitboard::FULL, &mut moves);
+    }
+
+    moves
+}
+
+/// Generates the legal moves of pieces on `from_mask` to squares on `to_mask`. With
+/// `first_only` it may stop as soon as a move is found.
+fn generate_masked_moves(
+    board: &Board,
+    from_mask: Bitboard,
+    to_mask: Bitboard,
+    first_only: bool,
+) -> Movelist {
+    let mut moves = Movelist::new();
+    let done = |moves: &Movelist| first_only && !moves.is_empty();
+
     let us = board.color_to_move();
     let them = !us;
 
     let friendly = board.pieces_by_color(us);
+    let movable = friendly & from_mask;
     let enemy = board.pieces_by_color(them);
     let all: Bitboard = board.pieces();
 
@@ -440,9 +550,9 @@ pub fn generate_moves(board: &Board) -> Movelist {
         // Only king evasions
         add_moves(
             board,
-            friendly & king_square,
-            !attacks_through_king & !friendly,
-            |from| Bitboard::king_attacks(from),
+            movable & king_square,
+            !attacks_through_king & !friendly & to_mask,
+            Bitboard::king_attacks,
             &mut moves,
         );
         return moves;
@@ -463,28 +573,25 @@ pub fn generate_moves(board: &Board) -> Movelist {
         pawn_allowed_squares |= allowed_squares;
 
         // En passantable checker
-        match board.en_passant_square() {
-            Some(ep) => {
-                let pawn = Bitboard::new(ep).shift(them.up());
-                if (pawn & checking_pieces).is_non_empty() {
-                    pawn_allowed_squares |= ep;
-                }
+        if let Some(ep) = board.en_passant_square() {
+            let pawn = Bitboard::new(ep).shift(them.up());
+            if (pawn & checking_pieces).is_non_empty() {
+                pawn_allowed_squares |= ep;
             }
-            None => (),
         }
     } else {
         // Else pieces can move anywhere except capture own pieces
         allowed_squares = !friendly;
         pawn_allowed_squares = allowed_squares;
 
-        match board.en_passant_square() {
-            Some(ep) => {
-                pawn_allowed_squares |= ep;
-            }
-            None => (),
+        if let Some(ep) = board.en_passant_square() {
+            pawn_allowed_squares |= ep;
         }
     };
 
+    allowed_squares &= to_mask;
+    pawn_allowed_squares &= to_mask;
+
     // Check pinned pieces
     let mut pinned_pieces = Bitboard::EMPTY;
 
@@ -509,7 +616,9 @@ pub fn generate_moves(board: &Board) -> Movelist {
                 // Piece can only move between the pinner and king, or capture it.
                 let allowed = between | square;
 
-                pinned_moves(board, pinned, allowed_squares & allowed, &mut moves);
+                if (pinned & movable).is_non_empty() {
+                    pinned_moves(board, pinned, allowed_squares & allowed, &mut moves);
+                }
             }
 
             // Possible en passantable enemy pawn
@@ -524,7 +633,13 @@ pub fn generate_moves(board: &Board) -> Movelist {
             }
 
             // Case where 2 pawns, ours and enemys are between our king and an enemy slider -> can't en passant
-            if num_pinned == 2 && (pinned & pawn).is_non_empty() && (between & ep_square).is_empty()
+            let capturers = (pawn.shift(Direction::E) | pawn.shift(Direction::W))
+                & board.pieces_by_kind(PieceKind::Pawn)
+                & friendly;
+            if num_pinned == 2
+                && (pinned & pawn).is_non_empty()
+                && (pinned & capturers).is_non_empty()
+                && (between & ep_square).is_empty()
             {
                 pawn_allowed_squares &= !Bitboard::new(ep_square);
             }
@@ -544,25 +659,37 @@ pub fn generate_moves(board: &Board) -> Movelist {
                 // Piece can only move between the pinner and king, or capture it.
                 let allowed = between | square;
 
-                pinned_moves(board, pinned, allowed_squares & allowed, &mut moves);
+                if (pinned & movable).is_non_empty() {
+                    pinned_moves(board, pinned, allowed_squares & allowed, &mut moves);
+                }
             }
         }
     }
 
+    if done(&moves) {
+        return moves;
+    }
+
     // Moves for remaining pieces
-    let pieces = board.pieces_by_kind(PieceKind::Pawn) & friendly & !pinned_pieces;
+    let pieces = board.pieces_by_kind(PieceKind::Pawn) & movable & !pinned_pieces;
     pawn_moves(board, pieces, pawn_allowed_squares, &mut moves);
+    if done(&moves) {
+        return moves;
+    }
 
-    let pieces = knights & friendly & !pinned_pieces;
+    let pieces = knights & movable & !pinned_pieces;
     add_moves(
         board,
         pieces,
         allowed_squares,
-        |from| Bitboard::knight_attacks(from),
+        Bitboard::knight_attacks,
         &mut moves,
     );
+    if done(&moves) {
+        return moves;
+    }
 
-    let pieces = (bishops | queens) & friendly & !pinned_pieces;
+    let pieces = (bishops | queens) & movable & !pinned_pieces;
     add_moves(
         board,
         pieces,
@@ -570,8 +697,11 @@ pub fn generate_moves(board: &Board) -> Movelist {
         |from| Bitboard::bishop_attacks(from, all),
         &mut moves,
     );
+    if done(&moves) {
+        return moves;
+    }
 
-    let pieces = (rooks | queens) & friendly & !pinned_pieces;
+    let pieces = (rooks | queens) & movable & !pinned_pieces;
     add_moves(
         board,
         pieces,
@@ -579,48 +709,141 @@ pub fn generate_moves(board: &Board) -> Movelist {
         |from| Bitboard::rook_attacks(from, all),
         &mut moves,
     );
+    if done(&moves) {
+        return moves;
+    }
 
-    let pieces = friendly & king_square;
+    let pieces = movable & king_square;
     add_moves(
         board,
         pieces,
-        !attacks_through_king & !friendly,
-        |from| Bitboard::king_attacks(from),
+        !attacks_through_king & !friendly & to_mask,
+        Bitboard::king_attacks,
         &mut moves,
     );
 
-    // Castlings
-    let (king_travel, middle_squares) = match us {
-        Color::White => (Bitboard::from_u64(28), Bitboard::from_u64(14)),
-        Color::Black => (Bitboard::from_u64(28 << 56), Bitboard::from_u64(14 << 56)),
-    };
+    if pieces.is_non_empty() {
+        castling_moves(board, king_square, attacks, to_mask, &mut moves);
+    }
 
-    let can_castle = board.has_castling_rights(CastlingRights::Queenside(us))
-        && ((king_travel & attacks) | (middle_squares & all)).is_empty();
+    moves
+}
 
-    if can_castle {
-        moves.push(Move::new(
-            king_square,
-            Square::from_index(king_square.to_index() - 2),
-            MoveKind::Castling,
-        ));
-    }
+pub fn generate_moves(board: &Board) -> Movelist {
+    generate_masked_moves(board, Bitboard::FULL, Bitboard::FULL, false)
+}
 
-    let (king_travel, middle_squares) = match us {
-        Color::White => (Bitboard::from_u64(112), Bitboard::from_u64(96)),
-        Color::Black => (Bitboard::from_u64(112 << 56), Bitboard::from_u64(96 << 56)),
-    };
+/// Generates the legal moves of the piece on `square`.
+/// # Example
+/// ```
+/// # use chess::{board::Board, moves::generate_moves_from, square::Square};
+/// let board = Board::default();
+/// assert_eq!(generate_moves_from(&board, Square::E2).count(), 2);
+/// assert_eq!(generate_moves_from(&board, Square::G1).count(), 2);
+/// assert!(generate_moves_from(&board, Square::E7).is_empty());
+/// ```
+pub fn generate_moves_from(board: &Board, square: Square) -> Movelist {
+    generate_masked_moves(board, Bitboard::new(square), Bitboard::FULL, false)
+}
 
-    let can_castle = board.has_castling_rights(CastlingRights::Kingside(us))
-        && ((king_travel & attacks) | (middle_squares & all)).is_empty();
+/// Generates the legal moves ending on `square`.
+pub fn generate_moves_to(board: &Board, square: Square) -> Movelist {
+    generate_masked_moves(board, Bitboard::FULL, Bitboard::new(square), false)
+}
 
-    if can_castle {
-        moves.push(Move::new(
-            king_square,
-            Square::from_index(king_square.to_index() + 2),
-            MoveKind::Castling,
-        ));
+/// Generates the legal moves of pieces of `kind`.
+pub fn generate_moves_for(board: &Board, kind: PieceKind) -> Movelist {
+    generate_masked_moves(board, board.pieces_by_kind(kind), Bitboard::FULL, false)
+}
+
+/// Returns true if the side to move has a legal move, stopping at the first one found.
+pub(crate) fn has_legal_moves(board: &Board) -> bool {
+    !generate_masked_moves(board, Bitboard::FULL, Bitboard::FULL, true).is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::Rng;
+
+    fn indices(moves: &Movelist) -> Vec<u16> {
+        let mut indices: Vec<u16> = moves.into_iter().map(|m| m.to_index()).collect();
+        indices.sort();
+        indices
+    }
+
+    #[test]
+    fn masked_generation() {
+        let fens = [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "8/8/8/2k5/3Pp3/8/8/4K2R b K d3 0 1",
+            "7k/5Q2/6K1/8/8/8/8/8 b - - 0 1",
+        ];
+        let mut rng = Rng::new(44);
+
+        for fen in fens {
+            let mut board = Board::from_fen(fen).unwrap();
+
+            for _ in 0..30 {
+                let moves = generate_moves(&board);
+                assert_eq!(board.has_legal_moves(), !moves.is_empty());
+
+                let filter = |f: &dyn Fn(Move) -> bool| {
+                    let mut list = Movelist::new();
+                    for mv in moves.into_iter().filter(|&m| f(m)) {
+                        list.push(mv);
+                    }
+                    indices(&list)
+                };
+
+                for i in 0..64 {
+                    let square = Square::from_index(i);
+                    assert_eq!(
+                        indices(&generate_moves_from(&board, square)),
+                        filter(&|m| m.from() == square)
+                    );
+                    assert_eq!(
+                        indices(&generate_moves_to(&board, square)),
+                        filter(&|m| m.to() == square)
+                    );
+                }
+
+                for kind in [
+                    PieceKind::Pawn,
+                    PieceKind::Knight,
+                    PieceKind::Bishop,
+                    PieceKind::Rook,
+                    PieceKind::Queen,
+                    PieceKind::King,
+                ] {
+                    assert_eq!(
+                        indices(&generate_moves_for(&board, kind)),
+                        filter(&|m| board.at(m.from()).unwrap().kind() == kind)
+                    );
+                }
+
+                if moves.is_empty() {
+                    break;
+                }
+                board = board.do_move(moves[rng.below(moves.count() as u64) as usize]);
+            }
+        }
     }
 
-    moves
+    #[test]
+    fn en_passant_pins() {
+        let moves = |fen| -> Vec<String> {
+            let board = Board::from_fen(fen).unwrap();
+            generate_moves(&board).into_iter().map(|m| m.to_string()).collect()
+        };
+
+        // The knight and the en passant pawn both stand between our king and the bishop,
+        // but the capturing pawn does not, so the capture is legal
+        assert!(moves("4k2b/8/8/3Pp3/8/8/1N6/K7 w - e6 0 1").contains(&"d5e6".to_owned()));
+
+        // Capturing would remove both pawns from the rank between king and rook
+        assert!(!moves("7k/8/8/KPp4r/8/8/8/8 w - c6 0 1").contains(&"b5c6".to_owned()));
+    }
 }
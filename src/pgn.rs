@@ -0,0 +1,499 @@
+use std::{
+    fmt,
+    io::{self, BufRead},
+};
+
+use crate::{
+    board::Board,
+    color::Color,
+    moves::{generate_moves, Move, MoveKind},
+    piece::PieceKind,
+    square::{File, Rank, Square},
+};
+
+#[derive(Debug)]
+pub struct ParsePgnError {
+    msg: String,
+}
+
+impl ParsePgnError {
+    pub fn new<S: AsRef<str>>(msg: S) -> Self {
+        Self {
+            msg: msg.as_ref().to_owned(),
+        }
+    }
+}
+
+impl fmt::Display for ParsePgnError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Error parsing PGN: {}.", self.msg)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GameResult {
+    WhiteWins,
+    BlackWins,
+    Draw,
+}
+
+impl GameResult {
+    /// The result of a game won by `color`.
+    pub fn win_for(color: Color) -> Self {
+        match color {
+            Color::White => GameResult::WhiteWins,
+            Color::Black => GameResult::BlackWins,
+        }
+    }
+
+    fn parse(token: &str) -> Option<Self> {
+        match token {
+            "1-0" => Some(GameResult::WhiteWins),
+            "0-1" => Some(GameResult::BlackWins),
+            "1/2-1/2" => Some(GameResult::Draw),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for GameResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GameResult::WhiteWins => write!(f, "1-0"),
+            GameResult::BlackWins => write!(f, "0-1"),
+            GameResult::Draw => write!(f, "1/2-1/2"),
+        }
+    }
+}
+
+/// A game read from a PGN file. Moves are kept in SAN until the game is replayed.
+#[derive(Debug, Clone, Default)]
+pub struct PgnGame {
+    pub tags: Vec<(String, String)>,
+    pub moves: Vec<String>,
+    pub result: Option<GameResult>,
+}
+
+impl PgnGame {
+    /// Returns the value of tag `name`.
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Returns the starting position, which is given by the `FEN` tag if present.
+    pub fn start_position(&self) -> Result<Board, ParsePgnError> {
+        match self.tag("FEN") {
+            Some(fen) => Board::from_fen(fen).map_err(|e| ParsePgnError::new(e.to_string())),
+            None => Ok(Board::default()),
+        }
+    }
+
+    /// Plays through the first `max_plies` moves of the game and returns each position
+    /// together with the move played in it.
+    pub fn replay(&self, max_plies: usize) -> Result<Vec<(Board, Move)>, ParsePgnError> {
+        let mut board = self.start_position()?;
+        let mut positions = Vec::new();
+
+        for san in self.moves.iter().take(max_plies) {
+            let mv = parse_san(san, &board)?;
+            let next = board.do_move(mv);
+            positions.push((board, mv));
+            board = next;
+        }
+
+        Ok(positions)
+    }
+}
+
+/// Parses a move in Standard Algebraic Notation.
+/// # Example
+/// ```
+/// # use chess::{board::Board, pgn::parse_san};
+/// let board = Board::default();
+/// assert_eq!(parse_san("Nf3", &board).unwrap().to_string(), "g1f3");
+/// assert_eq!(parse_san("e4", &board).unwrap().to_string(), "e2e4");
+/// assert!(parse_san("e5", &board).is_err());
+/// ```
+pub fn parse_san(san: &str, board: &Board) -> Result<Move, ParsePgnError> {
+    let invalid = || ParsePgnError::new(format!("Invalid move '{}'", san));
+
+    let stripped = san.trim_end_matches(['+', '#', '!', '?']);
+
+    let castling_file = match stripped {
+        "O-O" | "0-0" => Some(File::G),
+        "O-O-O" | "0-0-0" => Some(File::C),
+        _ => None,
+    };
+
+    if let Some(file) = castling_file {
+        return generate_moves(board)
+            .into_iter()
+            .find(|mv| matches!(mv.kind(), MoveKind::Castling) && mv.to().file() == file)
+            .ok_or_else(invalid);
+    }
+
+    let mut chars: Vec<char> = stripped.chars().collect();
+
+    let kind = match chars.first() {
+        Some('N') => PieceKind::Knight,
+        Some('B') => PieceKind::Bishop,
+        Some('R') => PieceKind::Rook,
+        Some('Q') => PieceKind::Queen,
+        Some('K') => PieceKind::King,
+        _ => PieceKind::Pawn,
+    };
+    if kind != PieceKind::Pawn {
+        chars.remove(0);
+    }
+
+    // Promotion, either "e8=Q" or "e8Q"
+    let promotion = match chars.last() {
+        Some(&c) if kind == PieceKind::Pawn && c.is_ascii_uppercase() => {
+            chars.pop();
+            if chars.last() == Some(&'=') {
+                chars.pop();
+            }
+            Some(PieceKind::try_from(c.to_ascii_lowercase()).map_err(|_| invalid())?)
+        }
+        _ => None,
+    };
+
+    if chars.len() < 2 {
+        return Err(invalid());
+    }
+
+    let to_rank: Rank = chars.pop().unwrap().try_into().map_err(|_| invalid())?;
+    let to_file: File = chars.pop().unwrap().try_into().map_err(|_| invalid())?;
+    let to = Square::new(to_rank, to_file);
+
+    // Whatever remains is disambiguation and the capture marker
+    let mut from_file = None;
+    let mut from_rank = None;
+    for c in chars {
+        match c {
+            'a'..='h' => from_file = Some(File::try_from(c).map_err(|_| invalid())?),
+            '1'..='8' => from_rank = Some(Rank::try_from(c).map_err(|_| invalid())?),
+            'x' | ':' => (),
+            _ => return Err(invalid()),
+        }
+    }
+
+    let moves = generate_moves(board);
+    let mut candidates = moves.into_iter().filter(|mv| {
+        mv.to() == to
+            && !matches!(mv.kind(), MoveKind::Castling)
+            && mv.promotion_kind() == promotion
+            && board.at(mv.from()).map(|p| p.kind()) == Some(kind)
+            && from_file.is_none_or(|f| mv.from().file() == f)
+            && from_rank.is_none_or(|r| mv.from().rank() == r)
+    });
+
+    match (candidates.next(), candidates.next()) {
+        (Some(mv), None) => Ok(mv),
+        (Some(_), Some(_)) => Err(ParsePgnError::new(format!("Ambiguous move '{}'", san))),
+        _ => Err(ParsePgnError::new(format!("Illegal move '{}'", san))),
+    }
+}
+
+fn square_name(square: Square) -> String {
+    format!("{}{}", char::from(square.file()), char::from(square.rank()))
+}
+
+/// Writes a legal move in Standard Algebraic Notation.
+/// # Example
+/// ```
+/// # use chess::{board::Board, pgn::{parse_san, to_san}};
+/// let board = Board::from_fen("4k3/8/8/8/8/8/4K3/R6R w - - 0 1").unwrap();
+/// let mv = parse_san("Rad1", &board).unwrap();
+/// assert_eq!(to_san(mv, &board), "Rad1");
+/// ```
+pub fn to_san(mv: Move, board: &Board) -> String {
+    let mut san = String::new();
+    let kind = board.at(mv.from()).map(|p| p.kind());
+
+    if matches!(mv.kind(), MoveKind::Castling) {
+        san.push_str(if mv.to().file() == File::G {
+            "O-O"
+        } else {
+            "O-O-O"
+        });
+    } else if kind == Some(PieceKind::Pawn) {
+        if mv.kind().is_capture() {
+            san.push(mv.from().file().into());
+            san.push('x');
+        }
+        san.push_str(&square_name(mv.to()));
+        if let Some(promotion) = mv.promotion_kind() {
+            san.push('=');
+            san.push(char::from(promotion).to_ascii_uppercase());
+        }
+    } else {
+        if let Some(kind) = kind {
+            san.push(char::from(kind).to_ascii_uppercase());
+        }
+
+        // Other pieces of the same kind that can move to the same square
+        let others: Vec<Move> = generate_moves(board)
+            .into_iter()
+            .filter(|other| {
+                other.to() == mv.to()
+                    && other.from() != mv.from()
+                    && board.at(other.from()).map(|p| p.kind()) == kind
+            })
+            .collect();
+        if !others.is_empty() {
+            if others.iter().all(|o| o.from().file() != mv.from().file()) {
+                san.push(mv.from().file().into());
+            } else if others.iter().all(|o| o.from().rank() != mv.from().rank()) {
+                san.push(mv.from().rank().into());
+            } else {
+                san.push_str(&square_name(mv.from()));
+            }
+        }
+
+        if mv.kind().is_capture() {
+            san.push('x');
+        }
+        san.push_str(&square_name(mv.to()));
+    }
+
+    let next = board.do_move(mv);
+    if next.is_in_check() {
+        san.push(if next.has_legal_moves() { '+' } else { '#' });
+    }
+
+    san
+}
+
+/// Parses the tag pair on `line`, e.g. `[White "Carlsen, Magnus"]`.
+fn parse_tag(line: &str) -> Option<(String, String)> {
+    let inner = line.trim().strip_prefix('[')?.strip_suffix(']')?;
+    let (name, value) = inner.split_once(char::is_whitespace)?;
+    let value = value.trim().strip_prefix('"')?.strip_suffix('"')?;
+
+    Some((name.to_owned(), value.replace("\\\"", "\"")))
+}
+
+/// Strips a move number such as `12.` or `12...` from the start of `token`. Digits not
+/// followed by a dot are kept, so that results and castling written with zeros survive.
+fn strip_move_number(token: &str) -> &str {
+    let rest = token.trim_start_matches(|c: char| c.is_ascii_digit());
+    let has_number = rest.len() < token.len();
+    if !has_number || rest.starts_with('.') {
+        rest.trim_start_matches('.')
+    } else {
+        token
+    }
+}
+
+/// Splits movetext into SAN moves, skipping move numbers, comments, variations and NAGs.
+/// Returns the moves and the game termination marker, if any.
+fn parse_movetext(text: &str) -> (Vec<String>, Option<GameResult>) {
+    let mut moves = Vec::new();
+    let mut result = None;
+
+    let mut comment = false;
+    let mut line_comment = false;
+    let mut variation_depth = 0;
+    let mut token = String::new();
+
+    let mut flush = |token: &mut String, moves: &mut Vec<String>| {
+        let t = strip_move_number(token);
+        if !t.is_empty() && !t.starts_with('$') {
+            if let Some(r) = GameResult::parse(token) {
+                result = Some(r);
+            } else if token != "*" {
+                moves.push(t.to_owned());
+            }
+        }
+        token.clear();
+    };
+
+    for c in text.chars() {
+        if line_comment {
+            line_comment = c != '\n';
+            continue;
+        }
+        if comment {
+            comment = c != '}';
+            continue;
+        }
+
+        match c {
+            '{' => comment = true,
+            ';' => line_comment = true,
+            '(' => variation_depth += 1,
+            ')' => variation_depth -= 1,
+            _ if variation_depth > 0 => (),
+            c if c.is_whitespace() => flush(&mut token, &mut moves),
+            c => token.push(c),
+        }
+
+        if matches!(c, '{' | ';' | '(' | ')') {
+            flush(&mut token, &mut moves);
+        }
+    }
+    flush(&mut token, &mut moves);
+
+    (moves, result)
+}
+
+/// Reads games one by one from a PGN database.
+pub struct PgnReader<R: BufRead> {
+    lines: io::Lines<R>,
+    pending_tag: Option<String>,
+}
+
+impl<R: BufRead> PgnReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            lines: reader.lines(),
+            pending_tag: None,
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for PgnReader<R> {
+    type Item = io::Result<PgnGame>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut tags = Vec::new();
+        let mut movetext = String::new();
+        let mut in_comment = false;
+
+        if let Some(line) = self.pending_tag.take() {
+            tags.extend(parse_tag(&line));
+        }
+
+        for line in self.lines.by_ref() {
+            let line = match line {
+                Ok(line) => line,
+                Err(e) => return Some(Err(e)),
+            };
+
+            if !in_comment && line.trim_start().starts_with('[') {
+                if !movetext.trim().is_empty() {
+                    // Start of the next game
+                    self.pending_tag = Some(line);
+                    break;
+                }
+                tags.extend(parse_tag(&line));
+                continue;
+            }
+
+            for c in line.chars() {
+                match c {
+                    '{' => in_comment = true,
+                    '}' => in_comment = false,
+                    _ => (),
+                }
+            }
+
+            movetext.push_str(&line);
+            movetext.push('\n');
+        }
+
+        if tags.is_empty() && movetext.trim().is_empty() {
+            return None;
+        }
+
+        let (moves, result) = parse_movetext(&movetext);
+
+        Some(Ok(PgnGame {
+            tags,
+            moves,
+            result,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PGN: &str = r#"[Event "Test"]
+[White "A"]
+[Black "B"]
+[Result "1-0"]
+
+1. e4 e5 2. Nf3 {best by test} Nc6 (2... d6 3. d4) 3. Bb5 a6 $1 4. Ba4 Nf6 5. O-O
+Be7 1-0
+
+[Event "Second"]
+[FEN "4k3/P7/8/8/8/8/8/4K3 w - - 0 1"]
+[Result "*"]
+
+1. a8=Q+ Kd7 *
+"#;
+
+    #[test]
+    fn read_games() {
+        let games: Vec<PgnGame> = PgnReader::new(PGN.as_bytes())
+            .collect::<Result<_, _>>()
+            .unwrap();
+
+        assert_eq!(games.len(), 2);
+        assert_eq!(games[0].tag("White"), Some("A"));
+        assert_eq!(games[0].result, Some(GameResult::WhiteWins));
+        assert_eq!(games[0].moves.len(), 10);
+        assert_eq!(games[0].moves[9], "Be7");
+
+        let replay = games[0].replay(100).unwrap();
+        assert_eq!(replay[8].1.to_string(), "e1g1");
+
+        assert_eq!(games[1].result, None);
+        let replay = games[1].replay(100).unwrap();
+        assert_eq!(replay[0].1.to_string(), "a7a8q");
+    }
+
+    #[test]
+    fn zero_castling() {
+        let pgn = "1. e4 e5 2. Nf3 Nc6 3. Bc4 Bc5 4. 0-0 d6 5. d3 Be6 6. Nc3 Qd7 7. a3 0-0-0 1-0";
+        let game = PgnReader::new(pgn.as_bytes()).next().unwrap().unwrap();
+
+        assert_eq!(game.moves[6], "0-0");
+        assert_eq!(game.moves[13], "0-0-0");
+        assert_eq!(game.result, Some(GameResult::WhiteWins));
+
+        let replay = game.replay(100).unwrap();
+        assert_eq!(replay[6].1.to_string(), "e1g1");
+        assert_eq!(replay[13].1.to_string(), "e8c8");
+
+        assert_eq!(strip_move_number("12...Nf6"), "Nf6");
+        assert_eq!(strip_move_number("..."), "");
+        assert_eq!(strip_move_number("1/2-1/2"), "1/2-1/2");
+    }
+
+    #[test]
+    fn write_san() {
+        let games: Vec<PgnGame> = PgnReader::new(PGN.as_bytes())
+            .collect::<Result<_, _>>()
+            .unwrap();
+
+        // Writing the moves back gives the original SAN
+        for game in &games {
+            for ((board, mv), san) in game.replay(100).unwrap().iter().zip(&game.moves) {
+                assert_eq!(&to_san(*mv, board), san);
+            }
+        }
+
+        let board = Board::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        assert_eq!(to_san(parse_san("Ra8", &board).unwrap(), &board), "Ra8#");
+        let board = Board::from_fen("4k3/8/8/8/8/1N6/8/1N2KN2 w - - 0 1").unwrap();
+        assert_eq!(to_san(parse_san("Nb1d2", &board).unwrap(), &board), "Nb1d2");
+    }
+
+    #[test]
+    fn san_disambiguation() {
+        let board = Board::from_fen("4k3/8/8/8/8/8/4K3/R6R w - - 0 1").unwrap();
+        assert!(parse_san("Rd1", &board).is_err());
+        assert_eq!(parse_san("Rad1", &board).unwrap().to_string(), "a1d1");
+        assert_eq!(parse_san("Rhf1", &board).unwrap().to_string(), "h1f1");
+
+        let board = Board::from_fen("4k3/8/8/3p4/4P3/8/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(parse_san("exd5", &board).unwrap().to_string(), "e4d5");
+    }
+}
@@ -1,10 +1,26 @@
+use std::{
+    cmp::Reverse,
+    collections::HashMap,
+    sync::atomic::{AtomicBool, AtomicU64, Ordering},
+    thread,
+};
+
 use crate::{
     board::Board,
     eval::{evaluate, Score, DRAW, INF, MATE},
     moves::{generate_moves, Move},
-    uci::SearchParams,
+    piece::Piece,
+    tt::{Bound, TranspositionTable},
 };
 
+/// Statistics collected during search.
+#[derive(Debug, Clone, Default)]
+pub struct SearchStats {
+    pub nodes: u64,
+    /// Deepest ply reached, including quiescence search
+    pub seldepth: usize,
+}
+
 pub fn negamax(board: &Board, depth: usize, depth_left: usize) -> Score {
     let moves = generate_moves(board);
 
@@ -40,75 +56,726 @@ pub fn negamax(board: &Board, depth: usize, depth_left: usize) -> Score {
     max
 }
 
-pub fn negamax_alphabeta(
-    board: &Board,
-    mut alpha: Score,
-    beta: Score,
-    depth: usize,
-    depth_left: usize,
-) -> Score {
-    let moves = generate_moves(board);
+/// How many nodes are searched between checks for aborting the search.
+const STOP_CHECK_INTERVAL: u64 = 1024;
 
-    if depth_left == 0 {
-        if moves.is_empty() {
-            if board.is_in_check() {
-                return -MATE + (depth as i32);
-            } else {
-                return DRAW;
+/// Iterative deepening stops at this depth even when there is time left.
+pub const MAX_DEPTH: usize = 64;
+
+/// Plies from the root after which only quiescence search is done.
+const MAX_PLY: usize = 128;
+
+/// History scores are capped below the scores of captures and killers.
+const HISTORY_MAX: i32 = 100_000;
+
+/// Callback deciding whether to abort the search, given statistics of the whole search.
+pub type StopFn<'a> = dyn Fn(&SearchStats) -> bool + Sync + 'a;
+
+#[derive(Debug, Clone)]
+pub struct SearchOptions {
+    pub max_depth: usize,
+    /// Number of principal variations to search for.
+    pub multipv: usize,
+    /// Number of threads in `search_parallel()`.
+    pub threads: usize,
+    /// Only search for a mate in this many moves. The search ends as soon as one is found,
+    /// or when the depth needed for it has been searched without finding one.
+    pub mate: Option<usize>,
+    /// Restricts the root to these moves. All moves are searched if empty.
+    pub searchmoves: Vec<Move>,
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        Self {
+            max_depth: MAX_DEPTH,
+            multipv: 1,
+            threads: 1,
+            mate: None,
+            searchmoves: Vec::new(),
+        }
+    }
+}
+
+/// A principal variation and its score.
+#[derive(Debug, Clone)]
+pub struct PvLine {
+    pub score: Score,
+    pub pv: Vec<Move>,
+}
+
+/// Outcome of the deepest completed iteration. `lines` are ranked best first.
+#[derive(Debug, Clone, Default)]
+pub struct SearchResult {
+    pub depth: usize,
+    pub lines: Vec<PvLine>,
+    pub stats: SearchStats,
+}
+
+impl SearchResult {
+    pub fn best_move(&self) -> Option<Move> {
+        self.lines.first().map(|line| line.pv[0])
+    }
+
+    /// The expected reply to the best move.
+    pub fn ponder_move(&self) -> Option<Move> {
+        self.lines.first().and_then(|line| line.pv.get(1).copied())
+    }
+
+    /// Number of moves to a forced mate by the side to move, if the best line is one.
+    /// # Example
+    /// ```
+    /// # use chess::{board::Board, search::{search_parallel, SearchOptions}, tt::TranspositionTable};
+    /// let board = Board::from_fen("k7/8/2K5/8/8/8/8/7R w - - 0 1").unwrap();
+    /// let tt = TranspositionTable::new(1);
+    /// let options = SearchOptions { mate: Some(1), ..Default::default() };
+    /// let result = search_parallel(&board, &options, &tt, &|_| false, |_| ());
+    /// assert_eq!((result.depth, result.mate_in()), (1, None));
+    ///
+    /// let options = SearchOptions { mate: Some(2), ..Default::default() };
+    /// let result = search_parallel(&board, &options, &tt, &|_| false, |_| ());
+    /// assert_eq!((result.depth, result.mate_in()), (3, Some(2)));
+    /// ```
+    pub fn mate_in(&self) -> Option<usize> {
+        match self.lines.first() {
+            Some(line) if line.score >= MATE - MAX_PLY as Score => {
+                Some((MATE - line.score + 1) as usize / 2)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Alpha-beta searcher. The search can be aborted through the `should_stop` callback,
+/// which is polled every `STOP_CHECK_INTERVAL` nodes.
+pub struct Searcher<'a> {
+    pub stats: SearchStats,
+    tt: &'a TranspositionTable,
+    should_stop: &'a StopFn<'a>,
+    /// Node counter of all threads in a parallel search
+    shared_nodes: Option<&'a AtomicU64>,
+    /// Helper threads of a parallel search vary the root move order and starting depth
+    thread_id: usize,
+    killers: [[Move; 2]; MAX_PLY],
+    /// Bonus for quiet moves that caused a beta cutoff, indexed by from and to square
+    history: Box<[[i32; 64]; 64]>,
+    aborted: bool,
+}
+
+impl<'a> Searcher<'a> {
+    pub fn new(tt: &'a TranspositionTable, should_stop: &'a StopFn<'a>) -> Self {
+        Self {
+            stats: SearchStats::default(),
+            tt,
+            should_stop,
+            shared_nodes: None,
+            thread_id: 0,
+            killers: [[Move::null(); 2]; MAX_PLY],
+            history: Box::new([[0; 64]; 64]),
+            aborted: false,
+        }
+    }
+
+    /// Returns true if the search was stopped. Scores returned after that are meaningless.
+    pub fn aborted(&self) -> bool {
+        self.aborted
+    }
+
+    /// Statistics of the whole search, including other threads in a parallel search.
+    fn current_stats(&self) -> SearchStats {
+        let nodes = match self.shared_nodes {
+            Some(nodes) => nodes.load(Ordering::Relaxed) + self.stats.nodes % STOP_CHECK_INTERVAL,
+            None => self.stats.nodes,
+        };
+
+        SearchStats {
+            nodes,
+            seldepth: self.stats.seldepth,
+        }
+    }
+
+    /// Iterative deepening search. `on_iteration` is called after every completed depth.
+    /// # Example
+    /// ```
+    /// # use chess::{board::Board, search::{SearchOptions, Searcher}, tt::TranspositionTable};
+    /// let board = Board::default();
+    /// let tt = TranspositionTable::new(1);
+    /// let options = SearchOptions { max_depth: 2, multipv: 3, ..Default::default() };
+    /// let result = Searcher::new(&tt, &|_| false).search(&board, &options, |_| ());
+    /// assert_eq!(result.depth, 2);
+    /// assert_eq!(result.lines.len(), 3);
+    /// assert!(result.lines[0].score >= result.lines[2].score);
+    /// ```
+    pub fn search(
+        &mut self,
+        board: &Board,
+        options: &SearchOptions,
+        mut on_iteration: impl FnMut(&SearchResult),
+    ) -> SearchResult {
+        let mut root_moves: Vec<Move> = generate_moves(board)
+            .into_iter()
+            .filter(|mv| options.searchmoves.is_empty() || options.searchmoves.contains(mv))
+            .collect();
+        let mut result = SearchResult::default();
+
+        if !root_moves.is_empty() {
+            let n = self.thread_id % root_moves.len();
+            root_moves.rotate_left(n);
+        }
+
+        // Mate in n moves is found at depth 2n - 1
+        let max_depth = match options.mate {
+            Some(moves) => options.max_depth.min(2 * moves.max(1) - 1),
+            None => options.max_depth,
+        };
+        let first_depth = (1 + self.thread_id % 2).min(max_depth);
+
+        for depth in first_depth..=max_depth {
+            let mut lines: Vec<PvLine> = Vec::new();
+
+            for _ in 0..options.multipv.min(root_moves.len()) {
+                let excluded: Vec<Move> = lines.iter().map(|line| line.pv[0]).collect();
+                let line = self.search_root(board, &root_moves, depth, &excluded);
+                lines.extend(line);
+                if self.aborted {
+                    break;
+                }
+            }
+
+            // A partial iteration is only better than nothing
+            if !self.aborted || result.lines.is_empty() {
+                result.lines = lines;
+            }
+            result.stats = self.current_stats();
+
+            if self.aborted || result.lines.is_empty() {
+                break;
+            }
+
+            result.depth = depth;
+            on_iteration(&result);
+
+            // A longer mate can come from the table of an earlier search
+            let mate_found = |n| result.mate_in().is_some_and(|m| m <= n);
+            if options.mate.is_some_and(mate_found) {
+                break;
+            }
+
+            // Search the best lines first in the next iteration
+            for line in result.lines.iter().rev() {
+                let first = line.pv[0].to_index();
+                if let Some(i) = root_moves.iter().position(|mv| mv.to_index() == first) {
+                    root_moves[..=i].rotate_right(1);
+                }
             }
+
+            // Don't start another iteration if time already ran out
+            if (self.should_stop)(&self.current_stats()) {
+                self.aborted = true;
+                break;
+            }
+        }
+
+        result
+    }
+
+    /// Searches the root moves that are not `excluded` and returns the best line. When
+    /// aborted, the best line found so far is returned.
+    fn search_root(
+        &mut self,
+        board: &Board,
+        moves: &[Move],
+        depth: usize,
+        excluded: &[Move],
+    ) -> Option<PvLine> {
+        let mut best: Option<PvLine> = None;
+        let mut child_pv = Vec::new();
+        let key = board.polyglot_key();
+
+        for &mv in moves {
+            if excluded.contains(&mv) {
+                continue;
+            }
+
+            let next = board.do_move(mv);
+            let alpha = best.as_ref().map_or(-INF, |line| line.score);
+            let score = -self.alphabeta(
+                &next,
+                board.polyglot_key_after(key, &next),
+                -INF,
+                -alpha,
+                1,
+                depth - 1,
+                &mut child_pv,
+            );
+            if self.aborted {
+                break;
+            }
+
+            if best.is_none() || score > alpha {
+                let mut pv = vec![mv];
+                pv.extend_from_slice(&child_pv);
+                best = Some(PvLine { score, pv });
+            }
+        }
+
+        best
+    }
+
+    fn visit(&mut self, depth: usize) -> bool {
+        self.stats.nodes += 1;
+        self.stats.seldepth = self.stats.seldepth.max(depth);
+
+        if !self.aborted && self.stats.nodes.is_multiple_of(STOP_CHECK_INTERVAL) {
+            if let Some(nodes) = self.shared_nodes {
+                nodes.fetch_add(STOP_CHECK_INTERVAL, Ordering::Relaxed);
+            }
+            self.aborted = (self.should_stop)(&self.current_stats());
+        }
+
+        self.aborted
+    }
+
+    /// Move ordering: hash move, captures by MVV-LVA, killers and then quiet moves by history.
+    fn order_score(&self, board: &Board, mv: Move, hash_move: Option<Move>, ply: usize) -> i32 {
+        let kind_value = |sq| board.at(sq).map_or(0, |p: Piece| p.kind() as i32);
+
+        if hash_move.is_some_and(|m| m == mv) {
+            1_000_000
+        } else if mv.kind().is_capture() {
+            500_000 + 10 * kind_value(mv.to()) - kind_value(mv.from())
+        } else if self.killers[ply][0] == mv {
+            400_000
+        } else if self.killers[ply][1] == mv {
+            300_000
         } else {
-            // return evaluate(board);
-            return quiescence_search(board, alpha, beta);
+            self.history[mv.from().to_index()][mv.to().to_index()]
         }
-    } else {
+    }
+
+    fn update_quiet(&mut self, mv: Move, ply: usize, depth_left: usize) {
+        if self.killers[ply][0] != mv {
+            self.killers[ply][1] = self.killers[ply][0];
+            self.killers[ply][0] = mv;
+        }
+
+        let history = &mut self.history[mv.from().to_index()][mv.to().to_index()];
+        *history = (*history + (depth_left * depth_left) as i32).min(HISTORY_MAX);
+    }
+
+    /// Alpha-beta search. The principal variation is stored in `pv` when a move raises alpha.
+    /// `key` is the Polyglot key of `board`, updated incrementally along the search.
+    #[allow(clippy::too_many_arguments)]
+    pub fn alphabeta(
+        &mut self,
+        board: &Board,
+        key: u64,
+        mut alpha: Score,
+        beta: Score,
+        depth: usize,
+        depth_left: usize,
+        pv: &mut Vec<Move>,
+    ) -> Score {
+        pv.clear();
+        if self.visit(depth) {
+            return alpha;
+        }
+
+        let moves = generate_moves(board);
+
         if moves.is_empty() {
             if board.is_in_check() {
                 return -MATE + (depth as i32);
             } else {
                 return DRAW;
             }
+        } else if depth_left == 0 || depth >= MAX_PLY {
+            return self.quiescence(board, alpha, beta, depth);
+        }
+
+        debug_assert_eq!(key, board.polyglot_key());
+        let entry = self.tt.probe(key);
+
+        if let Some(entry) = entry.filter(|e| e.depth >= depth_left) {
+            let score = entry.score(depth);
+            match entry.bound {
+                Bound::Exact => {
+                    if score > alpha && score < beta {
+                        pv.extend(moves.into_iter().find(|&m| m == entry.mv));
+                    }
+                    return score.clamp(alpha, beta);
+                }
+                Bound::Lower if score >= beta => return beta,
+                Bound::Upper if score <= alpha => return alpha,
+                _ => (),
+            }
         }
+
+        let hash_move = entry.map(|e| e.mv);
+        let mut ordered: Vec<(i32, Move)> = moves
+            .into_iter()
+            .map(|mv| (self.order_score(board, mv, hash_move, depth), mv))
+            .collect();
+        ordered.sort_unstable_by_key(|&(score, _)| Reverse(score));
+
+        let mut child_pv = Vec::new();
+        let mut best_move = Move::null();
+        let mut bound = Bound::Upper;
+
+        for (_, mv) in ordered {
+            let next = board.do_move(mv);
+            let score = -self.alphabeta(
+                &next,
+                board.polyglot_key_after(key, &next),
+                -beta,
+                -alpha,
+                depth + 1,
+                depth_left - 1,
+                &mut child_pv,
+            );
+            if self.aborted {
+                return alpha;
+            }
+
+            if score >= beta {
+                if !mv.kind().is_capture() {
+                    self.update_quiet(mv, depth, depth_left);
+                }
+                self.tt
+                    .store(key, mv, beta, Bound::Lower, depth_left, depth);
+                return beta;
+            }
+
+            if score > alpha {
+                alpha = score;
+                best_move = mv;
+                bound = Bound::Exact;
+
+                pv.clear();
+                pv.push(mv);
+                pv.extend_from_slice(&child_pv);
+            }
+        }
+
+        self.tt
+            .store(key, best_move, alpha, bound, depth_left, depth);
+
+        alpha
     }
 
-    for mv in &moves {
-        let score =
-            -negamax_alphabeta(&board.do_move(mv), -beta, -alpha, depth + 1, depth_left - 1);
-        if score >= beta {
+    pub fn quiescence(
+        &mut self,
+        board: &Board,
+        mut alpha: Score,
+        beta: Score,
+        depth: usize,
+    ) -> Score {
+        if self.visit(depth) {
+            return alpha;
+        }
+
+        let standing_pat = evaluate(board);
+
+        if standing_pat >= beta {
             return beta;
         }
+        if alpha < standing_pat {
+            alpha = standing_pat;
+        }
+
+        let moves = generate_moves(board);
 
-        if score > alpha {
-            alpha = score;
+        for mv in &moves {
+            if mv.kind().is_capture() {
+                let score = -self.quiescence(&board.do_move(mv), -beta, -alpha, depth + 1);
+
+                if score >= beta {
+                    return beta;
+                }
+                if score > alpha {
+                    alpha = score;
+                }
+            }
         }
+
+        alpha
     }
+}
+
+/// Lazy SMP search. `options.threads` searchers share the transposition table, with helper
+/// threads starting at different depths and root moves. The main thread reports iterations
+/// through `on_iteration`, and the threads vote on the final best move.
+/// # Example
+/// ```
+/// # use chess::{board::Board, search::{search_parallel, SearchOptions}, tt::TranspositionTable};
+/// let board = Board::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+/// let tt = TranspositionTable::new(1);
+/// let options = SearchOptions { max_depth: 3, threads: 4, ..Default::default() };
+/// let result = search_parallel(&board, &options, &tt, &|_| false, |_| ());
+/// assert_eq!(result.best_move().unwrap().to_string(), "a1a8");
+/// ```
+pub fn search_parallel(
+    board: &Board,
+    options: &SearchOptions,
+    tt: &TranspositionTable,
+    should_stop: &StopFn<'_>,
+    on_iteration: impl FnMut(&SearchResult),
+) -> SearchResult {
+    tt.new_search();
+
+    let nodes = AtomicU64::new(0);
+    let done = AtomicBool::new(false);
+    let helper_stop = |stats: &SearchStats| done.load(Ordering::Relaxed) || should_stop(stats);
+
+    let searcher = |id, should_stop| Searcher {
+        shared_nodes: Some(&nodes),
+        thread_id: id,
+        ..Searcher::new(tt, should_stop)
+    };
+
+    let mut results = thread::scope(|s| {
+        let helpers: Vec<_> = (1..options.threads)
+            .map(|id| {
+                let mut helper = searcher(id, &helper_stop);
+                let options = SearchOptions {
+                    multipv: 1,
+                    ..options.clone()
+                };
+                s.spawn(move || {
+                    let result = helper.search(board, &options, |_| ());
+                    (result, helper.stats)
+                })
+            })
+            .collect();
+
+        let mut main = searcher(0, should_stop);
+        let result = main.search(board, options, on_iteration);
+        done.store(true, Ordering::Relaxed);
 
-    return alpha;
+        let mut results = vec![(result, main.stats)];
+        results.extend(helpers.into_iter().map(|h| h.join().unwrap()));
+        results
+    });
+
+    let stats = merge_stats(results.iter().map(|(_, stats)| stats));
+
+    let mut result = if options.multipv == 1 {
+        vote(results.into_iter().map(|(result, _)| result).collect())
+    } else {
+        results.swap_remove(0).0
+    };
+    result.stats = stats;
+
+    result
+}
+
+/// Combines the statistics of all threads: nodes add up, seldepth is the deepest of any thread.
+fn merge_stats<'a>(stats: impl IntoIterator<Item = &'a SearchStats>) -> SearchStats {
+    stats
+        .into_iter()
+        .fold(SearchStats::default(), |total, stats| SearchStats {
+            nodes: total.nodes + stats.nodes,
+            seldepth: total.seldepth.max(stats.seldepth),
+        })
 }
 
-pub fn quiescence_search(board: &Board, mut alpha: Score, beta: Score) -> Score {
-    let standing_pat = evaluate(board);
+/// Picks the result of the thread whose best move got the most votes. Votes are weighted by
+/// depth and by how much better the score is than the worst one among the threads. The main
+/// thread comes first in `results`.
+fn vote(results: Vec<SearchResult>) -> SearchResult {
+    // Helpers that did not complete an iteration don't vote
+    let results: Vec<SearchResult> = results
+        .into_iter()
+        .enumerate()
+        .filter(|(i, result)| !result.lines.is_empty() && (*i == 0 || result.depth > 0))
+        .map(|(_, result)| result)
+        .collect();
 
-    if standing_pat >= beta {
-        return beta;
+    let min_score = results.iter().map(|r| r.lines[0].score).min().unwrap_or(0);
+
+    let mut votes: HashMap<Move, i64> = HashMap::new();
+    for result in &results {
+        let weight = (result.lines[0].score - min_score + 14) as i64 * result.depth.max(1) as i64;
+        *votes.entry(result.lines[0].pv[0]).or_default() += weight;
     }
-    if alpha < standing_pat {
-        alpha = standing_pat;
+
+    let votes_for = |result: &SearchResult| votes[&result.lines[0].pv[0]];
+
+    let mut best = 0;
+    for (i, result) in results.iter().enumerate() {
+        let (v, best_v) = (votes_for(result), votes_for(&results[best]));
+        if v > best_v || (v == best_v && result.depth > results[best].depth) {
+            best = i;
+        }
     }
 
-    let moves = generate_moves(board);
+    results.into_iter().nth(best).unwrap_or_default()
+}
 
-    for mv in &moves {
-        if mv.kind().is_capture() {
-            let score = -quiescence_search(&board.do_move(mv), -beta, -alpha);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::uci::parse_move;
 
-            if score >= beta {
-                return beta;
-            }
-            if score > alpha {
-                alpha = score;
-            }
+    fn mv(uci: &str) -> Move {
+        parse_move(uci, &Board::default()).unwrap()
+    }
+
+    fn result(depth: usize, score: Score, mv: Move, nodes: u64) -> SearchResult {
+        SearchResult {
+            depth,
+            lines: vec![PvLine {
+                score,
+                pv: vec![mv],
+            }],
+            stats: SearchStats {
+                nodes,
+                seldepth: depth,
+            },
         }
     }
 
-    return alpha;
+    #[test]
+    fn vote_majority() {
+        let results = vec![
+            result(8, 20, mv("e2e4"), 1),
+            result(8, 20, mv("d2d4"), 2),
+            result(8, 20, mv("d2d4"), 3),
+        ];
+        let best = vote(results);
+        assert_eq!(best.best_move(), Some(mv("d2d4")));
+        // The first thread with the winning move is picked
+        assert_eq!(best.stats.nodes, 2);
+    }
+
+    #[test]
+    fn vote_weights_score_and_depth() {
+        // A much better score outweighs two votes for a worse move
+        let results = vec![
+            result(8, 0, mv("e2e4"), 0),
+            result(8, 0, mv("e2e4"), 0),
+            result(8, 100, mv("d2d4"), 0),
+        ];
+        assert_eq!(vote(results).best_move(), Some(mv("d2d4")));
+
+        // Equal scores: the deeper search carries more weight
+        let results = vec![
+            result(4, 0, mv("e2e4"), 0),
+            result(4, 0, mv("e2e4"), 0),
+            result(12, 0, mv("d2d4"), 0),
+        ];
+        assert_eq!(vote(results).best_move(), Some(mv("d2d4")));
+    }
+
+    #[test]
+    fn vote_tie_prefers_deeper_result() {
+        let results = vec![result(6, 10, mv("e2e4"), 1), result(7, 10, mv("e2e4"), 2)];
+        let best = vote(results);
+        assert_eq!(best.depth, 7);
+        assert_eq!(best.stats.nodes, 2);
+    }
+
+    #[test]
+    fn vote_ignores_unfinished_helpers() {
+        let mut unfinished = result(0, 500, mv("d2d4"), 0);
+        let results = vec![
+            result(1, 0, mv("e2e4"), 0),
+            unfinished.clone(),
+            unfinished.clone(),
+        ];
+        assert_eq!(vote(results).best_move(), Some(mv("e2e4")));
+
+        // The main thread always votes, even without a completed iteration
+        unfinished.lines[0].pv[0] = mv("g1f3");
+        assert_eq!(vote(vec![unfinished]).best_move(), Some(mv("g1f3")));
+
+        let empty = SearchResult::default();
+        assert_eq!(vote(vec![empty.clone(), empty]).best_move(), None);
+        assert_eq!(vote(Vec::new()).best_move(), None);
+    }
+
+    #[test]
+    fn stats_across_threads() {
+        let results = [
+            result(9, 0, mv("e2e4"), 1000),
+            result(12, 0, mv("e2e4"), 250),
+            result(3, 0, mv("d2d4"), 5),
+        ];
+        let stats = merge_stats(results.iter().map(|r| &r.stats));
+        assert_eq!(stats.nodes, 1255);
+        assert_eq!(stats.seldepth, 12);
+        assert_eq!(merge_stats([]).nodes, 0);
+    }
+
+    #[test]
+    fn searchmoves_restrict_root() {
+        // Ra8 mates, but only the king may move
+        let board = Board::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        let tt = TranspositionTable::new(1);
+        let searchmoves = vec![
+            parse_move("g1f1", &board).unwrap(),
+            parse_move("g1h1", &board).unwrap(),
+        ];
+        let options = SearchOptions {
+            max_depth: 3,
+            multipv: 3,
+            searchmoves: searchmoves.clone(),
+            ..Default::default()
+        };
+        let result = Searcher::new(&tt, &|_| false).search(&board, &options, |_| ());
+
+        assert_eq!(result.lines.len(), 2);
+        assert!(result
+            .lines
+            .iter()
+            .all(|line| searchmoves.contains(&line.pv[0])));
+        assert_eq!(result.mate_in(), None);
+    }
+
+    #[test]
+    fn mate_search_without_mate() {
+        let tt = TranspositionTable::new(1);
+        let options = SearchOptions {
+            mate: Some(2),
+            ..Default::default()
+        };
+        let mut depths = Vec::new();
+        let result = Searcher::new(&tt, &|_| false)
+            .search(&Board::default(), &options, |r| depths.push(r.depth));
+
+        // Stops after the depth a mate in 2 needs, still reporting a move
+        assert_eq!(depths, [1, 2, 3]);
+        assert_eq!(result.depth, 3);
+        assert_eq!(result.mate_in(), None);
+        assert!(result.best_move().is_some());
+    }
+
+    #[test]
+    fn mate_search_ignores_longer_mates() {
+        // Mate in 3, which a previous search leaves in the table
+        let board =
+            Board::from_fen("r1b1kb1r/pppp1ppp/5q2/4n3/3KP3/2N3PN/PPP4P/R1BQ1B1R b kq - 0 1")
+                .unwrap();
+        let tt = TranspositionTable::new(1);
+        let deep = SearchOptions {
+            max_depth: 6,
+            ..Default::default()
+        };
+        let result = Searcher::new(&tt, &|_| false).search(&board, &deep, |_| ());
+        assert_eq!(result.mate_in(), Some(3));
+
+        let options = SearchOptions {
+            mate: Some(2),
+            ..Default::default()
+        };
+        let mut depths = Vec::new();
+        let result = Searcher::new(&tt, &|_| false)
+            .search(&board, &options, |r| depths.push((r.depth, r.mate_in())));
+        assert_eq!(depths.last().map(|&(depth, _)| depth), Some(3));
+        assert!(
+            depths.iter().any(|&(_, mate)| mate == Some(3)),
+            "{depths:?}"
+        );
+        assert_eq!(result.depth, 3);
+    }
 }
@@ -0,0 +1,156 @@
+use crate::{
+    rng::Rng,
+    search::{PvLine, MAX_DEPTH},
+};
+
+/// Playing strength below full strength. Weaker levels search shallower, with fewer nodes,
+/// and deliberately pick worse moves among several candidates.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Skill {
+    level: u8,
+}
+
+impl Skill {
+    /// Full strength.
+    pub const MAX_LEVEL: u8 = 20;
+
+    /// Estimated Elo of some levels. The steps come from short self-play matches, 100 games
+    /// at 10+0.1 between each listed level and the one listed before it, so they are rough
+    /// estimates. Self-play gives only differences, so level 0 is set to 800.
+    const ELO_POINTS: [(u8, i64); 7] = [
+        (0, 800),
+        (3, 1090),
+        (6, 1220),
+        (9, 1350),
+        (12, 1490),
+        (15, 1560),
+        (19, 1660),
+    ];
+
+    /// Elo range advertised through `UCI_Elo`, from level 0 up to level 19.
+    pub const MIN_ELO: i64 = Self::ELO_POINTS[0].1;
+    pub const MAX_ELO: i64 = Self::ELO_POINTS[Self::ELO_POINTS.len() - 1].1;
+
+    /// Number of candidate moves to choose from when strength is limited.
+    pub const MULTIPV: usize = 4;
+
+    pub fn new(level: u8) -> Self {
+        Self {
+            level: level.min(Self::MAX_LEVEL),
+        }
+    }
+
+    /// Maps an Elo rating to the highest skill level not stronger than it, interpolating
+    /// between the levels with an Elo estimate.
+    /// # Example
+    /// ```
+    /// # use chess::skill::Skill;
+    /// assert_eq!(Skill::from_elo(Skill::MIN_ELO).level(), 0);
+    /// assert_eq!(Skill::from_elo(1350).level(), 9);
+    /// assert_eq!(Skill::from_elo(1400).level(), 10);
+    /// assert_eq!(Skill::from_elo(Skill::MAX_ELO).level(), 19);
+    /// ```
+    pub fn from_elo(elo: i64) -> Self {
+        let elo = elo.clamp(Self::MIN_ELO, Self::MAX_ELO);
+        let (mut level, _) = Self::ELO_POINTS[0];
+
+        for pair in Self::ELO_POINTS.windows(2) {
+            let [(level0, elo0), (level1, elo1)] = [pair[0], pair[1]];
+            if elo >= elo0 {
+                let steps = (elo.min(elo1) - elo0) * (level1 - level0) as i64 / (elo1 - elo0);
+                level = level0 + steps as u8;
+            }
+        }
+
+        Self::new(level)
+    }
+
+    pub fn level(&self) -> u8 {
+        self.level
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.level < Self::MAX_LEVEL
+    }
+
+    pub fn max_depth(&self) -> usize {
+        if self.is_enabled() {
+            1 + self.level as usize / 2
+        } else {
+            MAX_DEPTH
+        }
+    }
+
+    pub fn max_nodes(&self) -> Option<u64> {
+        if self.is_enabled() {
+            Some(1000 << (self.level / 2))
+        } else {
+            None
+        }
+    }
+
+    /// Picks one of the ranked `lines`. The lower the level, the more score loss is accepted,
+    /// with some randomness on top.
+    pub fn pick<'a>(&self, lines: &'a [PvLine], rng: &mut Rng) -> Option<&'a PvLine> {
+        let top = lines.first()?.score as i64;
+        if !self.is_enabled() {
+            return lines.first();
+        }
+
+        let weakness = 120 - 2 * self.level as i64;
+        let delta = (top - lines.last()?.score as i64).min(100);
+
+        lines.iter().max_by_key(|line| {
+            let score = line.score as i64;
+            let push = (weakness * (top - score) + delta * rng.below(weakness as u64) as i64) / 128;
+            score + push
+        })
+    }
+}
+
+impl Default for Skill {
+    fn default() -> Self {
+        Self::new(Self::MAX_LEVEL)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::Board;
+
+    fn lines(scores: &[i32]) -> Vec<PvLine> {
+        let moves = Board::default().legal_moves();
+        scores
+            .iter()
+            .zip(moves)
+            .map(|(&score, mv)| PvLine {
+                score,
+                pv: vec![mv],
+            })
+            .collect()
+    }
+
+    #[test]
+    fn pick_weaker_moves() {
+        let lines = lines(&[50, 40, 20, -300]);
+        let picks = |skill: Skill, seed| {
+            let mut rng = Rng::new(seed);
+            (0..200)
+                .map(|_| skill.pick(&lines, &mut rng).unwrap().score)
+                .collect::<Vec<_>>()
+        };
+
+        // Full strength always plays the best move
+        assert!(picks(Skill::default(), 1).iter().all(|&s| s == 50));
+
+        // Same seed, same moves
+        assert_eq!(picks(Skill::new(0), 1), picks(Skill::new(0), 1));
+
+        // Weak levels play worse moves, stronger ones don't blunder a piece
+        assert!(picks(Skill::new(0), 1).iter().any(|&s| s < 50));
+        let medium = picks(Skill::new(14), 1);
+        assert!(medium.iter().any(|&s| s < 50));
+        assert!(medium.iter().all(|&s| s > -300));
+    }
+}
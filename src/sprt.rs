@@ -0,0 +1,184 @@
+use std::fmt;
+
+/// Game results of a match from the first engine's point of view.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct MatchScore {
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+}
+
+/// Converts an expected score between 0 and 1 into an Elo difference.
+fn elo(score: f64) -> f64 {
+    -400.0 * (1.0 / score - 1.0).log10()
+}
+
+/// Converts an Elo difference into an expected score.
+fn expected_score(elo: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-elo / 400.0))
+}
+
+impl MatchScore {
+    pub fn games(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+
+    /// Points per game, between 0 and 1.
+    pub fn score(&self) -> f64 {
+        (self.wins as f64 + self.draws as f64 / 2.0) / self.games().max(1) as f64
+    }
+
+    /// Variance of the points of a single game.
+    fn variance(&self) -> f64 {
+        let n = self.games().max(1) as f64;
+        let mu = self.score();
+
+        (self.wins as f64 * (1.0 - mu).powi(2)
+            + self.draws as f64 * (0.5 - mu).powi(2)
+            + self.losses as f64 * mu.powi(2))
+            / n
+    }
+
+    /// Elo difference with the margin of its 95% confidence interval. There is no estimate
+    /// before both a point has been won and a point has been lost.
+    /// # Example
+    /// ```
+    /// # use chess::sprt::MatchScore;
+    /// let score = MatchScore { wins: 60, draws: 0, losses: 40 };
+    /// let (elo, margin) = score.elo().unwrap();
+    /// assert_eq!(elo.round(), 70.0);
+    /// assert!(margin > 60.0 && margin < 80.0);
+    /// ```
+    pub fn elo(&self) -> Option<(f64, f64)> {
+        let mu = self.score();
+        if self.games() == 0 || mu <= 0.0 || mu >= 1.0 {
+            return None;
+        }
+
+        let deviation = (self.variance() / self.games() as f64).sqrt();
+        let low = (mu - 1.96 * deviation).max(f64::EPSILON);
+        let high = (mu + 1.96 * deviation).min(1.0 - f64::EPSILON);
+
+        Some((elo(mu), (elo(high) - elo(low)) / 2.0))
+    }
+}
+
+impl fmt::Display for MatchScore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} - {} - {} [{:.3}] {}",
+            self.wins,
+            self.losses,
+            self.draws,
+            self.score(),
+            self.games()
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SprtStatus {
+    Continue,
+    /// The first engine is not stronger by `elo1`
+    AcceptH0,
+    /// The first engine is stronger by more than `elo0`
+    AcceptH1,
+}
+
+/// Sequential probability ratio test of the hypotheses that the first engine is `elo0`
+/// stronger (H0) against `elo1` stronger (H1), with false positive rate `alpha` and false
+/// negative rate `beta`.
+#[derive(Debug, Clone, Copy)]
+pub struct Sprt {
+    pub elo0: f64,
+    pub elo1: f64,
+    pub alpha: f64,
+    pub beta: f64,
+}
+
+impl Default for Sprt {
+    fn default() -> Self {
+        Self {
+            elo0: 0.0,
+            elo1: 5.0,
+            alpha: 0.05,
+            beta: 0.05,
+        }
+    }
+}
+
+impl Sprt {
+    /// Log-likelihood ratio bounds at which H0 and H1 are accepted.
+    pub fn bounds(&self) -> (f64, f64) {
+        (
+            (self.beta / (1.0 - self.alpha)).ln(),
+            ((1.0 - self.beta) / self.alpha).ln(),
+        )
+    }
+
+    /// Log-likelihood ratio of the results, using the normal approximation of the
+    /// generalized SPRT. It stays zero as long as all games ended the same way.
+    pub fn llr(&self, score: &MatchScore) -> f64 {
+        let variance = score.variance();
+        if score.games() == 0 || variance == 0.0 {
+            return 0.0;
+        }
+
+        let s0 = expected_score(self.elo0);
+        let s1 = expected_score(self.elo1);
+
+        score.games() as f64 * (s1 - s0) * (2.0 * score.score() - s0 - s1) / (2.0 * variance)
+    }
+
+    pub fn status(&self, score: &MatchScore) -> SprtStatus {
+        let llr = self.llr(score);
+        let (lower, upper) = self.bounds();
+
+        if llr <= lower {
+            SprtStatus::AcceptH0
+        } else if llr >= upper {
+            SprtStatus::AcceptH1
+        } else {
+            SprtStatus::Continue
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sprt() {
+        let sprt = Sprt::default();
+        let (lower, upper) = sprt.bounds();
+        assert!((lower + 2.944).abs() < 0.001);
+        assert!((upper - 2.944).abs() < 0.001);
+
+        let even = MatchScore {
+            wins: 20,
+            draws: 20,
+            losses: 20,
+        };
+        assert_eq!(even.elo().unwrap().0, 0.0);
+        assert_eq!(sprt.status(&even), SprtStatus::Continue);
+        assert_eq!(sprt.status(&MatchScore::default()), SprtStatus::Continue);
+
+        let even = MatchScore {
+            wins: 9000,
+            draws: 12000,
+            losses: 9000,
+        };
+        assert_eq!(sprt.status(&even), SprtStatus::AcceptH0);
+
+        let better = MatchScore {
+            wins: 3000,
+            draws: 4000,
+            losses: 2000,
+        };
+        assert!(sprt.llr(&better) > upper);
+        assert_eq!(sprt.status(&better), SprtStatus::AcceptH1);
+        assert_eq!(better.to_string(), "3000 - 2000 - 4000 [0.556] 9000");
+    }
+}
@@ -2,7 +2,7 @@ use std::{ops, str::FromStr};
 
 use crate::bitboard::Direction;
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub enum Rank {
     First,
     Second,
@@ -43,12 +43,12 @@ impl Rank {
         }
     }
 
-    pub(crate) const fn to_index(&self) -> usize {
-        *self as usize
+    pub(crate) const fn to_index(self) -> usize {
+        self as usize
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub enum File {
     A,
     B,
@@ -90,12 +90,12 @@ impl File {
         }
     }
 
-    pub(crate) const fn to_index(&self) -> usize {
-        *self as usize
+    pub(crate) const fn to_index(self) -> usize {
+        self as usize
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct Square(u8);
 
 impl Square {
@@ -150,7 +150,7 @@ impl Square {
         Self(index as u8)
     }
 
-    pub(crate) const fn to_index_const(&self) -> usize {
+    pub(crate) const fn to_index_const(self) -> usize {
         self.0 as usize
     }
 
@@ -242,7 +242,7 @@ impl ops::Sub<Direction> for Square {
 
 impl From<Rank> for char {
     fn from(value: Rank) -> Self {
-        ('1' as u8 + value.to_index() as u8) as char
+        (b'1' + value.to_index() as u8) as char
     }
 }
 
@@ -251,7 +251,7 @@ impl TryFrom<char> for Rank {
 
     fn try_from(value: char) -> Result<Self, Self::Error> {
         match value {
-            '1'..='8' => Ok(Rank::from_index((value as u8 - '1' as u8) as usize)),
+            '1'..='8' => Ok(Rank::from_index((value as u8 - b'1') as usize)),
             _ => Err(()),
         }
     }
@@ -259,7 +259,7 @@ impl TryFrom<char> for Rank {
 
 impl From<File> for char {
     fn from(value: File) -> Self {
-        ('a' as u8 + value.to_index() as u8) as char
+        (b'a' + value.to_index() as u8) as char
     }
 }
 
@@ -268,7 +268,7 @@ impl TryFrom<char> for File {
 
     fn try_from(value: char) -> Result<Self, Self::Error> {
         match value {
-            'a'..='h' => Ok(File::from_index((value as u8 - 'a' as u8) as usize)),
+            'a'..='h' => Ok(File::from_index((value as u8 - b'a') as usize)),
             _ => Err(()),
         }
     }
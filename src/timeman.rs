@@ -0,0 +1,96 @@
+use std::time::{Duration, Instant};
+
+use crate::{color::Color, uci::SearchParams};
+
+/// Number of moves assumed to be left in the game when `movestogo` is not given.
+const DEFAULT_MOVES_TO_GO: u32 = 30;
+
+/// Decides how long to think about a move. The clock starts when the manager is created, so
+/// time spent pondering counts once the search is converted into a timed one.
+#[derive(Debug, Clone)]
+pub struct TimeManager {
+    start: Instant,
+    budget: Option<Duration>,
+}
+
+impl TimeManager {
+    /// Allocates time for the side to move. There is no budget for infinite searches, or when
+    /// neither a clock nor `movetime` is given.
+    /// # Example
+    /// ```
+    /// # use std::time::Duration;
+    /// # use chess::{color::Color, timeman::TimeManager, uci::SearchParams};
+    /// let mut params = SearchParams::new();
+    /// params.movetime = Some(1000);
+    /// let tm = TimeManager::new(&params, Color::White, 10);
+    /// assert_eq!(tm.budget(), Some(Duration::from_millis(990)));
+    /// ```
+    pub fn new(params: &SearchParams, color: Color, move_overhead: u32) -> Self {
+        let (time, inc) = match color {
+            Color::White => (params.wtime, params.winc as u32),
+            Color::Black => (params.btime, params.binc as u32),
+        };
+
+        let budget = if params.infinite {
+            None
+        } else if let Some(movetime) = params.movetime {
+            Some(movetime.saturating_sub(move_overhead))
+        } else if time > 0 {
+            let moves_to_go = params
+                .movestogo
+                .map_or(DEFAULT_MOVES_TO_GO, |n| n.max(1) as u32);
+            let max = time.saturating_sub(move_overhead);
+            Some((time / moves_to_go + inc * 3 / 4).min(max))
+        } else {
+            None
+        };
+
+        Self {
+            start: Instant::now(),
+            budget: budget.map(|ms| Duration::from_millis(ms as u64)),
+        }
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    pub fn budget(&self) -> Option<Duration> {
+        self.budget
+    }
+
+    /// Returns true once the allocated time has been used up.
+    pub fn time_up(&self) -> bool {
+        self.budget.is_some_and(|budget| self.elapsed() >= budget)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allocation() {
+        let mut params = SearchParams::new();
+        assert_eq!(TimeManager::new(&params, Color::White, 10).budget(), None);
+
+        params.wtime = 30_000;
+        params.winc = 400;
+        params.btime = 1000;
+        let tm = TimeManager::new(&params, Color::White, 10);
+        assert_eq!(tm.budget(), Some(Duration::from_millis(1300)));
+
+        // Never more than what is left on the clock
+        params.movestogo = Some(1);
+        let tm = TimeManager::new(&params, Color::White, 10);
+        assert_eq!(tm.budget(), Some(Duration::from_millis(29_990)));
+
+        let tm = TimeManager::new(&params, Color::Black, 10);
+        assert_eq!(tm.budget(), Some(Duration::from_millis(990)));
+
+        params.infinite = true;
+        let tm = TimeManager::new(&params, Color::White, 10);
+        assert_eq!(tm.budget(), None);
+        assert!(!tm.time_up());
+    }
+}
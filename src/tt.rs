@@ -0,0 +1,215 @@
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+
+use crate::{
+    eval::{Score, MATE},
+    moves::Move,
+};
+
+/// Scores within this many plies of `MATE` are mate scores.
+const MAX_MATE_PLY: Score = 1000;
+
+/// Kind of score stored in an entry. Search is fail-hard, so a score that failed low is an
+/// upper bound and a score that failed high is a lower bound.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Bound {
+    Exact,
+    Lower,
+    Upper,
+}
+
+impl Bound {
+    fn to_bits(self) -> u64 {
+        match self {
+            Bound::Exact => 1,
+            Bound::Lower => 2,
+            Bound::Upper => 3,
+        }
+    }
+
+    fn from_bits(bits: u64) -> Option<Self> {
+        match bits {
+            1 => Some(Bound::Exact),
+            2 => Some(Bound::Lower),
+            3 => Some(Bound::Upper),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct TtEntry {
+    pub mv: Move,
+    pub depth: usize,
+    pub bound: Bound,
+    score: Score,
+    generation: u8,
+}
+
+impl TtEntry {
+    /// Returns the stored score, with mate scores converted back to distance from the root.
+    pub fn score(&self, ply: usize) -> Score {
+        if self.score >= MATE - MAX_MATE_PLY {
+            self.score - ply as Score
+        } else if self.score <= -MATE + MAX_MATE_PLY {
+            self.score + ply as Score
+        } else {
+            self.score
+        }
+    }
+
+    // Layout: score 32 bits, move 16 bits, depth 8 bits, bound 2 bits, generation 6 bits
+    fn to_bits(self) -> u64 {
+        (self.score as u32 as u64)
+            | (self.mv.to_index() as u64) << 32
+            | (self.depth.min(255) as u64) << 48
+            | self.bound.to_bits() << 56
+            | (self.generation as u64) << 58
+    }
+
+    fn from_bits(bits: u64) -> Option<Self> {
+        Some(Self {
+            score: bits as u32 as i32,
+            mv: Move::from_index((bits >> 32) as u16),
+            depth: ((bits >> 48) & 255) as usize,
+            bound: Bound::from_bits((bits >> 56) & 3)?,
+            generation: (bits >> 58) as u8,
+        })
+    }
+}
+
+/// Transposition table shared by all search threads. Entries are stored lock-free as the
+/// key xored with the data, so entries torn by concurrent writes fail the key check.
+pub struct TranspositionTable {
+    entries: Vec<[AtomicU64; 2]>,
+    generation: AtomicU8,
+}
+
+impl TranspositionTable {
+    const ENTRY_SIZE: usize = 16;
+
+    /// Creates a table of `mb` megabytes.
+    pub fn new(mb: usize) -> Self {
+        let len = (mb * 1024 * 1024 / Self::ENTRY_SIZE).max(1);
+
+        Self {
+            entries: (0..len)
+                .map(|_| [AtomicU64::new(0), AtomicU64::new(0)])
+                .collect(),
+            generation: AtomicU8::new(0),
+        }
+    }
+
+    pub fn clear(&self) {
+        for [key, data] in &self.entries {
+            key.store(0, Ordering::Relaxed);
+            data.store(0, Ordering::Relaxed);
+        }
+        self.generation.store(0, Ordering::Relaxed);
+    }
+
+    /// Called at the start of every search so that entries from earlier searches get replaced
+    /// first.
+    pub fn new_search(&self) {
+        let generation = (self.generation.load(Ordering::Relaxed) + 1) & 63;
+        self.generation.store(generation, Ordering::Relaxed);
+    }
+
+    fn index(&self, key: u64) -> usize {
+        ((key as u128 * self.entries.len() as u128) >> 64) as usize
+    }
+
+    pub fn probe(&self, key: u64) -> Option<TtEntry> {
+        let [stored_key, data] = &self.entries[self.index(key)];
+        let data = data.load(Ordering::Relaxed);
+
+        if stored_key.load(Ordering::Relaxed) ^ data != key {
+            return None;
+        }
+
+        TtEntry::from_bits(data)
+    }
+
+    /// Stores a search result. `ply` is the distance from the root, which is needed to store
+    /// mate scores relative to this position.
+    pub fn store(&self, key: u64, mv: Move, score: Score, bound: Bound, depth: usize, ply: usize) {
+        let [stored_key, data] = &self.entries[self.index(key)];
+        let generation = self.generation.load(Ordering::Relaxed);
+
+        // Keep deeper results of the current search for other positions
+        let old_data = data.load(Ordering::Relaxed);
+        if let Some(old) = TtEntry::from_bits(old_data) {
+            if stored_key.load(Ordering::Relaxed) ^ old_data != key
+                && old.generation == generation
+                && old.depth > depth + 2
+            {
+                return;
+            }
+        }
+
+        let score = if score >= MATE - MAX_MATE_PLY {
+            score + ply as Score
+        } else if score <= -MATE + MAX_MATE_PLY {
+            score - ply as Score
+        } else {
+            score
+        };
+
+        let entry = TtEntry {
+            mv,
+            depth,
+            bound,
+            score,
+            generation,
+        };
+        let bits = entry.to_bits();
+
+        stored_key.store(key ^ bits, Ordering::Relaxed);
+        data.store(bits, Ordering::Relaxed);
+    }
+
+    /// Permille of the table used by the current search, sampled from the first entries.
+    pub fn hashfull(&self) -> u32 {
+        let generation = self.generation.load(Ordering::Relaxed);
+
+        let sample = self.entries.len().min(1000);
+        let used = self.entries[..sample]
+            .iter()
+            .filter_map(|[_, data]| TtEntry::from_bits(data.load(Ordering::Relaxed)))
+            .filter(|entry| entry.generation == generation)
+            .count();
+
+        (used * 1000 / sample) as u32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::Board;
+
+    #[test]
+    fn store_and_probe() {
+        let tt = TranspositionTable::new(1);
+        let board = Board::default();
+        let mv = board.legal_moves()[3];
+        let key = board.polyglot_key();
+
+        assert!(tt.probe(key).is_none());
+        assert_eq!(tt.hashfull(), 0);
+
+        tt.store(key, mv, -35, Bound::Lower, 7, 2);
+        let entry = tt.probe(key).unwrap();
+        assert_eq!(entry.mv.to_index(), mv.to_index());
+        assert_eq!(entry.score(5), -35);
+        assert_eq!(entry.bound, Bound::Lower);
+        assert_eq!(entry.depth, 7);
+        assert!(tt.probe(key ^ 1).is_none());
+
+        // Mate in 3 plies from a node at ply 2 is mate in 4 plies from a node at ply 3
+        tt.store(key, mv, MATE - 5, Bound::Exact, 3, 2);
+        assert_eq!(tt.probe(key).unwrap().score(3), MATE - 6);
+
+        tt.clear();
+        assert!(tt.probe(key).is_none());
+    }
+}
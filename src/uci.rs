@@ -1,13 +1,56 @@
+pub mod client;
+pub mod options;
+
 use std::{fmt, str::FromStr};
 
 use crate::{
     board::Board,
-    eval::Score,
-    moves::{generate_moves, Move},
+    eval::{Score, MATE},
+    moves::{Move, MoveKind},
     piece::PieceKind,
     square::{File, Rank, Square},
 };
 
+use self::options::{EngineOption, SetOptionError};
+
+/// Parameters of the `go perft` debug command.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PerftParams {
+    pub depth: usize,
+    /// Tally captures, checks etc. at the leaves, always on a single thread
+    pub stats: bool,
+    pub threads: usize,
+    /// Size of the node count table in megabytes, none if 0
+    pub hash: usize,
+}
+
+impl Default for PerftParams {
+    fn default() -> Self {
+        Self {
+            depth: 0,
+            stats: false,
+            threads: 1,
+            hash: 0,
+        }
+    }
+}
+
+impl fmt::Display for PerftParams {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "go perft {}", self.depth)?;
+        if self.stats {
+            write!(f, " stats")?;
+        }
+        if self.threads != 1 {
+            write!(f, " threads {}", self.threads)?;
+        }
+        if self.hash != 0 {
+            write!(f, " hash {}", self.hash)?;
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug)]
 pub struct SearchParams {
     pub wtime: u32,
@@ -20,6 +63,16 @@ pub struct SearchParams {
     pub mate: Option<u8>,
     pub movetime: Option<u32>,
     pub infinite: bool,
+    /// Search the expected reply position until `ponderhit` or `stop`.
+    pub ponder: bool,
+    /// Restricts the search to these root moves, in UCI notation.
+    pub searchmoves: Vec<String>,
+}
+
+impl Default for SearchParams {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl SearchParams {
@@ -35,53 +88,290 @@ impl SearchParams {
             mate: None,
             movetime: None,
             infinite: false,
+            ponder: false,
+            searchmoves: Vec::new(),
         }
     }
 }
 
-#[derive(Debug)]
+impl fmt::Display for SearchParams {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "go")?;
+        if self.ponder {
+            write!(f, " ponder")?;
+        }
+        if self.infinite {
+            write!(f, " infinite")?;
+        }
+        if self.wtime > 0 || self.btime > 0 {
+            write!(f, " wtime {} btime {}", self.wtime, self.btime)?;
+        }
+        if self.winc > 0 || self.binc > 0 {
+            write!(f, " winc {} binc {}", self.winc, self.binc)?;
+        }
+        if let Some(movestogo) = self.movestogo {
+            write!(f, " movestogo {}", movestogo)?;
+        }
+        if let Some(depth) = self.depth {
+            write!(f, " depth {}", depth)?;
+        }
+        if let Some(nodes) = self.nodes {
+            write!(f, " nodes {}", nodes)?;
+        }
+        if let Some(mate) = self.mate {
+            write!(f, " mate {}", mate)?;
+        }
+        if let Some(movetime) = self.movetime {
+            write!(f, " movetime {}", movetime)?;
+        }
+        if !self.searchmoves.is_empty() {
+            write!(f, " searchmoves {}", self.searchmoves.join(" "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Score as reported to the GUI, either in centipawns or in moves to mate. Negative mate
+/// scores mean the engine is getting mated.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UciScore {
+    Cp(i32),
+    Mate(i32),
+}
+
+impl UciScore {
+    /// Scores within this many plies of `MATE` are mate scores.
+    const MAX_MATE_PLY: i32 = 1000;
+
+    /// Converts a search score to centipawns or full moves to mate.
+    /// # Example
+    /// ```
+    /// # use chess::{eval::MATE, uci::UciScore};
+    /// assert_eq!(UciScore::from_score(35), UciScore::Cp(35));
+    /// assert_eq!(UciScore::from_score(MATE - 1), UciScore::Mate(1));
+    /// assert_eq!(UciScore::from_score(MATE - 3), UciScore::Mate(2));
+    /// assert_eq!(UciScore::from_score(-MATE + 2), UciScore::Mate(-1));
+    /// ```
+    pub fn from_score(score: Score) -> Self {
+        if score >= MATE - Self::MAX_MATE_PLY {
+            UciScore::Mate((MATE - score + 1) / 2)
+        } else if score <= -MATE + Self::MAX_MATE_PLY {
+            UciScore::Mate(-(MATE + score) / 2)
+        } else {
+            UciScore::Cp(score)
+        }
+    }
+}
+
+impl fmt::Display for UciScore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UciScore::Cp(cp) => write!(f, "cp {}", cp),
+            UciScore::Mate(moves) => write!(f, "mate {}", moves),
+        }
+    }
+}
+
+/// Whether the reported score is exact or only a bound from a failed aspiration search.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScoreBound {
+    Exact,
+    Lower,
+    Upper,
+}
+
+#[derive(Debug, Clone)]
 pub struct Info {
     pub depth: Option<u32>,
-    // pub seldepth: Option<u32>,
+    pub seldepth: Option<u32>,
     pub time: Option<u32>,
     pub nodes: Option<u64>,
-    // pub pv: Vector<Move>,
-    // pub multipv: u32,
-    pub score: Option<Score>, // TODO: change Score to enum
+    pub pv: Vec<Move>,
+    pub multipv: Option<u32>,
+    pub score: Option<UciScore>,
+    pub bound: ScoreBound,
+    /// Win, draw and loss probabilities in per mille
+    pub wdl: Option<(u32, u32, u32)>,
     pub currmove: Option<Move>,
     pub currmovenumber: Option<u32>,
-    // pub hashfull: Option<u32>,
+    pub hashfull: Option<u32>,
     pub nps: Option<u64>,
-    // pub tbhits: Option<u64>,
-    // pub sbhits: Option<u64>,
-    // pub cpuload: Option<u32>,
-    // pub string: Option<String>,
-    // pub refutation: Vector<Move>,
-    // pub currline: Vector<Move>,
+    pub tbhits: Option<u64>,
+    pub sbhits: Option<u64>,
+    pub cpuload: Option<u32>,
+    pub string: Option<String>,
+    pub refutation: Vec<Move>,
+    pub currline: Vec<Move>,
+}
+
+impl Default for Info {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Info {
+    pub fn new() -> Self {
+        Self {
+            depth: None,
+            seldepth: None,
+            time: None,
+            nodes: None,
+            pv: Vec::new(),
+            multipv: None,
+            score: None,
+            bound: ScoreBound::Exact,
+            wdl: None,
+            currmove: None,
+            currmovenumber: None,
+            hashfull: None,
+            nps: None,
+            tbhits: None,
+            sbhits: None,
+            cpuload: None,
+            string: None,
+            refutation: Vec::new(),
+            currline: Vec::new(),
+        }
+    }
+}
+
+fn write_moves(f: &mut fmt::Formatter<'_>, name: &str, moves: &[Move]) -> fmt::Result {
+    if !moves.is_empty() {
+        write!(f, " {}", name)?;
+        for mv in moves {
+            write!(f, " {}", mv)?;
+        }
+    }
+    Ok(())
+}
+
+impl fmt::Display for Info {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "info")?;
+        if let Some(depth) = self.depth {
+            write!(f, " depth {}", depth)?;
+        }
+        if let Some(seldepth) = self.seldepth {
+            write!(f, " seldepth {}", seldepth)?;
+        }
+        if let Some(multipv) = self.multipv {
+            write!(f, " multipv {}", multipv)?;
+        }
+        if let Some(score) = self.score {
+            write!(f, " score {}", score)?;
+            match self.bound {
+                ScoreBound::Exact => (),
+                ScoreBound::Lower => write!(f, " lowerbound")?,
+                ScoreBound::Upper => write!(f, " upperbound")?,
+            }
+        }
+        if let Some((w, d, l)) = self.wdl {
+            write!(f, " wdl {} {} {}", w, d, l)?;
+        }
+        if let Some(currmove) = self.currmove {
+            write!(f, " currmove {}", currmove)?;
+        }
+        if let Some(currmovenumber) = self.currmovenumber {
+            write!(f, " currmovenumber {}", currmovenumber)?;
+        }
+        if let Some(nodes) = self.nodes {
+            write!(f, " nodes {}", nodes)?;
+        }
+        if let Some(nps) = self.nps {
+            write!(f, " nps {}", nps)?;
+        }
+        if let Some(hashfull) = self.hashfull {
+            write!(f, " hashfull {}", hashfull)?;
+        }
+        if let Some(tbhits) = self.tbhits {
+            write!(f, " tbhits {}", tbhits)?;
+        }
+        if let Some(sbhits) = self.sbhits {
+            write!(f, " sbhits {}", sbhits)?;
+        }
+        if let Some(cpuload) = self.cpuload {
+            write!(f, " cpuload {}", cpuload)?;
+        }
+        if let Some(time) = self.time {
+            write!(f, " time {}", time)?;
+        }
+        write_moves(f, "pv", &self.pv)?;
+        write_moves(f, "refutation", &self.refutation)?;
+        write_moves(f, "currline", &self.currline)?;
+        // Everything after "string" is part of the string, so it must come last
+        if let Some(string) = &self.string {
+            write!(f, " string {}", string)?;
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug)]
 pub enum UCICommand {
     // To engine
     Uci,
+    UciNewGame,
     IsReady,
     Quit,
     Stop,
+    PonderHit,
     Position(String, Vec<String>),
     Go(SearchParams),
+    SetOption(String, Option<String>),
 
     // From engine
     Info(Info),
     Id(String, String),
+    Option(EngineOption),
     UciOk,
     ReadyOk,
-    BestMove(Move),
+    BestMove(Move, Option<Move>),
 
     // Debug commands (not really UCI)
-    Perft(usize),
+    Perft(PerftParams),
     Display,
 }
 
+impl fmt::Display for UCICommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UCICommand::Uci => write!(f, "uci"),
+            UCICommand::UciNewGame => write!(f, "ucinewgame"),
+            UCICommand::IsReady => write!(f, "isready"),
+            UCICommand::Quit => write!(f, "quit"),
+            UCICommand::Stop => write!(f, "stop"),
+            UCICommand::PonderHit => write!(f, "ponderhit"),
+            UCICommand::Position(fen, moves) => {
+                write!(f, "position fen {}", fen)?;
+                if !moves.is_empty() {
+                    write!(f, " moves {}", moves.join(" "))?;
+                }
+                Ok(())
+            }
+            UCICommand::Go(params) => write!(f, "{}", params),
+            UCICommand::SetOption(name, Some(value)) => {
+                write!(f, "setoption name {} value {}", name, value)
+            }
+            UCICommand::SetOption(name, None) => write!(f, "setoption name {}", name),
+            UCICommand::Info(info) => write!(f, "{}", info),
+            UCICommand::Id(key, value) => write!(f, "id {} {}", key, value),
+            UCICommand::Option(option) => write!(f, "{}", option),
+            UCICommand::UciOk => write!(f, "uciok"),
+            UCICommand::ReadyOk => write!(f, "readyok"),
+            // The null move means there was no move to play
+            UCICommand::BestMove(mv, _) if *mv == Move::null() => write!(f, "bestmove 0000"),
+            UCICommand::BestMove(mv, Some(ponder)) => {
+                write!(f, "bestmove {} ponder {}", mv, ponder)
+            }
+            UCICommand::BestMove(mv, None) => write!(f, "bestmove {}", mv),
+            UCICommand::Perft(params) => write!(f, "{}", params),
+            UCICommand::Display => write!(f, "d"),
+        }
+    }
+}
+
+#[derive(Debug)]
 pub struct ParseUCICommandError {
     msg: String,
     line: String,
@@ -130,8 +420,6 @@ where
         .map(|t| t.to_owned())
         .collect();
 
-    println!("{}", fen);
-
     Ok(UCICommand::Position(fen, moves))
 }
 
@@ -155,6 +443,22 @@ where
         })
 }
 
+/// Parameters of `go`, which end the move list of `searchmoves`.
+const GO_PARAMETERS: [&str; 12] = [
+    "searchmoves",
+    "ponder",
+    "wtime",
+    "btime",
+    "winc",
+    "binc",
+    "movestogo",
+    "depth",
+    "nodes",
+    "mate",
+    "movetime",
+    "infinite",
+];
+
 fn parse_go<'a, I>(tokens: I, line: &str) -> Result<UCICommand, ParseUCICommandError>
 where
     I: Iterator<Item = &'a str>,
@@ -167,7 +471,21 @@ where
     {
         &"perft" => {
             tokens.next();
-            Ok(UCICommand::Perft(parse(&mut tokens, line)?))
+            let mut params = PerftParams {
+                depth: parse(&mut tokens, line)?,
+                ..Default::default()
+            };
+
+            while let Some(token) = tokens.next() {
+                match token {
+                    "stats" => params.stats = true,
+                    "threads" => params.threads = parse::<_, usize>(&mut tokens, line)?.max(1),
+                    "hash" => params.hash = parse(&mut tokens, line)?,
+                    _ => return Err(ParseUCICommandError::new("Unknown perft parameter", line)),
+                }
+            }
+
+            Ok(UCICommand::Perft(params))
         }
         _ => {
             let mut params = SearchParams::new();
@@ -184,6 +502,12 @@ where
                     "mate" => params.mate = Some(parse(&mut tokens, line)?),
                     "movetime" => params.movetime = Some(parse(&mut tokens, line)?),
                     "infinite" => params.infinite = true,
+                    "ponder" => params.ponder = true,
+                    "searchmoves" => {
+                        while let Some(mv) = tokens.next_if(|t| !GO_PARAMETERS.contains(t)) {
+                            params.searchmoves.push(mv.to_owned());
+                        }
+                    }
                     _ => return Err(ParseUCICommandError::new("Unknown command", line)),
                 }
             }
@@ -193,6 +517,48 @@ where
     }
 }
 
+/// Splits the first whitespace separated token off `s`.
+fn next_token(s: &str) -> (&str, &str) {
+    let s = s.trim_start();
+    let end = s.find(char::is_whitespace).unwrap_or(s.len());
+    s.split_at(end)
+}
+
+fn parse_setoption(line: &str) -> Result<UCICommand, ParseUCICommandError> {
+    let missing_name = || ParseUCICommandError::new("Missing option name", line);
+
+    let (_, rest) = next_token(line);
+    let (token, mut rest) = next_token(rest);
+    if token != "name" {
+        return Err(missing_name());
+    }
+
+    // Both the name and the value may contain spaces. The value is kept verbatim, since
+    // paths may contain repeated whitespace.
+    let mut name = Vec::new();
+    let mut value = None;
+    loop {
+        let (token, after) = next_token(rest);
+        match token {
+            "" => break,
+            "value" => {
+                let raw = after.strip_prefix(char::is_whitespace).unwrap_or(after);
+                let raw = raw.strip_suffix('\r').unwrap_or(raw);
+                value = (!raw.is_empty()).then(|| raw.to_owned());
+                break;
+            }
+            _ => name.push(token),
+        }
+        rest = after;
+    }
+
+    if name.is_empty() {
+        return Err(missing_name());
+    }
+
+    Ok(UCICommand::SetOption(name.join(" "), value))
+}
+
 pub fn parse_command(line: &str) -> Result<UCICommand, ParseUCICommandError> {
     let mut tokens = line.split_whitespace();
 
@@ -201,31 +567,402 @@ pub fn parse_command(line: &str) -> Result<UCICommand, ParseUCICommandError> {
         .ok_or(ParseUCICommandError::new("Command is empty", line))?
     {
         "uci" => Ok(UCICommand::Uci),
+        "ucinewgame" => Ok(UCICommand::UciNewGame),
         "isready" => Ok(UCICommand::IsReady),
         "quit" => Ok(UCICommand::Quit),
         "stop" => Ok(UCICommand::Stop),
+        "ponderhit" => Ok(UCICommand::PonderHit),
         "position" => parse_position(tokens, line),
         "go" => parse_go(tokens, line),
+        "setoption" => parse_setoption(line),
         "d" => Ok(UCICommand::Display),
         _ => Err(ParseUCICommandError::new("Unknown command", line)),
     }
 }
 
-pub fn parse_move(mv: &str, board: &Board) -> Result<Move, ()> {
+/// Parses moves from `tokens` as long as they are legal, starting from `board`.
+fn parse_line<'a, I>(tokens: &mut std::iter::Peekable<I>, board: &Board) -> Vec<Move>
+where
+    I: Iterator<Item = &'a str>,
+{
+    let mut board = board.clone();
+    let mut moves = Vec::new();
+
+    while let Some(mv) = tokens.peek().and_then(|t| parse_move(t, &board).ok()) {
+        tokens.next();
+        board = board.do_move(mv);
+        moves.push(mv);
+    }
+
+    moves
+}
+
+fn parse_info<'a, I>(
+    tokens: I,
+    line: &str,
+    board: &Board,
+) -> Result<UCICommand, ParseUCICommandError>
+where
+    I: Iterator<Item = &'a str>,
+{
+    let mut tokens = tokens.peekable();
+    let mut info = Info::new();
+
+    while let Some(token) = tokens.next() {
+        match token {
+            "depth" => info.depth = Some(parse(&mut tokens, line)?),
+            "seldepth" => info.seldepth = Some(parse(&mut tokens, line)?),
+            "time" => info.time = Some(parse(&mut tokens, line)?),
+            "nodes" => info.nodes = Some(parse(&mut tokens, line)?),
+            "multipv" => info.multipv = Some(parse(&mut tokens, line)?),
+            "currmovenumber" => info.currmovenumber = Some(parse(&mut tokens, line)?),
+            "hashfull" => info.hashfull = Some(parse(&mut tokens, line)?),
+            "nps" => info.nps = Some(parse(&mut tokens, line)?),
+            "tbhits" => info.tbhits = Some(parse(&mut tokens, line)?),
+            "sbhits" => info.sbhits = Some(parse(&mut tokens, line)?),
+            "cpuload" => info.cpuload = Some(parse(&mut tokens, line)?),
+            "score" => {
+                info.score = match tokens.next() {
+                    Some("cp") => Some(UciScore::Cp(parse(&mut tokens, line)?)),
+                    Some("mate") => Some(UciScore::Mate(parse(&mut tokens, line)?)),
+                    _ => return Err(ParseUCICommandError::new("Invalid score", line)),
+                }
+            }
+            "lowerbound" => info.bound = ScoreBound::Lower,
+            "upperbound" => info.bound = ScoreBound::Upper,
+            "wdl" => {
+                info.wdl = Some((
+                    parse(&mut tokens, line)?,
+                    parse(&mut tokens, line)?,
+                    parse(&mut tokens, line)?,
+                ))
+            }
+            "currmove" => {
+                let mv = tokens.next().unwrap_or_default();
+                info.currmove = Some(parse_move(mv, board)?);
+            }
+            "pv" => info.pv = parse_line(&mut tokens, board),
+            "refutation" => info.refutation = parse_line(&mut tokens, board),
+            "currline" => {
+                // The CPU number is optional
+                tokens.next_if(|t| t.parse::<u32>().is_ok());
+                info.currline = parse_line(&mut tokens, board);
+            }
+            "string" => {
+                info.string = Some(tokens.by_ref().collect::<Vec<_>>().join(" "));
+            }
+            _ => return Err(ParseUCICommandError::new("Unknown info", line)),
+        }
+    }
+
+    Ok(UCICommand::Info(info))
+}
+
+/// Parses a line sent by an engine. Moves are parsed in the position on `board`, which is the
+/// position the engine is searching.
+/// # Example
+/// ```
+/// # use chess::{board::Board, uci::{parse_engine_command, UCICommand}};
+/// let board = Board::default();
+/// match parse_engine_command("info depth 2 score cp 15 pv e2e4 e7e5", &board) {
+///     Ok(UCICommand::Info(info)) => {
+///         assert_eq!(info.depth, Some(2));
+///         assert_eq!(info.pv.len(), 2);
+///     }
+///     _ => panic!(),
+/// }
+/// ```
+pub fn parse_engine_command(line: &str, board: &Board) -> Result<UCICommand, ParseUCICommandError> {
+    let mut tokens = line.split_whitespace();
+
+    match tokens
+        .next()
+        .ok_or(ParseUCICommandError::new("Command is empty", line))?
+    {
+        "id" => match (
+            tokens.next(),
+            line.split_once(" name ").or(line.split_once(" author ")),
+        ) {
+            (Some(key @ ("name" | "author")), Some((_, value))) => {
+                Ok(UCICommand::Id(key.to_owned(), value.trim().to_owned()))
+            }
+            _ => Err(ParseUCICommandError::new("Invalid id", line)),
+        },
+        "option" => line
+            .parse()
+            .map(UCICommand::Option)
+            .map_err(|e: SetOptionError| ParseUCICommandError::new(e.to_string().as_str(), line)),
+        "uciok" => Ok(UCICommand::UciOk),
+        "readyok" => Ok(UCICommand::ReadyOk),
+        "info" => parse_info(tokens, line, board),
+        "bestmove" => {
+            let mv = match tokens.next() {
+                // Sent by some engines when there are no legal moves
+                Some("(none)" | "0000") => Move::null(),
+                Some(mv) => parse_move(mv, board)?,
+                None => return Err(ParseUCICommandError::new("Missing best move", line)),
+            };
+            // There is no position to ponder on without a move
+            let ponder = match (tokens.next(), tokens.next()) {
+                (Some("ponder"), Some(ponder)) if mv != Move::null() => {
+                    parse_move(ponder, &board.do_move(mv)).ok()
+                }
+                _ => None,
+            };
+            Ok(UCICommand::BestMove(mv, ponder))
+        }
+        _ => Err(ParseUCICommandError::new("Unknown command", line)),
+    }
+}
+
+pub fn parse_move(mv: &str, board: &Board) -> Result<Move, ParseUCICommandError> {
+    let invalid = || ParseUCICommandError::new("Invalid move", mv);
     let mut chars = mv.chars();
 
-    let from_file: File = chars.next().ok_or(())?.try_into()?;
-    let from_rank: Rank = chars.next().ok_or(())?.try_into()?;
-    let to_file: File = chars.next().ok_or(())?.try_into()?;
-    let to_rank: Rank = chars.next().ok_or(())?.try_into()?;
+    let from_file: File = chars
+        .next()
+        .ok_or_else(invalid)?
+        .try_into()
+        .map_err(|_| invalid())?;
+    let from_rank: Rank = chars
+        .next()
+        .ok_or_else(invalid)?
+        .try_into()
+        .map_err(|_| invalid())?;
+    let to_file: File = chars
+        .next()
+        .ok_or_else(invalid)?
+        .try_into()
+        .map_err(|_| invalid())?;
+    let to_rank: Rank = chars
+        .next()
+        .ok_or_else(invalid)?
+        .try_into()
+        .map_err(|_| invalid())?;
 
     let from = Square::new(from_rank, from_file);
     let to = Square::new(to_rank, to_file);
 
     let promotion_kind: Option<PieceKind> = chars.next().and_then(|c| c.try_into().ok());
 
-    generate_moves(board)
-        .into_iter()
-        .find(|m| m.from() == from && m.to() == to && m.promotion_kind() == promotion_kind)
-        .ok_or(())
+    let piece = board.at(from).ok_or_else(invalid)?;
+    let capture = board.at(to).is_some();
+    let distance = |a: usize, b: usize| a.abs_diff(b);
+
+    let kind = match (piece.kind(), promotion_kind) {
+        (PieceKind::Pawn, Some(kind)) => match (kind, capture) {
+            (PieceKind::Knight, false) => MoveKind::PromKnight,
+            (PieceKind::Bishop, false) => MoveKind::PromBishop,
+            (PieceKind::Rook, false) => MoveKind::PromRook,
+            (PieceKind::Queen, false) => MoveKind::PromQueen,
+            (PieceKind::Knight, true) => MoveKind::PromCapKnight,
+            (PieceKind::Bishop, true) => MoveKind::PromCapBishop,
+            (PieceKind::Rook, true) => MoveKind::PromCapRook,
+            (PieceKind::Queen, true) => MoveKind::PromCapQueen,
+            _ => return Err(invalid()),
+        },
+        (_, Some(_)) => return Err(invalid()),
+        (PieceKind::King, None) if distance(from.file().to_index(), to.file().to_index()) == 2 => {
+            MoveKind::Castling
+        }
+        (PieceKind::Pawn, None)
+            if board.en_passant_square() == Some(to) && from.file() != to.file() =>
+        {
+            MoveKind::EnPassant
+        }
+        (PieceKind::Pawn, None) if distance(from.rank().to_index(), to.rank().to_index()) == 2 => {
+            MoveKind::Double
+        }
+        _ if capture => MoveKind::Cap,
+        _ => MoveKind::Quiet,
+    };
+
+    // Checking the single move is much cheaper than generating all of them
+    let mv = Move::new(from, to, kind);
+    if board.is_legal(mv) {
+        Ok(mv)
+    } else {
+        Err(invalid())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_setoption() {
+        match parse_command("setoption name Move Overhead value 100") {
+            Ok(UCICommand::SetOption(name, value)) => {
+                assert_eq!(name, "Move Overhead");
+                assert_eq!(value.as_deref(), Some("100"));
+            }
+            _ => panic!(),
+        }
+
+        match parse_command("setoption  name SyzygyPath value /path/with  two spaces") {
+            Ok(UCICommand::SetOption(name, value)) => {
+                assert_eq!(name, "SyzygyPath");
+                assert_eq!(value.as_deref(), Some("/path/with  two spaces"));
+            }
+            _ => panic!(),
+        }
+
+        match parse_command("setoption name Clear Hash") {
+            Ok(UCICommand::SetOption(name, value)) => {
+                assert_eq!(name, "Clear Hash");
+                assert_eq!(value, None);
+            }
+            _ => panic!(),
+        }
+
+        match parse_command("setoption name BookFile value  leading space.bin") {
+            Ok(UCICommand::SetOption(_, value)) => {
+                assert_eq!(value.as_deref(), Some(" leading space.bin"));
+            }
+            _ => panic!(),
+        }
+
+        assert!(parse_command("setoption value 1").is_err());
+        assert!(parse_command("setoption name").is_err());
+    }
+
+    #[test]
+    fn info_display() {
+        let board = Board::default();
+        let e4 = parse_move("e2e4", &board).unwrap();
+        let e5 = parse_move("e7e5", &board.do_move(e4)).unwrap();
+
+        let mut info = Info::new();
+        info.depth = Some(5);
+        info.seldepth = Some(9);
+        info.score = Some(UciScore::Cp(-12));
+        info.bound = ScoreBound::Upper;
+        info.nodes = Some(1000);
+        info.time = Some(10);
+        info.pv = vec![e4, e5];
+        info.string = Some("some text".to_owned());
+
+        assert_eq!(
+            UCICommand::Info(info).to_string(),
+            "info depth 5 seldepth 9 score cp -12 upperbound nodes 1000 time 10 pv e2e4 e7e5 string some text"
+        );
+
+        let mut info = Info::new();
+        info.multipv = Some(2);
+        info.score = Some(UciScore::Mate(-3));
+        info.wdl = Some((0, 0, 1000));
+        info.hashfull = Some(500);
+        assert_eq!(
+            info.to_string(),
+            "info multipv 2 score mate -3 wdl 0 0 1000 hashfull 500"
+        );
+
+        assert_eq!(
+            UCICommand::BestMove(e4, Some(e5)).to_string(),
+            "bestmove e2e4 ponder e7e5"
+        );
+        assert_eq!(UCICommand::BestMove(e4, None).to_string(), "bestmove e2e4");
+        assert_eq!(
+            UCICommand::BestMove(Move::null(), None).to_string(),
+            "bestmove 0000"
+        );
+        assert_eq!(
+            UCICommand::Id("name".to_owned(), "Test engine".to_owned()).to_string(),
+            "id name Test engine"
+        );
+    }
+
+    #[test]
+    fn parse_ponder() {
+        match parse_command("go ponder wtime 1000 btime 2000") {
+            Ok(UCICommand::Go(params)) => {
+                assert!(params.ponder);
+                assert_eq!(params.wtime, 1000);
+                assert_eq!(params.to_string(), "go ponder wtime 1000 btime 2000");
+            }
+            _ => panic!(),
+        }
+
+        assert!(matches!(
+            parse_command("ponderhit"),
+            Ok(UCICommand::PonderHit)
+        ));
+    }
+
+    #[test]
+    fn parse_perft() {
+        match parse_command("go perft 5 stats") {
+            Ok(UCICommand::Perft(params)) => {
+                assert_eq!(params.depth, 5);
+                assert!(params.stats);
+                assert_eq!(params.to_string(), "go perft 5 stats");
+            }
+            _ => panic!(),
+        }
+
+        match parse_command("go perft 6 threads 4 hash 64") {
+            Ok(UCICommand::Perft(params)) => {
+                assert_eq!((params.depth, params.threads, params.hash), (6, 4, 64));
+                assert!(!params.stats);
+                assert_eq!(params.to_string(), "go perft 6 threads 4 hash 64");
+            }
+            _ => panic!(),
+        }
+
+        assert!(parse_command("go perft 3 detailed").is_err());
+        assert!(parse_command("go perft 3 threads").is_err());
+        assert_eq!(
+            parse_command("go perft 3").unwrap().to_string(),
+            "go perft 3"
+        );
+    }
+
+    #[test]
+    fn parse_searchmoves() {
+        match parse_command("go searchmoves e2e4 d2d4 mate 3") {
+            Ok(UCICommand::Go(params)) => {
+                assert_eq!(params.searchmoves, ["e2e4", "d2d4"]);
+                assert_eq!(params.mate, Some(3));
+                assert_eq!(params.to_string(), "go mate 3 searchmoves e2e4 d2d4");
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn parse_engine_output() {
+        let board = Board::default();
+        let line = "info depth 5 seldepth 9 multipv 2 score cp -12 upperbound nodes 1000 time 10 \
+                    pv e2e4 e7e5 string some text";
+        match parse_engine_command(line, &board) {
+            Ok(UCICommand::Info(info)) => assert_eq!(info.to_string(), line),
+            _ => panic!(),
+        }
+
+        // Moves after the line ends or turns illegal are not part of it
+        match parse_engine_command("info currline 1 d2d4 d7d5 nodes 5", &board) {
+            Ok(UCICommand::Info(info)) => {
+                assert_eq!(info.currline.len(), 2);
+                assert_eq!(info.nodes, Some(5));
+            }
+            _ => panic!(),
+        }
+        assert!(parse_engine_command("info pv e2e4 e2e4", &board).is_err());
+
+        match parse_engine_command("bestmove (none)", &board) {
+            Ok(UCICommand::BestMove(mv, None)) => assert_eq!(mv.to_index(), 0),
+            _ => panic!(),
+        }
+        match parse_engine_command("bestmove 0000 ponder e7e5", &board) {
+            Ok(UCICommand::BestMove(mv, None)) => assert_eq!(mv, Move::null()),
+            _ => panic!(),
+        }
+        assert!(matches!(
+            parse_engine_command("id author Some One", &board),
+            Ok(UCICommand::Id(key, value)) if key == "author" && value == "Some One"
+        ));
+        assert!(parse_engine_command("bestmove e2e5", &board).is_err());
+        assert!(parse_engine_command("go", &board).is_err());
+    }
 }
@@ -0,0 +1,432 @@
+use std::{
+    ffi::OsStr,
+    fmt,
+    io::{self, BufRead, BufReader, Write},
+    process::{Child, ChildStdin, Command, ExitStatus, Stdio},
+    sync::{
+        mpsc::{self, Receiver, RecvTimeoutError},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use crate::{board::Board, moves::Move};
+
+use super::{
+    options::EngineOption, parse_engine_command, Info, PerftParams, SearchParams, UCICommand,
+};
+
+#[derive(Debug)]
+pub struct UciClientError {
+    msg: String,
+    illegal_move: Option<String>,
+}
+
+impl UciClientError {
+    pub fn new<S: AsRef<str>>(msg: S) -> Self {
+        Self {
+            msg: msg.as_ref().to_owned(),
+            illegal_move: None,
+        }
+    }
+
+    /// The engine answered with a best move that is not legal in the position.
+    pub fn illegal<S: AsRef<str>>(mv: S) -> Self {
+        Self {
+            msg: format!("Illegal best move '{}'", mv.as_ref()),
+            illegal_move: Some(mv.as_ref().to_owned()),
+        }
+    }
+
+    /// The best move as the engine sent it, if that is what went wrong.
+    pub fn illegal_move(&self) -> Option<&str> {
+        self.illegal_move.as_deref()
+    }
+}
+
+impl fmt::Display for UciClientError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "UCI client error: {}", self.msg)
+    }
+}
+
+impl From<io::Error> for UciClientError {
+    fn from(e: io::Error) -> Self {
+        Self::new(e.to_string())
+    }
+}
+
+/// What an engine sent during one `go`.
+#[derive(Debug)]
+pub struct SearchOutput {
+    pub infos: Vec<Info>,
+    /// Null if the engine had no move to play
+    pub best_move: Move,
+    pub ponder: Option<Move>,
+}
+
+/// Drives an external UCI engine running as a child process.
+pub struct UciClient {
+    pub name: String,
+    pub author: String,
+    pub options: Vec<EngineOption>,
+    child: Child,
+    stdin: ChildStdin,
+    lines: Receiver<String>,
+    stderr: Arc<Mutex<Vec<String>>>,
+    stderr_reader: Option<JoinHandle<()>>,
+    /// Position of the last `position` command, needed to parse moves
+    board: Board,
+}
+
+impl UciClient {
+    /// Starts `program` and performs the `uci` handshake, waiting at most `timeout` for `uciok`.
+    pub fn spawn<I, S>(program: &str, args: I, timeout: Duration) -> Result<Self, UciClientError>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| UciClientError::new(format!("Could not start '{}': {}", program, e)))?;
+
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let stderr = child.stderr.take().expect("stderr is piped");
+
+        // Output is read in threads so that reads can time out
+        let (sender, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let captured = Arc::new(Mutex::new(Vec::new()));
+        let stderr_reader = thread::spawn({
+            let captured = Arc::clone(&captured);
+            move || {
+                for line in BufReader::new(stderr).lines().map_while(Result::ok) {
+                    captured.lock().unwrap().push(line);
+                }
+            }
+        });
+
+        let mut client = Self {
+            name: String::new(),
+            author: String::new(),
+            options: Vec::new(),
+            child,
+            stdin,
+            lines,
+            stderr: captured,
+            stderr_reader: Some(stderr_reader),
+            board: Board::default(),
+        };
+
+        client.send(&UCICommand::Uci)?;
+        let deadline = Instant::now() + timeout;
+        loop {
+            match client.receive(deadline)? {
+                UCICommand::Id(key, value) if key == "name" => client.name = value,
+                UCICommand::Id(_, value) => client.author = value,
+                UCICommand::Option(option) => client.options.push(option),
+                UCICommand::UciOk => break,
+                _ => (),
+            }
+        }
+
+        Ok(client)
+    }
+
+    pub fn send(&mut self, command: &UCICommand) -> Result<(), UciClientError> {
+        writeln!(self.stdin, "{}", command)?;
+        self.stdin.flush()?;
+        Ok(())
+    }
+
+    fn receive_line(&mut self, deadline: Instant) -> Result<String, UciClientError> {
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        self.lines.recv_timeout(timeout).map_err(|e| match e {
+            RecvTimeoutError::Timeout => UciClientError::new("Timed out waiting for engine"),
+            RecvTimeoutError::Disconnected => UciClientError::new("Engine closed its output"),
+        })
+    }
+
+    /// Waits for the next command the engine sends. Lines that are not valid UCI are skipped,
+    /// but a best move that can't be read in the current position is an error.
+    fn receive(&mut self, deadline: Instant) -> Result<UCICommand, UciClientError> {
+        loop {
+            let line = self.receive_line(deadline)?;
+            let mut tokens = line.split_whitespace();
+
+            match parse_engine_command(&line, &self.board) {
+                Ok(command) => return Ok(command),
+                Err(_) if tokens.next() == Some("bestmove") => {
+                    return Err(UciClientError::illegal(tokens.next().unwrap_or_default()));
+                }
+                Err(_) => (),
+            }
+        }
+    }
+
+    pub fn set_option(&mut self, name: &str, value: Option<&str>) -> Result<(), UciClientError> {
+        self.send(&UCICommand::SetOption(
+            name.to_owned(),
+            value.map(|v| v.to_owned()),
+        ))
+    }
+
+    /// Sends `isready` and waits at most `timeout` for `readyok`.
+    pub fn is_ready(&mut self, timeout: Duration) -> Result<(), UciClientError> {
+        self.send(&UCICommand::IsReady)?;
+        let deadline = Instant::now() + timeout;
+        while !matches!(self.receive(deadline)?, UCICommand::ReadyOk) {}
+        Ok(())
+    }
+
+    pub fn new_game(&mut self) -> Result<(), UciClientError> {
+        self.send(&UCICommand::UciNewGame)
+    }
+
+    /// Sets the position to `board` followed by `moves`.
+    pub fn set_position(&mut self, board: &Board, moves: &[Move]) -> Result<(), UciClientError> {
+        self.send(&UCICommand::Position(
+            board.fen(),
+            moves.iter().map(|mv| mv.to_string()).collect(),
+        ))?;
+
+        self.board = moves.iter().fold(board.clone(), |b, &mv| b.do_move(mv));
+        Ok(())
+    }
+
+    /// Starts a search and collects its output until `bestmove`. If it has not arrived within
+    /// `timeout`, the search is stopped and the engine gets another `timeout` to reply.
+    pub fn go(
+        &mut self,
+        params: &SearchParams,
+        timeout: Duration,
+    ) -> Result<SearchOutput, UciClientError> {
+        writeln!(self.stdin, "{}", params)?;
+        self.stdin.flush()?;
+
+        let mut infos = Vec::new();
+        let mut deadline = Instant::now() + timeout;
+        let mut stopped = false;
+        loop {
+            let command = match self.receive(deadline) {
+                Err(_) if !stopped && Instant::now() >= deadline => {
+                    self.stop()?;
+                    stopped = true;
+                    deadline = Instant::now() + timeout;
+                    continue;
+                }
+                result => result?,
+            };
+
+            match command {
+                UCICommand::Info(info) => infos.push(info),
+                UCICommand::BestMove(best_move, ponder) => {
+                    // The null move is left to the caller, who knows whether the game is over
+                    if best_move != Move::null() && !self.board.legal_moves().contains(&best_move) {
+                        return Err(UciClientError::illegal(best_move.to_string()));
+                    }
+                    return Ok(SearchOutput {
+                        infos,
+                        best_move,
+                        ponder,
+                    });
+                }
+                _ => (),
+            }
+        }
+    }
+
+    /// Runs the `go perft` debug command most engines support and returns the node count of
+    /// every move, as printed in `<move>: <nodes>` lines. The output is taken to be complete
+    /// once the engine answers the `isready` sent after it.
+    pub fn perft(
+        &mut self,
+        depth: usize,
+        timeout: Duration,
+    ) -> Result<Vec<(String, u64)>, UciClientError> {
+        self.send(&UCICommand::Perft(PerftParams {
+            depth,
+            ..Default::default()
+        }))?;
+        self.send(&UCICommand::IsReady)?;
+
+        let deadline = Instant::now() + timeout;
+        let mut divide = Vec::new();
+        loop {
+            let line = self.receive_line(deadline)?;
+            if line.trim() == "readyok" {
+                return Ok(divide);
+            }
+
+            if let Some((mv, nodes)) = line.split_once(':') {
+                let is_move = mv.len() >= 4 && mv.len() <= 5 && mv.is_ascii();
+                if let (true, Ok(nodes)) = (is_move, nodes.trim().parse()) {
+                    divide.push((mv.to_owned(), nodes));
+                }
+            }
+        }
+    }
+
+    pub fn stop(&mut self) -> Result<(), UciClientError> {
+        self.send(&UCICommand::Stop)
+    }
+
+    /// Lines the engine has written to stderr so far.
+    pub fn stderr(&self) -> Vec<String> {
+        self.stderr.lock().unwrap().clone()
+    }
+
+    /// Sends `quit` and waits at most `timeout` for the engine to exit before killing it.
+    pub fn quit(&mut self, timeout: Duration) -> Result<ExitStatus, UciClientError> {
+        // The engine may be gone already
+        let _ = self.send(&UCICommand::Quit);
+
+        let deadline = Instant::now() + timeout;
+        let status = loop {
+            if let Some(status) = self.child.try_wait()? {
+                break status;
+            }
+            if Instant::now() >= deadline {
+                self.child.kill()?;
+                break self.child.wait()?;
+            }
+            thread::sleep(Duration::from_millis(5));
+        };
+
+        if let Some(reader) = self.stderr_reader.take() {
+            let _ = reader.join();
+        }
+
+        Ok(status)
+    }
+}
+
+impl Drop for UciClient {
+    fn drop(&mut self) {
+        if let Ok(None) = self.child.try_wait() {
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::uci::UciScore;
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// A stand-in engine that answers with canned output.
+    const SCRIPT: &str = r#"
+        while read command rest; do
+            case $command in
+                uci)
+                    echo "id name Scripted Engine"
+                    echo "id author Someone"
+                    echo "option name Hash type spin default 16 min 1 max 64"
+                    echo "option name Book File type string default <empty>"
+                    echo "uciok" ;;
+                isready) echo "readyok" ;;
+                go)
+                    if [ "${rest%% *}" = perft ]; then
+                        echo "e2e4: 20"
+                        echo "g1f3: 21"
+                        echo
+                        echo "Nodes searched: 41"
+                        continue
+                    fi
+                    echo "not uci"
+                    echo "info depth 1 score cp 20 nodes 30 pv e2e4 e7e5"
+                    echo "info string searching hard"
+                    echo "warning: $rest" >&2
+                    echo "bestmove e2e4 ponder e7e5" ;;
+                quit) exit 0 ;;
+            esac
+        done
+    "#;
+
+    #[test]
+    fn scripted_engine() {
+        let mut client = UciClient::spawn("sh", ["-c", SCRIPT], TIMEOUT).unwrap();
+        assert_eq!(client.name, "Scripted Engine");
+        assert_eq!(client.author, "Someone");
+        assert_eq!(client.options.len(), 2);
+        assert_eq!(client.options[1], EngineOption::string("Book File", ""));
+
+        client.set_option("Hash", Some("32")).unwrap();
+        client.new_game().unwrap();
+        client.is_ready(TIMEOUT).unwrap();
+
+        let mut params = SearchParams::new();
+        params.depth = Some(1);
+        client.set_position(&Board::default(), &[]).unwrap();
+        let output = client.go(&params, TIMEOUT).unwrap();
+
+        assert_eq!(output.best_move.to_string(), "e2e4");
+        assert_eq!(
+            output.ponder.map(|mv| mv.to_string()),
+            Some("e7e5".to_owned())
+        );
+        assert_eq!(output.infos.len(), 2);
+        assert!(matches!(output.infos[0].score, Some(UciScore::Cp(20))));
+        assert_eq!(output.infos[0].pv.len(), 2);
+        assert_eq!(output.infos[1].string.as_deref(), Some("searching hard"));
+
+        let divide = client.perft(2, TIMEOUT).unwrap();
+        assert_eq!(divide, [("e2e4".to_owned(), 20), ("g1f3".to_owned(), 21)]);
+
+        assert!(client.quit(TIMEOUT).unwrap().success());
+        assert_eq!(client.stderr(), ["warning: depth 1"]);
+    }
+
+    #[test]
+    fn illegal_best_move() {
+        // Answers every search with the move given as argument
+        const SCRIPT: &str = r#"
+            while read command rest; do
+                case $command in
+                    uci) echo "uciok" ;;
+                    go) echo "bestmove $1" ;;
+                    quit) exit 0 ;;
+                esac
+            done
+        "#;
+        let mut params = SearchParams::new();
+        params.depth = Some(1);
+
+        // Unreadable in the position, and readable but illegal
+        for mv in ["e2e5", "e1e2", "zzzz"] {
+            let mut client = UciClient::spawn("sh", ["-c", SCRIPT, "sh", mv], TIMEOUT).unwrap();
+            let start = Instant::now();
+            let e = client.go(&params, TIMEOUT).unwrap_err();
+
+            assert_eq!(e.illegal_move(), Some(mv));
+            assert!(start.elapsed() < TIMEOUT);
+        }
+
+        let mut client = UciClient::spawn("sh", ["-c", SCRIPT, "sh", "e2e4"], TIMEOUT).unwrap();
+        let output = client.go(&params, TIMEOUT).unwrap();
+        assert_eq!(output.best_move.to_string(), "e2e4");
+    }
+
+    #[test]
+    fn handshake_timeout() {
+        let result = UciClient::spawn("sh", ["-c", "cat > /dev/null"], Duration::from_millis(50));
+        assert!(result.is_err());
+        assert!(UciClient::spawn("nonexistent-engine", [] as [&str; 0], TIMEOUT).is_err());
+    }
+}
@@ -0,0 +1,371 @@
+use std::{fmt, str::FromStr};
+
+#[derive(Debug)]
+pub struct SetOptionError {
+    msg: String,
+}
+
+impl SetOptionError {
+    pub fn new<S: AsRef<str>>(msg: S) -> Self {
+        Self {
+            msg: msg.as_ref().to_owned(),
+        }
+    }
+}
+
+impl fmt::Display for SetOptionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Error setting option: {}", self.msg)
+    }
+}
+
+/// The UCI option types and their defaults and limits.
+#[derive(Debug, Clone, PartialEq)]
+pub enum OptionKind {
+    Check { default: bool },
+    Spin { default: i64, min: i64, max: i64 },
+    Combo { default: String, vars: Vec<String> },
+    Button,
+    String { default: String },
+}
+
+/// Current value of an option.
+#[derive(Debug, Clone, PartialEq)]
+pub enum OptionValue {
+    Check(bool),
+    Spin(i64),
+    Combo(String),
+    Button,
+    String(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EngineOption {
+    pub name: String,
+    pub kind: OptionKind,
+}
+
+impl EngineOption {
+    pub fn check(name: &str, default: bool) -> Self {
+        Self {
+            name: name.to_owned(),
+            kind: OptionKind::Check { default },
+        }
+    }
+
+    pub fn spin(name: &str, default: i64, min: i64, max: i64) -> Self {
+        Self {
+            name: name.to_owned(),
+            kind: OptionKind::Spin { default, min, max },
+        }
+    }
+
+    pub fn combo(name: &str, default: &str, vars: &[&str]) -> Self {
+        Self {
+            name: name.to_owned(),
+            kind: OptionKind::Combo {
+                default: default.to_owned(),
+                vars: vars.iter().map(|&v| v.to_owned()).collect(),
+            },
+        }
+    }
+
+    pub fn button(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            kind: OptionKind::Button,
+        }
+    }
+
+    pub fn string(name: &str, default: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            kind: OptionKind::String {
+                default: default.to_owned(),
+            },
+        }
+    }
+
+    pub fn default_value(&self) -> OptionValue {
+        match &self.kind {
+            OptionKind::Check { default } => OptionValue::Check(*default),
+            OptionKind::Spin { default, .. } => OptionValue::Spin(*default),
+            OptionKind::Combo { default, .. } => OptionValue::Combo(default.clone()),
+            OptionKind::Button => OptionValue::Button,
+            OptionKind::String { default } => OptionValue::String(default.clone()),
+        }
+    }
+
+    /// Parses and validates the value given in a `setoption` command.
+    pub fn parse_value(&self, value: Option<&str>) -> Result<OptionValue, SetOptionError> {
+        let missing = || SetOptionError::new(format!("Missing value for '{}'", self.name));
+
+        match &self.kind {
+            OptionKind::Check { .. } => match value.ok_or_else(missing)? {
+                "true" => Ok(OptionValue::Check(true)),
+                "false" => Ok(OptionValue::Check(false)),
+                v => Err(SetOptionError::new(format!(
+                    "'{}' is not a boolean for '{}'",
+                    v, self.name
+                ))),
+            },
+            OptionKind::Spin { min, max, .. } => {
+                let v = value.ok_or_else(missing)?;
+                match v.parse::<i64>() {
+                    Ok(n) if (*min..=*max).contains(&n) => Ok(OptionValue::Spin(n)),
+                    _ => Err(SetOptionError::new(format!(
+                        "'{}' is not in range {}..{} for '{}'",
+                        v, min, max, self.name
+                    ))),
+                }
+            }
+            OptionKind::Combo { vars, .. } => {
+                let v = value.ok_or_else(missing)?;
+                vars.iter()
+                    .find(|var| var.eq_ignore_ascii_case(v))
+                    .map(|var| OptionValue::Combo(var.clone()))
+                    .ok_or_else(|| {
+                        SetOptionError::new(format!("'{}' is not valid for '{}'", v, self.name))
+                    })
+            }
+            OptionKind::Button => Ok(OptionValue::Button),
+            OptionKind::String { .. } => match value {
+                None | Some("<empty>") => Ok(OptionValue::String(String::new())),
+                Some(v) => Ok(OptionValue::String(v.to_owned())),
+            },
+        }
+    }
+}
+
+fn or_empty(s: &str) -> &str {
+    if s.is_empty() {
+        "<empty>"
+    } else {
+        s
+    }
+}
+
+impl fmt::Display for EngineOption {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "option name {} type ", self.name)?;
+        match &self.kind {
+            OptionKind::Check { default } => write!(f, "check default {}", default),
+            OptionKind::Spin { default, min, max } => {
+                write!(f, "spin default {} min {} max {}", default, min, max)
+            }
+            OptionKind::Combo { default, vars } => {
+                write!(f, "combo default {}", default)?;
+                for var in vars {
+                    write!(f, " var {}", var)?;
+                }
+                Ok(())
+            }
+            OptionKind::Button => write!(f, "button"),
+            OptionKind::String { default } => write!(f, "string default {}", or_empty(default)),
+        }
+    }
+}
+
+/// Parses an `option` line sent by an engine. Names and values may contain spaces.
+impl FromStr for EngineOption {
+    type Err = SetOptionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        const KEYWORDS: [&str; 6] = ["name", "type", "default", "min", "max", "var"];
+        let invalid = |msg: &str| SetOptionError::new(format!("{} in '{}'", msg, s));
+
+        let mut tokens = s.split_whitespace();
+        if tokens.next() != Some("option") {
+            return Err(invalid("Not an option"));
+        }
+
+        // Each keyword is followed by a value of one or more tokens
+        let mut fields: Vec<(&str, Vec<&str>)> = Vec::new();
+        for token in tokens {
+            match fields.last_mut() {
+                Some((_, value)) if !KEYWORDS.contains(&token) => value.push(token),
+                _ if KEYWORDS.contains(&token) => fields.push((token, Vec::new())),
+                _ => return Err(invalid("Unexpected token")),
+            }
+        }
+
+        let field = |keyword: &str| {
+            fields
+                .iter()
+                .find(|(k, _)| *k == keyword)
+                .map(|(_, value)| value.join(" "))
+        };
+        let number = |keyword: &str| {
+            field(keyword)
+                .and_then(|v| v.parse::<i64>().ok())
+                .ok_or_else(|| invalid(&format!("Invalid {}", keyword)))
+        };
+
+        let name = field("name")
+            .filter(|name| !name.is_empty())
+            .ok_or_else(|| invalid("Missing name"))?;
+        let default = field("default");
+
+        let kind = match field("type").as_deref() {
+            Some("check") => OptionKind::Check {
+                default: default.as_deref() == Some("true"),
+            },
+            Some("spin") => OptionKind::Spin {
+                default: number("default")?,
+                min: number("min")?,
+                max: number("max")?,
+            },
+            Some("combo") => OptionKind::Combo {
+                default: default.unwrap_or_default(),
+                vars: fields
+                    .iter()
+                    .filter(|(k, _)| *k == "var")
+                    .map(|(_, value)| value.join(" "))
+                    .collect(),
+            },
+            Some("button") => OptionKind::Button,
+            Some("string") => OptionKind::String {
+                default: match default.as_deref() {
+                    None | Some("<empty>") => String::new(),
+                    Some(v) => v.to_owned(),
+                },
+            },
+            _ => return Err(invalid("Invalid type")),
+        };
+
+        Ok(Self { name, kind })
+    }
+}
+
+/// Registry of the options an engine supports together with their current values.
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    options: Vec<(EngineOption, OptionValue)>,
+}
+
+impl Options {
+    pub fn new(options: Vec<EngineOption>) -> Self {
+        Self {
+            options: options
+                .into_iter()
+                .map(|o| {
+                    let value = o.default_value();
+                    (o, value)
+                })
+                .collect(),
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &EngineOption> {
+        self.options.iter().map(|(o, _)| o)
+    }
+
+    /// Returns the current value of option `name`. Option names are case insensitive.
+    pub fn get(&self, name: &str) -> Option<&OptionValue> {
+        self.options
+            .iter()
+            .find(|(o, _)| o.name.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v)
+    }
+
+    /// Validates and stores a new value for option `name`. Returns the option with the
+    /// name spelled as it was declared, and the new value.
+    pub fn set(
+        &mut self,
+        name: &str,
+        value: Option<&str>,
+    ) -> Result<(&EngineOption, &OptionValue), SetOptionError> {
+        let (option, current) = self
+            .options
+            .iter_mut()
+            .find(|(o, _)| o.name.eq_ignore_ascii_case(name))
+            .ok_or_else(|| SetOptionError::new(format!("No such option '{}'", name)))?;
+
+        *current = option.parse_value(value)?;
+
+        Ok((option, current))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn option_display() {
+        assert_eq!(
+            EngineOption::spin("Hash", 16, 1, 1024).to_string(),
+            "option name Hash type spin default 16 min 1 max 1024"
+        );
+        assert_eq!(
+            EngineOption::string("SyzygyPath", "").to_string(),
+            "option name SyzygyPath type string default <empty>"
+        );
+        assert_eq!(
+            EngineOption::combo("Style", "Normal", &["Solid", "Normal"]).to_string(),
+            "option name Style type combo default Normal var Solid var Normal"
+        );
+        assert_eq!(
+            EngineOption::button("Clear Hash").to_string(),
+            "option name Clear Hash type button"
+        );
+    }
+
+    #[test]
+    fn option_parse() {
+        let options = [
+            EngineOption::spin("Hash", 16, 1, 1024),
+            EngineOption::check("Ponder", false),
+            EngineOption::string("SyzygyPath", ""),
+            EngineOption::string("Book File", "book.bin"),
+            EngineOption::combo("Style", "Very Solid", &["Very Solid", "Normal"]),
+            EngineOption::button("Clear Hash"),
+        ];
+        for option in options {
+            assert_eq!(option.to_string().parse::<EngineOption>().unwrap(), option);
+        }
+
+        assert!("option name Hash type spin default 16"
+            .parse::<EngineOption>()
+            .is_err());
+        assert!("option type button".parse::<EngineOption>().is_err());
+        assert!("id name x".parse::<EngineOption>().is_err());
+    }
+
+    #[test]
+    fn option_values() {
+        let mut options = Options::new(vec![
+            EngineOption::spin("Move Overhead", 10, 0, 5000),
+            EngineOption::check("Ponder", false),
+            EngineOption::combo("Style", "Normal", &["Solid", "Normal"]),
+            EngineOption::string("SyzygyPath", ""),
+        ]);
+
+        let (option, value) = options.set("move overhead", Some("100")).unwrap();
+        assert_eq!(option.name, "Move Overhead");
+        assert_eq!(value, &OptionValue::Spin(100));
+        assert!(options.set("Move Overhead", Some("5001")).is_err());
+        assert!(options.set("Move Overhead", Some("x")).is_err());
+        assert!(options.set("Move Overhead", None).is_err());
+        assert_eq!(options.get("Move Overhead"), Some(&OptionValue::Spin(100)));
+
+        assert!(options.set("Ponder", Some("yes")).is_err());
+        options.set("Ponder", Some("true")).unwrap();
+        assert_eq!(options.get("ponder"), Some(&OptionValue::Check(true)));
+
+        options.set("Style", Some("solid")).unwrap();
+        assert_eq!(
+            options.get("Style"),
+            Some(&OptionValue::Combo("Solid".to_owned()))
+        );
+        assert!(options.set("Style", Some("Wild")).is_err());
+
+        options.set("SyzygyPath", Some("/tb/a b")).unwrap();
+        assert_eq!(
+            options.get("SyzygyPath"),
+            Some(&OptionValue::String("/tb/a b".to_owned()))
+        );
+
+        assert!(options.set("Nonexistent", None).is_err());
+    }
+}
@@ -0,0 +1,314 @@
+use std::{fmt, str::FromStr};
+
+use crate::{moves::Move, uci::UciScore};
+
+/// Time control set by `level`. Times are in milliseconds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Level {
+    /// Moves per time control, or zero for the whole game.
+    pub moves: u32,
+    pub base: u32,
+    pub increment: u32,
+}
+
+/// Thinking output, sent after every iteration when `post` is on.
+#[derive(Debug, Clone)]
+pub struct Thinking {
+    pub depth: u32,
+    pub score: UciScore,
+    /// Time in centiseconds
+    pub time: u32,
+    pub nodes: u64,
+    pub pv: Vec<Move>,
+}
+
+#[derive(Debug)]
+pub enum XBoardCommand {
+    // To engine
+    XBoard,
+    Protover(u32),
+    New,
+    Force,
+    Go,
+    UserMove(String),
+    Level(Level),
+    St(u32),
+    Sd(u8),
+    Time(u32),
+    Otim(u32),
+    Undo,
+    Remove,
+    SetBoard(String),
+    Post,
+    NoPost,
+    Analyze,
+    Exit,
+    Result(String),
+    Ping(u32),
+    MoveNow,
+    Quit,
+    /// Commands that need no action, e.g. `accepted` or `random`.
+    Ignored,
+
+    // From engine
+    Features(Vec<(String, String)>),
+    Move(Move),
+    Pong(u32),
+    Thinking(Thinking),
+    IllegalMove(String),
+    Error(String, String),
+}
+
+impl fmt::Display for XBoardCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            XBoardCommand::XBoard => write!(f, "xboard"),
+            XBoardCommand::Protover(version) => write!(f, "protover {}", version),
+            XBoardCommand::New => write!(f, "new"),
+            XBoardCommand::Force => write!(f, "force"),
+            XBoardCommand::Go => write!(f, "go"),
+            XBoardCommand::UserMove(mv) => write!(f, "usermove {}", mv),
+            XBoardCommand::Level(level) => {
+                let seconds = level.base / 1000;
+                write!(f, "level {} {}", level.moves, seconds / 60)?;
+                if seconds % 60 != 0 {
+                    write!(f, ":{:02}", seconds % 60)?;
+                }
+                write!(f, " {}", level.increment as f64 / 1000.0)
+            }
+            XBoardCommand::St(seconds) => write!(f, "st {}", seconds),
+            XBoardCommand::Sd(depth) => write!(f, "sd {}", depth),
+            XBoardCommand::Time(cs) => write!(f, "time {}", cs),
+            XBoardCommand::Otim(cs) => write!(f, "otim {}", cs),
+            XBoardCommand::Undo => write!(f, "undo"),
+            XBoardCommand::Remove => write!(f, "remove"),
+            XBoardCommand::SetBoard(fen) => write!(f, "setboard {}", fen),
+            XBoardCommand::Post => write!(f, "post"),
+            XBoardCommand::NoPost => write!(f, "nopost"),
+            XBoardCommand::Analyze => write!(f, "analyze"),
+            XBoardCommand::Exit => write!(f, "exit"),
+            XBoardCommand::Result(result) => write!(f, "result {}", result),
+            XBoardCommand::Ping(n) => write!(f, "ping {}", n),
+            XBoardCommand::MoveNow => write!(f, "?"),
+            XBoardCommand::Quit => write!(f, "quit"),
+            XBoardCommand::Ignored => Ok(()),
+            XBoardCommand::Features(features) => {
+                write!(f, "feature")?;
+                for (name, value) in features {
+                    write!(f, " {}={}", name, value)?;
+                }
+                Ok(())
+            }
+            XBoardCommand::Move(mv) => write!(f, "move {}", mv),
+            XBoardCommand::Pong(n) => write!(f, "pong {}", n),
+            XBoardCommand::Thinking(thinking) => {
+                // Mate scores are given as 100000 + moves to mate
+                let score = match thinking.score {
+                    UciScore::Cp(cp) => cp,
+                    UciScore::Mate(moves) if moves > 0 => 100000 + moves,
+                    UciScore::Mate(moves) => -100000 + moves,
+                };
+                write!(
+                    f,
+                    "{} {} {} {}",
+                    thinking.depth, score, thinking.time, thinking.nodes
+                )?;
+                for mv in &thinking.pv {
+                    write!(f, " {}", mv)?;
+                }
+                Ok(())
+            }
+            XBoardCommand::IllegalMove(mv) => write!(f, "Illegal move: {}", mv),
+            XBoardCommand::Error(msg, command) => write!(f, "Error ({}): {}", msg, command),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ParseXBoardCommandError {
+    msg: String,
+    line: String,
+}
+
+impl ParseXBoardCommandError {
+    pub fn new<S: AsRef<str>>(msg: S, line: S) -> Self {
+        Self {
+            msg: msg.as_ref().to_owned(),
+            line: line.as_ref().to_owned(),
+        }
+    }
+
+    /// The reply the GUI expects for a command the engine did not understand.
+    pub fn reply(&self) -> XBoardCommand {
+        XBoardCommand::Error(self.msg.clone(), self.line.clone())
+    }
+}
+
+impl fmt::Display for ParseXBoardCommandError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "XBoard parse error: {}, command was '{}'",
+            self.msg, self.line
+        )
+    }
+}
+
+fn parse<'a, I, T>(tokens: &mut I, name: &str, line: &str) -> Result<T, ParseXBoardCommandError>
+where
+    I: Iterator<Item = &'a str>,
+    T: FromStr,
+{
+    tokens
+        .next()
+        .and_then(|t| t.parse().ok())
+        .ok_or_else(|| ParseXBoardCommandError::new(format!("invalid {}", name).as_str(), line))
+}
+
+/// Parses a time given as minutes or `minutes:seconds` into milliseconds.
+fn parse_minutes(token: &str) -> Option<u32> {
+    let (minutes, seconds) = token.split_once(':').unwrap_or((token, "0"));
+    Some((minutes.parse::<u32>().ok()? * 60 + seconds.parse::<u32>().ok()?) * 1000)
+}
+
+fn parse_level<'a, I>(mut tokens: I, line: &str) -> Result<XBoardCommand, ParseXBoardCommandError>
+where
+    I: Iterator<Item = &'a str>,
+{
+    let invalid = || ParseXBoardCommandError::new("invalid time control", line);
+
+    let moves = tokens
+        .next()
+        .and_then(|t| t.parse().ok())
+        .ok_or_else(invalid)?;
+    let base = tokens.next().and_then(parse_minutes).ok_or_else(invalid)?;
+    let increment = tokens
+        .next()
+        .and_then(|t| t.parse::<f64>().ok())
+        .ok_or_else(invalid)?;
+
+    Ok(XBoardCommand::Level(Level {
+        moves,
+        base,
+        increment: (increment * 1000.0) as u32,
+    }))
+}
+
+/// Parses a command sent by the GUI. Moves without `usermove` are accepted as well.
+/// # Example
+/// ```
+/// # use chess::xboard::{parse_command, XBoardCommand};
+/// assert!(matches!(parse_command("usermove e2e4"), Ok(XBoardCommand::UserMove(mv)) if mv == "e2e4"));
+/// assert!(matches!(parse_command("e7e5"), Ok(XBoardCommand::UserMove(mv)) if mv == "e7e5"));
+/// assert!(parse_command("fly").is_err());
+/// ```
+pub fn parse_command(line: &str) -> Result<XBoardCommand, ParseXBoardCommandError> {
+    let mut tokens = line.split_whitespace();
+    let command = tokens
+        .next()
+        .ok_or(ParseXBoardCommandError::new("empty command", line))?;
+
+    match command {
+        "xboard" => Ok(XBoardCommand::XBoard),
+        "protover" => Ok(XBoardCommand::Protover(parse(
+            &mut tokens,
+            "version",
+            line,
+        )?)),
+        "new" => Ok(XBoardCommand::New),
+        "force" => Ok(XBoardCommand::Force),
+        "go" => Ok(XBoardCommand::Go),
+        "st" => Ok(XBoardCommand::St(parse(&mut tokens, "time", line)?)),
+        "sd" => Ok(XBoardCommand::Sd(parse(&mut tokens, "depth", line)?)),
+        "time" => Ok(XBoardCommand::Time(parse(&mut tokens, "time", line)?)),
+        "otim" => Ok(XBoardCommand::Otim(parse(&mut tokens, "time", line)?)),
+        "ping" => Ok(XBoardCommand::Ping(parse(&mut tokens, "ping", line)?)),
+        "level" => parse_level(line.split_whitespace().skip(1), line),
+        "undo" => Ok(XBoardCommand::Undo),
+        "remove" => Ok(XBoardCommand::Remove),
+        "post" => Ok(XBoardCommand::Post),
+        "nopost" => Ok(XBoardCommand::NoPost),
+        "analyze" => Ok(XBoardCommand::Analyze),
+        "exit" => Ok(XBoardCommand::Exit),
+        "?" => Ok(XBoardCommand::MoveNow),
+        "quit" => Ok(XBoardCommand::Quit),
+        "usermove" => match tokens.next() {
+            Some(mv) => Ok(XBoardCommand::UserMove(mv.to_owned())),
+            None => Err(ParseXBoardCommandError::new("missing move", line)),
+        },
+        "setboard" | "result" => {
+            let rest = line[command.len()..].trim().to_owned();
+            if command == "setboard" {
+                Ok(XBoardCommand::SetBoard(rest))
+            } else {
+                Ok(XBoardCommand::Result(rest))
+            }
+        }
+        "accepted" | "rejected" | "random" | "computer" | "name" | "rating" | "ics" | "hard"
+        | "easy" | "white" | "black" | "draw" | "." | "hint" | "bk" => Ok(XBoardCommand::Ignored),
+        // Moves in coordinate notation, e.g. e2e4 or a7a8q
+        mv if mv.len() >= 4
+            && mv.as_bytes()[0].is_ascii_lowercase()
+            && mv.as_bytes()[1].is_ascii_digit() =>
+        {
+            Ok(XBoardCommand::UserMove(mv.to_owned()))
+        }
+        _ => Err(ParseXBoardCommandError::new("unknown command", line)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_time_controls() {
+        match parse_command("level 40 5 0") {
+            Ok(XBoardCommand::Level(level)) => assert_eq!(
+                level,
+                Level {
+                    moves: 40,
+                    base: 300_000,
+                    increment: 0
+                }
+            ),
+            _ => panic!(),
+        }
+
+        match parse_command("level 0 2:30 1.5") {
+            Ok(XBoardCommand::Level(level)) => {
+                assert_eq!(level.base, 150_000);
+                assert_eq!(level.increment, 1500);
+                assert_eq!(XBoardCommand::Level(level).to_string(), "level 0 2:30 1.5");
+            }
+            _ => panic!(),
+        }
+
+        assert!(matches!(parse_command("st 5"), Ok(XBoardCommand::St(5))));
+        assert!(matches!(
+            parse_command("time 6000"),
+            Ok(XBoardCommand::Time(6000))
+        ));
+        assert!(parse_command("sd x").is_err());
+        assert!(parse_command("level 40 5").is_err());
+    }
+
+    #[test]
+    fn thinking_display() {
+        let thinking = Thinking {
+            depth: 5,
+            score: UciScore::Mate(-2),
+            time: 120,
+            nodes: 5000,
+            pv: Vec::new(),
+        };
+        assert_eq!(
+            XBoardCommand::Thinking(thinking).to_string(),
+            "5 -100002 120 5000"
+        );
+        assert_eq!(
+            parse_command("error").unwrap_err().reply().to_string(),
+            "Error (unknown command): error"
+        );
+    }
+}